@@ -0,0 +1,251 @@
+//! A scripted USI engine for exercising the engine subsystem, by hand and
+//! from `tests/engine_session.rs`.
+//!
+//! It answers the handshake, plays the first legal move it finds and can be
+//! told to misbehave:
+//!
+//! ```text
+//! cargo build --example fake_usi_engine
+//! target/debug/examples/fake_usi_engine [--delay-ms N] [--stall-ms N] [--crash-on CMD] [--hang-on CMD] [--resign]
+//! ```
+//!
+//! `--crash-on go` exits without a reply when `go` arrives, `--hang-on isready`
+//! never answers `isready`, and so on for any command word. `--stall-ms N`
+//! spends N ms on every search and does not read `stop` meanwhile.
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+use shogi::bitboard::Factory as BBFactory;
+use shogi::{Move, Piece, PieceType, Position, Square};
+
+const START_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+const HAND_PIECE_TYPES: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+#[derive(Default)]
+struct Script {
+    delay: Duration,
+    stall: Duration,
+    crash_on: Option<String>,
+    hang_on: Option<String>,
+    resign: bool,
+}
+
+fn main() {
+    BBFactory::init();
+    let script = parse_args();
+    let stdin = io::stdin();
+    let mut out = io::stdout();
+    let mut pos = Position::new();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let command = line.split_whitespace().next().unwrap_or("");
+        if script.crash_on.as_deref() == Some(command) {
+            std::process::exit(3);
+        }
+        if script.hang_on.as_deref() == Some(command) {
+            continue;
+        }
+        thread::sleep(script.delay);
+
+        match command {
+            "usi" => {
+                let _ = writeln!(out, "id name FakeEngine");
+                let _ = writeln!(out, "id author rshogi");
+                let _ = writeln!(
+                    out,
+                    "option name USI_Hash type spin default 16 min 1 max 1024"
+                );
+                let _ = writeln!(
+                    out,
+                    "option name Style type combo default Normal var Normal var Aggressive"
+                );
+                let _ = writeln!(out, "usiok");
+            }
+            "isready" => {
+                let _ = writeln!(out, "readyok");
+            }
+            "position" => {
+                if let Some(next) = load_position(&line) {
+                    pos = next;
+                }
+            }
+            "go" => {
+                thread::sleep(script.stall);
+                let best = if script.resign {
+                    None
+                } else {
+                    first_legal_move(&mut pos)
+                };
+                match best {
+                    Some(mv) => {
+                        let usi = move_to_usi(mv);
+                        let _ =
+                            writeln!(out, "info depth 1 seldepth 1 score cp 0 nodes 1 pv {usi}");
+                        let _ = writeln!(out, "bestmove {usi}");
+                    }
+                    None => {
+                        let _ = writeln!(out, "bestmove resign");
+                    }
+                }
+            }
+            "quit" => break,
+            _ => {}
+        }
+        let _ = out.flush();
+    }
+}
+
+fn parse_args() -> Script {
+    let mut script = Script::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--delay-ms" => {
+                let ms = args.next().and_then(|v| v.parse().ok()).unwrap_or(0);
+                script.delay = Duration::from_millis(ms);
+            }
+            "--stall-ms" => {
+                let ms = args.next().and_then(|v| v.parse().ok()).unwrap_or(0);
+                script.stall = Duration::from_millis(ms);
+            }
+            "--crash-on" => script.crash_on = args.next(),
+            "--hang-on" => script.hang_on = args.next(),
+            "--resign" => script.resign = true,
+            other => eprintln!("unknown argument `{other}`"),
+        }
+    }
+    script
+}
+
+fn load_position(line: &str) -> Option<Position> {
+    let mut tokens = line.split_whitespace().skip(1);
+    let sfen = match tokens.next()? {
+        "startpos" => {
+            tokens.next();
+            START_SFEN.to_string()
+        }
+        "sfen" => tokens
+            .by_ref()
+            .take_while(|token| *token != "moves")
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => return None,
+    };
+    let mut pos = Position::new();
+    pos.set_sfen(&sfen).ok()?;
+    for token in tokens {
+        pos.make_move(parse_move(token)?).ok()?;
+    }
+    Some(pos)
+}
+
+fn first_legal_move(pos: &mut Position) -> Option<Move> {
+    let side = pos.side_to_move();
+    let mut candidates = Vec::new();
+    for file in 0..9 {
+        for rank in 0..9 {
+            let from = Square::new(file, rank)?;
+            let Some(piece) = *pos.piece_at(from) else {
+                continue;
+            };
+            if piece.color != side {
+                continue;
+            }
+            for to in pos.move_candidates(from, piece) {
+                for promote in [false, true] {
+                    candidates.push(Move::Normal { from, to, promote });
+                }
+            }
+        }
+    }
+    for piece_type in HAND_PIECE_TYPES {
+        if pos.hand(Piece {
+            piece_type,
+            color: side,
+        }) == 0
+        {
+            continue;
+        }
+        for file in 0..9 {
+            for rank in 0..9 {
+                let to = Square::new(file, rank)?;
+                candidates.push(Move::Drop { to, piece_type });
+            }
+        }
+    }
+
+    candidates.into_iter().find(|&mv| {
+        if pos.make_move(mv).is_ok() {
+            let _ = pos.unmake_move();
+            true
+        } else {
+            false
+        }
+    })
+}
+
+fn parse_move(text: &str) -> Option<Move> {
+    let bytes = text.as_bytes();
+    let square = |file: u8, rank: u8| Square::new(file.checked_sub(b'1')?, rank.checked_sub(b'a')?);
+    if bytes.len() == 4 && bytes[1] == b'*' {
+        let piece_type = match bytes[0] {
+            b'R' => PieceType::Rook,
+            b'B' => PieceType::Bishop,
+            b'G' => PieceType::Gold,
+            b'S' => PieceType::Silver,
+            b'N' => PieceType::Knight,
+            b'L' => PieceType::Lance,
+            b'P' => PieceType::Pawn,
+            _ => return None,
+        };
+        return Some(Move::Drop {
+            to: square(bytes[2], bytes[3])?,
+            piece_type,
+        });
+    }
+    if bytes.len() < 4 {
+        return None;
+    }
+    Some(Move::Normal {
+        from: square(bytes[0], bytes[1])?,
+        to: square(bytes[2], bytes[3])?,
+        promote: bytes.get(4) == Some(&b'+'),
+    })
+}
+
+fn move_to_usi(mv: Move) -> String {
+    let square = |sq: Square| format!("{}{}", sq.file() + 1, (b'a' + sq.rank()) as char);
+    match mv {
+        Move::Normal { from, to, promote } => {
+            format!(
+                "{}{}{}",
+                square(from),
+                square(to),
+                if promote { "+" } else { "" }
+            )
+        }
+        Move::Drop { to, piece_type } => {
+            let letter = match piece_type {
+                PieceType::Rook => 'R',
+                PieceType::Bishop => 'B',
+                PieceType::Gold => 'G',
+                PieceType::Silver => 'S',
+                PieceType::Knight => 'N',
+                PieceType::Lance => 'L',
+                _ => 'P',
+            };
+            format!("{letter}*{}", square(to))
+        }
+    }
+}
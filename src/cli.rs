@@ -0,0 +1,420 @@
+//! Command-line subcommands. They use only the core and the engine module,
+//! so they run without any UI feature, e.g. on build servers and in batch
+//! jobs.
+//!
+//! Positions and records are given as a file, `-` for standard input,
+//! `startpos`, or the text itself: a USI `position` line or an SFEN.
+
+use std::io::{self, Read as _};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::core::game::{GameState, START_SFEN};
+use crate::core::kif;
+use crate::core::movegen::Board;
+use crate::core::perft::{self, PERFT_SUITE};
+use crate::core::record::{self, RecordError, RecordFormat};
+use crate::core::sfen::BoardSetup;
+use crate::core::tsume::{self, TsumeOptions, TsumeResult};
+use crate::core::usi::{japanese_pv, move_to_usi};
+use crate::engine::GoParams;
+use crate::engine::annotate;
+use crate::engine::registry::EngineRegistry;
+
+const USAGE: &str = "usage:
+  rshogi convert <input> [--to kif|csa|usi|sfen] [--output <file>] [--ply <n>]
+  rshogi validate <record>...
+  rshogi board <input> [--ply <n>]
+  rshogi mate <input> [--ply <n>] [--nodes <n>] [--keep-hands]
+  rshogi analyze <record> --engine <path> [--movetime <ms> | --depth <n> | --nodes <n>] [--output <file>]
+  rshogi perft [--check] <input> <depth>
+  rshogi perft --suite [max-depth]
+
+<input> is a record file, `-` for standard input, `startpos`, a USI
+`position` line or an SFEN. Positions are taken at the end of the record
+unless --ply says otherwise.
+
+`mate` exits with status 2 when there is no mate and 3 when the node limit
+runs out first.";
+
+/// Exit status of `mate` when every sequence of checks can be escaped.
+const EXIT_NO_MATE: u8 = 2;
+/// Exit status of `mate` when the node limit ran out before an answer.
+const EXIT_UNKNOWN: u8 = 3;
+
+/// Deepest level the suite compares against the `shogi` crate, which checks
+/// every candidate move by playing it.
+const SUITE_CHECK_DEPTH: u32 = 2;
+
+/// Search time per position when `analyze` is given no limit.
+const DEFAULT_MOVETIME_MS: u64 = 1_000;
+
+/// Runs the subcommand named by `args[0]`, or returns `None` when there is
+/// none so the caller can start the UI instead.
+pub fn run(args: &[String]) -> Option<ExitCode> {
+    let rest = args.get(1..).unwrap_or_default();
+    let result = match args.first()?.as_str() {
+        "convert" => convert_command(rest),
+        "validate" => validate_command(rest),
+        "board" => board_command(rest),
+        "mate" => return Some(mate_command(rest).unwrap_or_else(fail)),
+        "analyze" => analyze_command(rest),
+        "perft" => perft_command(rest),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => return None,
+    };
+    Some(match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => fail(message),
+    })
+}
+
+fn fail(message: String) -> ExitCode {
+    eprintln!("rshogi: {message}");
+    ExitCode::FAILURE
+}
+
+/// Positional arguments and `--name [value]` options of one subcommand.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Splits `args`; options in `flags` take no value, those in `valued`
+    /// take the next argument.
+    fn parse(args: &[String], valued: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg.clone());
+                continue;
+            };
+            if flags.contains(&name) {
+                parsed.options.push((name.to_string(), None));
+            } else if valued.contains(&name) {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("--{name} needs a value"))?;
+                parsed.options.push((name.to_string(), Some(value.clone())));
+            } else {
+                return Err(format!("unknown option `{arg}`\n{USAGE}"));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.value(name)
+            .map(|text| {
+                text.parse()
+                    .map_err(|_| format!("--{name} expects a number, found `{text}`"))
+            })
+            .transpose()
+    }
+
+    /// The positional arguments as one input, so an unquoted SFEN works too.
+    fn input(&self) -> Result<String, String> {
+        if self.positional.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        Ok(self.positional.join(" "))
+    }
+}
+
+/// Reads `input` as described in the module docs and shows the position at
+/// `ply`, or at the end of the displayed line.
+fn load_game(input: &str, ply: Option<usize>) -> Result<GameState, String> {
+    let path = Path::new(input);
+    let mut game = if input == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| format!("reading standard input: {err}"))?;
+        parse_text(&text)?
+    } else if input == "startpos" {
+        GameState::from_sfen(START_SFEN).map_err(|err| err.to_string())?
+    } else if path.is_file() {
+        let read = match RecordFormat::from_path(path) {
+            Some(_) => record::read_record(path),
+            // Unknown extensions are recognised by their contents.
+            None => std::fs::read(path)
+                .map_err(RecordError::from)
+                .and_then(|bytes| {
+                    let text = record::decode_text(&bytes);
+                    record::parse_record(&text, RecordFormat::detect(&text))
+                }),
+        };
+        read.map_err(|err| format!("{}: {err}", path.display()))?
+    } else {
+        parse_text(input)?
+    };
+    game.jump_to_ply(ply.unwrap_or(game.history().len()));
+    let _ = game.take_pending_sound();
+    game.set_status("");
+    Ok(game)
+}
+
+fn parse_text(text: &str) -> Result<GameState, String> {
+    record::parse_record(text, RecordFormat::detect(text)).map_err(|err| err.to_string())
+}
+
+fn convert_command(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["to", "output", "ply"], &[])?;
+    let game = load_game(&args.input()?, args.number("ply")?)?;
+    let to = match args.value("to") {
+        Some(name) => {
+            Some(RecordFormat::from_name(name).ok_or_else(|| format!("unknown format `{name}`"))?)
+        }
+        None => None,
+    };
+    match args.value("output").map(PathBuf::from) {
+        Some(path) => {
+            let by_extension = RecordFormat::from_path(&path);
+            if to.is_some() && by_extension != to {
+                return Err(format!(
+                    "{} does not have the extension of the --to format",
+                    path.display()
+                ));
+            }
+            record::write_record(&game, &path).map_err(|err| format!("{}: {err}", path.display()))
+        }
+        None => {
+            let format = to.ok_or("say which format to write with --to")?;
+            print!("{}", record::format_record(&game, format));
+            Ok(())
+        }
+    }
+}
+
+/// Checks each record: it parses, its start position could begin a game,
+/// and every move of its main line is one the move generator allows.
+fn validate_command(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &[], &[])?;
+    if args.positional.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let mut failures = 0;
+    for input in &args.positional {
+        match validate_record(input) {
+            Ok(summary) => println!("{input}: ok, {summary}"),
+            Err(message) => {
+                println!("{input}: {message}");
+                failures += 1;
+            }
+        }
+    }
+    match failures {
+        0 => Ok(()),
+        count => Err(format!(
+            "{count} of {} records are invalid",
+            args.positional.len()
+        )),
+    }
+}
+
+fn validate_record(input: &str) -> Result<String, String> {
+    let mut game = load_game(input, Some(0))?;
+    BoardSetup::from_sfen(game.start_sfen())
+        .map_err(|err| err.to_string())?
+        .validate()
+        .map_err(|err| format!("start position: {err}"))?;
+    let moves: Vec<_> = game.history().iter().map(|entry| entry.mv).collect();
+    for (ply, &mv) in moves.iter().enumerate() {
+        game.jump_to_ply(ply);
+        if !game.legal_moves().contains(&mv) {
+            return Err(format!(
+                "move {} `{}` is not legal",
+                ply + 1,
+                move_to_usi(mv)
+            ));
+        }
+    }
+    game.jump_to_ply(moves.len());
+    Ok(match game.result() {
+        Some(result) => format!("{} moves, {result}", moves.len()),
+        None => format!("{} moves", moves.len()),
+    })
+}
+
+fn board_command(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["ply"], &[])?;
+    let game = load_game(&args.input()?, args.number("ply")?)?;
+    print!(
+        "{}",
+        kif::board_diagram(&BoardSetup::from_position(game.position()))
+    );
+    Ok(())
+}
+
+fn mate_command(args: &[String]) -> Result<ExitCode, String> {
+    let args = Args::parse(args, &["ply", "nodes"], &["keep-hands"])?;
+    let game = load_game(&args.input()?, args.number("ply")?)?;
+    let defaults = TsumeOptions::default();
+    let options = TsumeOptions {
+        max_nodes: args.number("nodes")?.unwrap_or(defaults.max_nodes),
+        remaining_to_defender: !args.has("keep-hands"),
+    };
+    let solution = match tsume::solve(game.position(), &options) {
+        TsumeResult::Mate(solution) => solution,
+        TsumeResult::NoMate => {
+            println!("No mate");
+            return Ok(ExitCode::from(EXIT_NO_MATE));
+        }
+        TsumeResult::Unknown => {
+            println!("No answer within {} positions", options.max_nodes);
+            return Ok(ExitCode::from(EXIT_UNKNOWN));
+        }
+    };
+
+    let usi: Vec<String> = solution.moves.iter().map(|&mv| move_to_usi(mv)).collect();
+    println!("Mate in {}", solution.length());
+    println!(
+        "{}",
+        japanese_pv(&solution.start_sfen, None, &usi).join(" ")
+    );
+    println!("{}", usi.join(" "));
+    for alternative in &solution.alternatives {
+        println!(
+            "Move {}: {} also mates",
+            alternative.ply + 1,
+            move_to_usi(alternative.mv)
+        );
+    }
+    println!("{} positions searched", solution.nodes);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Annotates a record with an engine's evaluation of every position and
+/// writes it as KIF.
+fn analyze_command(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &["engine", "movetime", "depth", "nodes", "output"],
+        &[],
+    )?;
+    let mut game = load_game(&args.input()?, None)?;
+    let engine = args
+        .value("engine")
+        .map(PathBuf::from)
+        .ok_or("say which engine to run with --engine")?;
+    let mut go = GoParams {
+        movetime: args.number("movetime")?,
+        depth: args.number("depth")?,
+        nodes: args.number("nodes")?,
+        ..GoParams::default()
+    };
+    if go.movetime.is_none() && go.depth.is_none() && go.nodes.is_none() {
+        go.movetime = Some(DEFAULT_MOVETIME_MS);
+    }
+
+    let config = EngineRegistry::load().config_for(&engine);
+    let total = game.history().len();
+    annotate::annotate_record(&mut game, config, &go, |ply, note| {
+        let first = note.lines().next().unwrap_or_default();
+        eprintln!("{ply}/{total}: {first}");
+    })?;
+
+    match args.value("output").map(PathBuf::from) {
+        Some(path) => {
+            record::write_record(&game, &path).map_err(|err| format!("{}: {err}", path.display()))
+        }
+        None => {
+            print!("{}", kif::write(&game));
+            Ok(())
+        }
+    }
+}
+
+fn perft_command(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &[], &["check", "suite"])?;
+    if args.has("suite") {
+        let max_depth = match args.positional.first() {
+            Some(depth) => parse_depth(depth)?,
+            None => 3,
+        };
+        return perft_suite(max_depth);
+    }
+    let Some((depth, input)) = args.positional.split_last() else {
+        return Err(USAGE.to_string());
+    };
+    if input.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let depth = parse_depth(depth)?;
+    let game = load_game(&input.join(" "), None)?;
+    let board = Board::from_position(game.position());
+
+    let mut total = 0;
+    for (mv, nodes) in perft::divide(&board, depth) {
+        println!("{}: {nodes}", move_to_usi(mv));
+        total += nodes;
+    }
+    println!();
+    println!("Nodes searched: {total}");
+
+    if args.has("check") {
+        let checked = perft::compare_with_shogi(game.position(), depth)
+            .map_err(|divergence| divergence.to_string())?;
+        if checked != total {
+            return Err(format!(
+                "the shogi crate counts {checked} nodes, the generator {total}"
+            ));
+        }
+        println!("The shogi crate agrees");
+    }
+    Ok(())
+}
+
+/// Checks every suite position against its published counts up to
+/// `max_depth`, and against the `shogi` crate at the shallow depths.
+fn perft_suite(max_depth: u32) -> Result<(), String> {
+    let mut failures = 0;
+    for case in PERFT_SUITE {
+        let game = GameState::from_sfen(case.sfen).map_err(|err| err.to_string())?;
+        let board = Board::from_position(game.position());
+        for (depth, &expected) in (1..=max_depth).zip(case.counts) {
+            let nodes = perft::perft(&board, depth);
+            if nodes == expected {
+                println!("{} depth {depth}: {nodes} ok", case.name);
+            } else {
+                println!("{} depth {depth}: {nodes}, expected {expected}", case.name);
+                failures += 1;
+            }
+        }
+        if let Err(divergence) =
+            perft::compare_with_shogi(game.position(), SUITE_CHECK_DEPTH.min(max_depth))
+        {
+            println!("{}: {divergence}", case.name);
+            failures += 1;
+        }
+    }
+    match failures {
+        0 => Ok(()),
+        count => Err(format!("{count} perft checks failed")),
+    }
+}
+
+fn parse_depth(text: &str) -> Result<u32, String> {
+    text.parse()
+        .map_err(|_| format!("expected a depth, found `{text}`"))
+}
@@ -0,0 +1,26 @@
+//! Where per-user settings files live.
+
+use std::env;
+use std::path::PathBuf;
+
+/// Settings directory, e.g. `~/.config/rshogi` on Linux,
+/// `~/Library/Application Support/rshogi` on macOS and `%APPDATA%\rshogi` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+    base.map(|base| base.join("rshogi"))
+}
+
+/// Path of the settings file `name` inside [`config_dir`].
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(name))
+}
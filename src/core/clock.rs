@@ -0,0 +1,386 @@
+//! Game clocks for the time controls used in shogi.
+//!
+//! Times are kept in milliseconds. Methods that depend on the current time
+//! take it as an argument, so the clock itself never reads the system time.
+
+use std::fmt;
+use std::time::Instant;
+
+use shogi::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeControl {
+    /// Main time, then a fixed period for every move once it runs out.
+    Byoyomi { main_ms: u64, byoyomi_ms: u64 },
+    /// Main time plus an increment after every move.
+    Fischer { main_ms: u64, increment_ms: u64 },
+    /// Main time only.
+    SuddenDeath { main_ms: u64 },
+    /// A fixed time for each move; unused time is not carried over.
+    PerMove { ms: u64 },
+}
+
+impl TimeControl {
+    pub fn main_ms(self) -> u64 {
+        match self {
+            Self::Byoyomi { main_ms, .. }
+            | Self::Fischer { main_ms, .. }
+            | Self::SuddenDeath { main_ms } => main_ms,
+            Self::PerMove { .. } => 0,
+        }
+    }
+
+    /// Time available for each move after the main time, if any.
+    pub fn byoyomi_ms(self) -> u64 {
+        match self {
+            Self::Byoyomi { byoyomi_ms, .. } => byoyomi_ms,
+            Self::PerMove { ms } => ms,
+            Self::Fischer { .. } | Self::SuddenDeath { .. } => 0,
+        }
+    }
+
+    pub fn increment_ms(self) -> u64 {
+        match self {
+            Self::Fischer { increment_ms, .. } => increment_ms,
+            _ => 0,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Self::Byoyomi {
+                main_ms,
+                byoyomi_ms,
+            } => format!(
+                "{} min + {} s byoyomi",
+                minutes(main_ms),
+                seconds(byoyomi_ms)
+            ),
+            Self::Fischer {
+                main_ms,
+                increment_ms,
+            } => format!(
+                "{} min + {} s / move",
+                minutes(main_ms),
+                seconds(increment_ms)
+            ),
+            Self::SuddenDeath { main_ms } => format!("{} min sudden death", minutes(main_ms)),
+            Self::PerMove { ms } => format!("{} s / move", seconds(ms)),
+        }
+    }
+
+    /// Text for the record's time control header: `HH:MM+SS` as in CSA
+    /// `$TIME_LIMIT` for byoyomi controls, the label otherwise.
+    pub fn header(self) -> String {
+        match self {
+            Self::Byoyomi { .. } | Self::SuddenDeath { .. } | Self::PerMove { .. } => {
+                let minutes = self.main_ms() / 60_000;
+                format!(
+                    "{:02}:{:02}+{:02}",
+                    minutes / 60,
+                    minutes % 60,
+                    self.byoyomi_ms() / 1_000
+                )
+            }
+            Self::Fischer { .. } => self.label(),
+        }
+    }
+}
+
+/// `ms` in minutes, e.g. `1.5`.
+fn minutes(ms: u64) -> String {
+    seconds(ms / 60)
+}
+
+fn seconds(ms: u64) -> String {
+    if ms.is_multiple_of(1_000) {
+        (ms / 1_000).to_string()
+    } else {
+        format!("{:.1}", ms as f64 / 1_000.0)
+    }
+}
+
+/// What a side has left on its clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeLeft {
+    pub main_ms: u64,
+    /// Byoyomi left for the current move; the full period when it is not in use.
+    pub byoyomi_ms: u64,
+}
+
+impl TimeLeft {
+    /// Whether the main time is used up and the side is on byoyomi.
+    pub fn in_byoyomi(self) -> bool {
+        self.main_ms == 0 && self.byoyomi_ms > 0
+    }
+
+    pub fn total_ms(self) -> u64 {
+        self.main_ms + self.byoyomi_ms
+    }
+}
+
+impl fmt::Display for TimeLeft {
+    /// `mm:ss` of main time, or the byoyomi seconds once main time is gone.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.in_byoyomi() {
+            return write!(f, "{}", self.byoyomi_ms.div_ceil(1_000));
+        }
+        let secs = self.main_ms.div_ceil(1_000);
+        if secs >= 3_600 {
+            write!(f, "{}:{:02}:{:02}", secs / 3_600, secs / 60 % 60, secs % 60)
+        } else {
+            write!(f, "{:02}:{:02}", secs / 60, secs % 60)
+        }
+    }
+}
+
+/// Chess-style clock for both sides.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    /// Main time left for each side, Black first.
+    main_left: [u64; 2],
+    /// Side whose time is running and when its turn began.
+    running: Option<(Color, Instant)>,
+    flagged: Option<Color>,
+    /// Extra time past the limit before the flag falls.
+    grace_ms: u64,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            main_left: [control.main_ms(); 2],
+            running: None,
+            flagged: None,
+            grace_ms: 0,
+        }
+    }
+
+    /// Allows `grace_ms` past the limit before the flag falls, covering
+    /// the latency of engine processes.
+    pub fn with_grace(mut self, grace_ms: u64) -> Self {
+        self.grace_ms = grace_ms;
+        self
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// Side whose time is running.
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// Side that ran out of time.
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged
+    }
+
+    /// Starts `color`'s turn, stopping the other side's time first.
+    pub fn start(&mut self, color: Color, now: Instant) {
+        if self.flagged.is_some() {
+            return;
+        }
+        self.stop(now);
+        self.running = Some((color, now));
+    }
+
+    /// Stops the running side's time without ending its move.
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((color, since)) = self.running.take() {
+            let slot = &mut self.main_left[index(color)];
+            *slot = slot.saturating_sub(elapsed_ms(since, now));
+        }
+    }
+
+    /// Ends the running side's move and starts the opponent's turn. Returns
+    /// the time the move took, or `None` when it came too late and the flag fell.
+    pub fn press(&mut self, now: Instant) -> Option<u64> {
+        let (color, since) = self.running?;
+        let elapsed = elapsed_ms(since, now);
+        if elapsed > self.allowance_ms(color) + self.grace_ms {
+            self.running = None;
+            self.flagged = Some(color);
+            return None;
+        }
+        let slot = &mut self.main_left[index(color)];
+        *slot = slot.saturating_sub(elapsed) + self.control.increment_ms();
+        self.running = Some((color.flip(), now));
+        Some(elapsed)
+    }
+
+    /// Drops the flag of the running side once its time is up.
+    pub fn check_flag(&mut self, now: Instant) -> Option<Color> {
+        if let Some((color, since)) = self.running
+            && elapsed_ms(since, now) > self.allowance_ms(color) + self.grace_ms
+        {
+            self.running = None;
+            self.flagged = Some(color);
+        }
+        self.flagged
+    }
+
+    pub fn time_left(&self, color: Color, now: Instant) -> TimeLeft {
+        let main = self.main_left[index(color)];
+        let byoyomi = self.control.byoyomi_ms();
+        match self.running {
+            Some((running, since)) if running == color => {
+                let elapsed = elapsed_ms(since, now);
+                TimeLeft {
+                    main_ms: main.saturating_sub(elapsed),
+                    byoyomi_ms: byoyomi.saturating_sub(elapsed.saturating_sub(main)),
+                }
+            }
+            _ if self.flagged == Some(color) => TimeLeft {
+                main_ms: 0,
+                byoyomi_ms: 0,
+            },
+            _ => TimeLeft {
+                main_ms: main,
+                byoyomi_ms: byoyomi,
+            },
+        }
+    }
+
+    /// Longest time `color` may take over its current move.
+    pub fn allowance_ms(&self, color: Color) -> u64 {
+        self.main_left[index(color)] + self.control.byoyomi_ms()
+    }
+
+    /// Main time left for `color` when its turn began, as sent in `btime`/`wtime`.
+    pub fn main_left_ms(&self, color: Color) -> u64 {
+        self.main_left[index(color)]
+    }
+}
+
+fn index(color: Color) -> usize {
+    match color {
+        Color::Black => 0,
+        Color::White => 1,
+    }
+}
+
+fn elapsed_ms(since: Instant, now: Instant) -> u64 {
+    u64::try_from(now.saturating_duration_since(since).as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn byoyomi_starts_when_main_time_runs_out() {
+        let t0 = Instant::now();
+        let mut clock = Clock::new(TimeControl::Byoyomi {
+            main_ms: 10_000,
+            byoyomi_ms: 5_000,
+        });
+        clock.start(Color::Black, t0);
+        assert_eq!(clock.press(at(t0, 3_000)), Some(3_000));
+        assert_eq!(clock.main_left_ms(Color::Black), 7_000);
+        assert_eq!(clock.running(), Some(Color::White));
+
+        clock.start(Color::Black, at(t0, 4_000));
+        let left = clock.time_left(Color::Black, at(t0, 13_000));
+        assert_eq!(
+            left,
+            TimeLeft {
+                main_ms: 0,
+                byoyomi_ms: 3_000
+            }
+        );
+        assert!(left.in_byoyomi());
+        // Main time is gone, but the move still came within byoyomi.
+        assert_eq!(clock.press(at(t0, 15_000)), Some(11_000));
+        assert_eq!(clock.main_left_ms(Color::Black), 0);
+
+        clock.start(Color::Black, at(t0, 20_000));
+        assert_eq!(clock.check_flag(at(t0, 25_000)), None);
+        assert_eq!(clock.check_flag(at(t0, 25_001)), Some(Color::Black));
+        assert_eq!(
+            clock.time_left(Color::Black, at(t0, 26_000)),
+            TimeLeft {
+                main_ms: 0,
+                byoyomi_ms: 0
+            }
+        );
+    }
+
+    #[test]
+    fn fischer_adds_the_increment_after_each_move() {
+        let t0 = Instant::now();
+        let mut clock = Clock::new(TimeControl::Fischer {
+            main_ms: 10_000,
+            increment_ms: 2_000,
+        });
+        clock.start(Color::Black, t0);
+        assert_eq!(clock.press(at(t0, 3_000)), Some(3_000));
+        assert_eq!(clock.main_left_ms(Color::Black), 9_000);
+        assert_eq!(clock.press(at(t0, 4_000)), Some(1_000));
+        assert_eq!(clock.main_left_ms(Color::White), 11_000);
+
+        assert_eq!(clock.allowance_ms(Color::Black), 9_000);
+        assert_eq!(clock.press(at(t0, 13_001)), None);
+        assert_eq!(clock.flagged(), Some(Color::Black));
+        assert_eq!(clock.running(), None);
+    }
+
+    #[test]
+    fn sudden_death_flags_once_main_time_is_used() {
+        let t0 = Instant::now();
+        let mut clock = Clock::new(TimeControl::SuddenDeath { main_ms: 1_000 });
+        clock.start(Color::White, t0);
+        assert_eq!(
+            clock.time_left(Color::White, at(t0, 400)),
+            TimeLeft {
+                main_ms: 600,
+                byoyomi_ms: 0
+            }
+        );
+        assert_eq!(clock.check_flag(at(t0, 1_000)), None);
+        assert_eq!(clock.check_flag(at(t0, 1_001)), Some(Color::White));
+        // A flagged clock does not restart.
+        clock.start(Color::Black, at(t0, 2_000));
+        assert_eq!(clock.running(), None);
+    }
+
+    #[test]
+    fn per_move_time_is_not_carried_over() {
+        let t0 = Instant::now();
+        let mut clock = Clock::new(TimeControl::PerMove { ms: 5_000 });
+        clock.start(Color::Black, t0);
+        assert_eq!(clock.press(at(t0, 1_000)), Some(1_000));
+        assert_eq!(clock.press(at(t0, 2_000)), Some(1_000));
+        assert_eq!(clock.allowance_ms(Color::Black), 5_000);
+        assert_eq!(
+            clock.time_left(Color::Black, at(t0, 4_000)),
+            TimeLeft {
+                main_ms: 0,
+                byoyomi_ms: 3_000
+            }
+        );
+        assert_eq!(clock.press(at(t0, 7_001)), None);
+        assert_eq!(clock.flagged(), Some(Color::Black));
+    }
+
+    #[test]
+    fn flag_waits_for_the_grace_period() {
+        let t0 = Instant::now();
+        let mut clock = Clock::new(TimeControl::PerMove { ms: 1_000 }).with_grace(500);
+        clock.start(Color::Black, t0);
+        assert_eq!(clock.check_flag(at(t0, 1_200)), None);
+        assert_eq!(clock.press(at(t0, 1_400)), Some(1_400));
+
+        assert_eq!(clock.check_flag(at(t0, 2_900)), None);
+        assert_eq!(clock.check_flag(at(t0, 2_901)), Some(Color::White));
+    }
+}
@@ -0,0 +1,653 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+use shogi::{Color, Move, Piece, PieceType, Square};
+
+use super::game::{
+    EndReason, GameInfo, GameResult, GameState, START_SFEN, promoted_piece_type,
+    unpromoted_piece_type,
+};
+use super::handicap::Handicap;
+use super::sfen::{BoardSetup, HAND_PIECE_TYPES, squares};
+use super::variation::ROOT;
+
+const CSA_PIECES: [(&str, PieceType); 14] = [
+    ("FU", PieceType::Pawn),
+    ("KY", PieceType::Lance),
+    ("KE", PieceType::Knight),
+    ("GI", PieceType::Silver),
+    ("KI", PieceType::Gold),
+    ("KA", PieceType::Bishop),
+    ("HI", PieceType::Rook),
+    ("OU", PieceType::King),
+    ("TO", PieceType::ProPawn),
+    ("NY", PieceType::ProLance),
+    ("NK", PieceType::ProKnight),
+    ("NG", PieceType::ProSilver),
+    ("UM", PieceType::ProBishop),
+    ("RY", PieceType::ProRook),
+];
+
+#[derive(Debug)]
+pub struct CsaError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl CsaError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CsaError {}
+
+/// Reads the first game of a CSA record into a game positioned after its last move.
+pub fn parse(text: &str) -> Result<GameState, CsaError> {
+    let mut reader = CsaReader::default();
+    let text = text.trim_start_matches('\u{feff}');
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        // Comments and headers may contain commas; other lines can hold several statements.
+        if line.starts_with(['\'', '$', 'N']) {
+            reader.read_statement(idx + 1, line)?;
+            continue;
+        }
+        for statement in line.split(',') {
+            reader.read_statement(idx + 1, statement.trim())?;
+        }
+        if reader.done {
+            break;
+        }
+    }
+    reader.finish()
+}
+
+/// Serializes the main line of `game`; CSA has no variations.
+pub fn write(game: &GameState) -> String {
+    let mut out = String::from("V2.2\n");
+    let info = game.info();
+    write_header(&mut out, "N+", &info.black);
+    write_header(&mut out, "N-", &info.white);
+    write_header(&mut out, "$EVENT:", &info.event);
+    write_header(&mut out, "$SITE:", &info.site);
+    write_header(&mut out, "$START_TIME:", &info.start_time);
+    write_header(&mut out, "$END_TIME:", &info.end_time);
+    write_header(&mut out, "$TIME_LIMIT:", &info.time_control);
+    write_header(&mut out, "$OPENING:", &info.opening);
+    let handicap = Handicap::find(game.start_sfen());
+    if let Some(handicap) = handicap.filter(|handicap| !handicap.is_even()) {
+        let _ = writeln!(out, "$HANDICAP:{}", handicap.kif_name);
+    }
+
+    let setup = BoardSetup::from_sfen(game.start_sfen()).unwrap_or_default();
+    match handicap {
+        // The even start with the handicap's pieces taken off, e.g. `PI82HI22KA`.
+        Some(_) => {
+            out.push_str("PI");
+            let start = BoardSetup::from_sfen(START_SFEN).unwrap_or_default();
+            for sq in squares() {
+                if let (Some(piece), None) = (start.piece_at(sq), setup.piece_at(sq)) {
+                    let _ = write!(out, "{}{}", square_code(sq), piece_code(piece.piece_type));
+                }
+            }
+            out.push('\n');
+        }
+        None => write_position(&mut out, &setup),
+    }
+    out.push_str(color_sign(setup.side_to_move()));
+    out.push('\n');
+    write_comment(&mut out, game.comment(0));
+
+    let tree = game.tree();
+    let main_line = tree.main_line(ROOT);
+    for entry in main_line.iter().map(|&id| tree.entry(id)) {
+        let color = color_sign(entry.piece.color);
+        match entry.mv {
+            Move::Normal { from, to, promote } => {
+                let piece_type = if promote {
+                    promoted_piece_type(entry.piece.piece_type)
+                } else {
+                    entry.piece.piece_type
+                };
+                let _ = writeln!(
+                    out,
+                    "{color}{}{}{}",
+                    square_code(from),
+                    square_code(to),
+                    piece_code(piece_type)
+                );
+            }
+            Move::Drop { to, piece_type } => {
+                let _ = writeln!(
+                    out,
+                    "{color}00{}{}",
+                    square_code(to),
+                    piece_code(piece_type)
+                );
+            }
+        }
+        if let Some(seconds) = entry.elapsed {
+            let _ = writeln!(out, "T{seconds}");
+        }
+        write_comment(&mut out, &entry.comment);
+    }
+
+    let end = main_line.last().copied().unwrap_or(ROOT);
+    if let Some(result) = tree.result(end) {
+        let moves = main_line.len();
+        let side_to_move = if moves.is_multiple_of(2) {
+            setup.side_to_move()
+        } else {
+            setup.side_to_move().flip()
+        };
+        let _ = writeln!(out, "{}", termination_text(result, side_to_move));
+    }
+    out
+}
+
+#[derive(Default)]
+struct CsaReader {
+    info: GameInfo,
+    setup: Option<BoardSetup>,
+    side_to_move: Option<Color>,
+    game: Option<GameState>,
+    finished: bool,
+    done: bool,
+}
+
+impl CsaReader {
+    fn read_statement(&mut self, line_no: usize, statement: &str) -> Result<(), CsaError> {
+        if statement.is_empty() || statement.starts_with('V') {
+            return Ok(());
+        }
+        if statement == "/" {
+            // Start of the next game in a multi-game file.
+            self.done = true;
+            return Ok(());
+        }
+
+        // `'*` carries a comment for the current position; a bare `'` is a file comment.
+        if let Some(comment) = statement.strip_prefix("'*") {
+            let game = self.game(line_no)?;
+            let ply = game.current_ply();
+            let mut text = game.comment(ply).to_string();
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(comment);
+            game.set_comment(ply, text);
+            return Ok(());
+        }
+        if statement.starts_with('\'') {
+            return Ok(());
+        }
+
+        if let Some(name) = statement.strip_prefix("N+") {
+            self.info.black = Some(name.to_string());
+            return Ok(());
+        }
+        if let Some(name) = statement.strip_prefix("N-") {
+            self.info.white = Some(name.to_string());
+            return Ok(());
+        }
+        if let Some(header) = statement.strip_prefix('$') {
+            self.read_header(header);
+            return Ok(());
+        }
+        if let Some(rest) = statement.strip_prefix('P') {
+            return self.read_position(line_no, rest);
+        }
+        if let Some(seconds) = statement.strip_prefix('T') {
+            if self.finished {
+                return Ok(());
+            }
+            let seconds = seconds
+                .trim()
+                .parse()
+                .map_err(|_| CsaError::new(line_no, format!("bad time `{statement}`")))?;
+            let game = self.game(line_no)?;
+            let ply = game.current_ply();
+            if ply > 0 {
+                game.set_elapsed(ply, Some(seconds));
+            }
+            return Ok(());
+        }
+        if let Some(keyword) = statement.strip_prefix('%') {
+            let game = self.game(line_no)?;
+            if let Some(result) = termination(keyword, game.side_to_move()) {
+                game.set_result(Some(result));
+                self.finished = true;
+            }
+            return Ok(());
+        }
+        match statement {
+            "+" => self.side_to_move = Some(Color::Black),
+            "-" => self.side_to_move = Some(Color::White),
+            _ if statement.starts_with(['+', '-']) => return self.read_move(line_no, statement),
+            _ => {
+                return Err(CsaError::new(
+                    line_no,
+                    format!("unknown statement `{statement}`"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_header(&mut self, header: &str) {
+        let (key, value) = header.split_once(':').unwrap_or((header, ""));
+        let owned = Some(value.to_string());
+        match key {
+            "EVENT" => self.info.event = owned,
+            "SITE" => self.info.site = owned,
+            "START_TIME" => self.info.start_time = owned,
+            "END_TIME" => self.info.end_time = owned,
+            "TIME_LIMIT" => self.info.time_control = owned,
+            "OPENING" => self.info.opening = owned,
+            // Implied by the `PI` line.
+            "HANDICAP" => {}
+            _ => self.info.extra.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    fn read_position(&mut self, line_no: usize, rest: &str) -> Result<(), CsaError> {
+        if self.game.is_some() {
+            return Err(CsaError::new(line_no, "position after the first move"));
+        }
+        let error = |message: String| CsaError::new(line_no, message);
+
+        if let Some(removed) = rest.strip_prefix('I') {
+            let mut setup =
+                BoardSetup::from_sfen(START_SFEN).map_err(|err| error(err.to_string()))?;
+            let tokens =
+                placements(removed).ok_or_else(|| error(format!("bad piece list `P{rest}`")))?;
+            for token in tokens {
+                let sq = parse_square(&token[..2])
+                    .ok_or_else(|| error(format!("bad square in `P{rest}`")))?;
+                setup.set_piece(sq, None);
+            }
+            self.setup = Some(setup);
+            return Ok(());
+        }
+
+        let setup = self.setup.get_or_insert_with(BoardSetup::empty);
+        if let Some(row) = rest.chars().next().and_then(|c| c.to_digit(10)) {
+            let rank = row
+                .checked_sub(1)
+                .filter(|rank| *rank < 9)
+                .ok_or_else(|| error(format!("bad row `P{rest}`")))? as u8;
+            let cells: Vec<char> = rest[1..].chars().collect();
+            for col in 0..9u8 {
+                let cell: String = cells.iter().skip(col as usize * 3).take(3).collect();
+                let sq = Square::new(8 - col, rank).expect("valid board coordinate");
+                if cell.trim().is_empty() || cell.trim() == "*" {
+                    setup.set_piece(sq, None);
+                    continue;
+                }
+                let piece =
+                    parse_piece(&cell).ok_or_else(|| error(format!("unknown piece `{cell}`")))?;
+                setup.set_piece(sq, Some(piece));
+            }
+            return Ok(());
+        }
+
+        let color = match rest.chars().next() {
+            Some('+') => Color::Black,
+            Some('-') => Color::White,
+            _ => return Err(error(format!("unknown position line `P{rest}`"))),
+        };
+        let tokens =
+            placements(&rest[1..]).ok_or_else(|| error(format!("bad piece list `P{rest}`")))?;
+        for token in tokens {
+            if token == "00AL" {
+                setup.give_remaining_pieces(color);
+                continue;
+            }
+            let piece_type = parse_piece_type(&token[2..])
+                .ok_or_else(|| error(format!("unknown piece `{token}`")))?;
+            if &token[..2] == "00" {
+                let piece_type = unpromoted_piece_type(piece_type);
+                let count = setup.hand(color, piece_type);
+                setup.set_hand(color, piece_type, count + 1);
+            } else {
+                let sq = parse_square(&token[..2])
+                    .ok_or_else(|| error(format!("bad square in `{token}`")))?;
+                setup.set_piece(sq, Some(Piece { piece_type, color }));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_move(&mut self, line_no: usize, text: &str) -> Result<(), CsaError> {
+        if self.finished {
+            return Ok(());
+        }
+        let game = self.game(line_no)?;
+        let mv = parse_move(game, text).map_err(|message| CsaError::new(line_no, message))?;
+        game.push_move(mv)
+            .map_err(|err| CsaError::new(line_no, format!("illegal move `{text}`: {err:?}")))?;
+        Ok(())
+    }
+
+    fn game(&mut self, line_no: usize) -> Result<&mut GameState, CsaError> {
+        if self.game.is_none() {
+            let mut setup = self
+                .setup
+                .clone()
+                .unwrap_or_else(|| BoardSetup::from_sfen(START_SFEN).unwrap_or_default());
+            setup.set_side_to_move(self.side_to_move.unwrap_or(Color::Black));
+            let game = GameState::from_sfen(&setup.to_sfen())
+                .map_err(|err| CsaError::new(line_no, err.to_string()))?;
+            self.game = Some(game);
+        }
+        Ok(self.game.as_mut().expect("game was just created"))
+    }
+
+    fn finish(mut self) -> Result<GameState, CsaError> {
+        self.game(0)?;
+        let mut game = self.game.take().expect("game was just created");
+        *game.info_mut() = self.info;
+        Ok(game)
+    }
+}
+
+/// Reads `+7776FU` or `-0055KA` against the current position of `game`.
+fn parse_move(game: &GameState, text: &str) -> Result<Move, String> {
+    if text.len() != 7 || !text.is_ascii() {
+        return Err(format!("bad move `{text}`"));
+    }
+    let color = if text.starts_with('+') {
+        Color::Black
+    } else {
+        Color::White
+    };
+    if color != game.side_to_move() {
+        return Err(format!("`{text}` played out of turn"));
+    }
+    let to = parse_square(&text[3..5]).ok_or_else(|| format!("bad destination in `{text}`"))?;
+    let piece_type =
+        parse_piece_type(&text[5..7]).ok_or_else(|| format!("unknown piece in `{text}`"))?;
+    if &text[1..3] == "00" {
+        return Ok(Move::Drop { to, piece_type });
+    }
+
+    let from = parse_square(&text[1..3]).ok_or_else(|| format!("bad origin in `{text}`"))?;
+    let moving = game
+        .piece_at(from)
+        .ok_or_else(|| format!("no piece to move in `{text}`"))?;
+    let promote = moving.piece_type != piece_type;
+    if promote && promoted_piece_type(moving.piece_type) != piece_type {
+        return Err(format!("`{text}` does not match the piece on the board"));
+    }
+    Ok(Move::Normal { from, to, promote })
+}
+
+fn termination(keyword: &str, side_to_move: Color) -> Option<GameResult> {
+    let opponent = Some(side_to_move.flip());
+    let (winner, reason) = match keyword {
+        "TORYO" => (opponent, EndReason::Resignation),
+        "TSUMI" => (opponent, EndReason::Checkmate),
+        "TIME_UP" => (opponent, EndReason::Timeout),
+        "ILLEGAL_MOVE" => (opponent, EndReason::IllegalMove),
+        "+ILLEGAL_ACTION" => (Some(Color::White), EndReason::IllegalMove),
+        "-ILLEGAL_ACTION" => (Some(Color::Black), EndReason::IllegalMove),
+        "SENNICHITE" => (None, EndReason::Repetition),
+        // The checking side just moved, so the side to move is the one defending.
+        "OUTE_SENNICHITE" => (Some(side_to_move), EndReason::PerpetualCheck),
+        "JISHOGI" => (None, EndReason::Impasse),
+        "KACHI" => (Some(side_to_move), EndReason::Impasse),
+        "HIKIWAKE" => (None, EndReason::DrawAgreement),
+        "CHUDAN" => (None, EndReason::Interrupted),
+        _ => return None,
+    };
+    Some(GameResult { winner, reason })
+}
+
+fn termination_text(result: GameResult, side_to_move: Color) -> &'static str {
+    match (result.reason, result.winner) {
+        (EndReason::Resignation, _) => "%TORYO",
+        (EndReason::Checkmate | EndReason::NoLegalMoves, _) => "%TSUMI",
+        (EndReason::Timeout, _) => "%TIME_UP",
+        (EndReason::Repetition, _) => "%SENNICHITE",
+        (EndReason::PerpetualCheck, _) => "%OUTE_SENNICHITE",
+        (EndReason::Impasse, None) => "%JISHOGI",
+        (EndReason::Impasse, Some(_)) => "%KACHI",
+        (EndReason::IllegalMove, Some(winner)) if winner == side_to_move => {
+            if winner == Color::Black {
+                "%-ILLEGAL_ACTION"
+            } else {
+                "%+ILLEGAL_ACTION"
+            }
+        }
+        (EndReason::IllegalMove, _) => "%ILLEGAL_MOVE",
+        (EndReason::DrawAgreement, _) => "%HIKIWAKE",
+        (EndReason::Interrupted, _) => "%CHUDAN",
+    }
+}
+
+/// Splits placement lists such as `00KI77FU` into four-character tokens.
+fn placements(text: &str) -> Option<Vec<&str>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(
+        (0..text.len())
+            .step_by(4)
+            .map(|idx| &text[idx..idx + 4])
+            .collect(),
+    )
+}
+
+fn parse_square(text: &str) -> Option<Square> {
+    let mut digits = text.chars().map(|c| c.to_digit(10));
+    let file = digits.next()??;
+    let rank = digits.next()??;
+    Square::new(file.checked_sub(1)? as u8, rank.checked_sub(1)? as u8)
+}
+
+fn parse_piece(cell: &str) -> Option<Piece> {
+    let color = match cell.chars().next()? {
+        '+' => Color::Black,
+        '-' => Color::White,
+        _ => return None,
+    };
+    let piece_type = parse_piece_type(cell.get(1..)?)?;
+    Some(Piece { piece_type, color })
+}
+
+fn parse_piece_type(code: &str) -> Option<PieceType> {
+    CSA_PIECES
+        .iter()
+        .find(|(name, _)| *name == code)
+        .map(|&(_, piece_type)| piece_type)
+}
+
+pub(crate) fn piece_code(piece_type: PieceType) -> &'static str {
+    CSA_PIECES
+        .iter()
+        .find(|(_, pt)| *pt == piece_type)
+        .map(|&(name, _)| name)
+        .expect("every piece type has a CSA code")
+}
+
+pub(crate) fn square_code(sq: Square) -> String {
+    format!("{}{}", sq.file() + 1, sq.rank() + 1)
+}
+
+fn color_sign(color: Color) -> &'static str {
+    if color == Color::Black { "+" } else { "-" }
+}
+
+fn write_position(out: &mut String, setup: &BoardSetup) {
+    for rank in 0..9 {
+        let _ = write!(out, "P{}", rank + 1);
+        for file in (0..9).rev() {
+            let sq = Square::new(file, rank).expect("valid board coordinate");
+            match setup.piece_at(sq) {
+                Some(piece) => {
+                    out.push_str(color_sign(piece.color));
+                    out.push_str(piece_code(piece.piece_type));
+                }
+                None => out.push_str(" * "),
+            }
+        }
+        out.push('\n');
+    }
+    for color in [Color::Black, Color::White] {
+        let mut hand = String::new();
+        for piece_type in HAND_PIECE_TYPES {
+            for _ in 0..setup.hand(color, piece_type) {
+                let _ = write!(hand, "00{}", piece_code(piece_type));
+            }
+        }
+        if !hand.is_empty() {
+            let _ = writeln!(out, "P{}{hand}", color_sign(color));
+        }
+    }
+}
+
+fn write_header(out: &mut String, prefix: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        let _ = writeln!(out, "{prefix}{value}");
+    }
+}
+
+fn write_comment(out: &mut String, comment: &str) {
+    for line in comment.lines() {
+        let _ = writeln!(out, "'*{line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::kif;
+    use crate::core::usi::move_to_usi;
+
+    /// The even start written out row by row, a few timed and commented
+    /// moves and a resignation.
+    const EVEN_GAME: &str = "\
+V2.2
+N+Sente
+N-Gote
+$EVENT:Test
+$ROUND:3
+P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+P2 * -HI *  *  *  *  * -KA *
+P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+P4 *  *  *  *  *  *  *  *  *
+P5 *  *  *  *  *  *  *  *  *
+P6 *  *  *  *  *  *  *  *  *
+P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+P8 * +KA *  *  *  *  * +HI *
+P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
++
+'*開始
+' a file comment
++7776FU
+T12
+-3334FU,T3
+'*角道
++8822UM
+T40
+-3122GI
+T1
++0045KA
+T7
+%TORYO
+";
+
+    fn parse_text(text: &str) -> GameState {
+        crate::core::init();
+        parse(text).expect("valid test record")
+    }
+
+    fn usi(game: &GameState) -> Vec<String> {
+        game.history()
+            .iter()
+            .map(|entry| move_to_usi(entry.mv))
+            .collect()
+    }
+
+    #[test]
+    fn reads_board_rows_moves_times_and_comments() {
+        let game = parse_text(EVEN_GAME);
+        assert_eq!(game.start_sfen(), START_SFEN);
+        assert_eq!(game.info().black.as_deref(), Some("Sente"));
+        assert_eq!(game.info().white.as_deref(), Some("Gote"));
+        assert_eq!(game.info().event.as_deref(), Some("Test"));
+        assert_eq!(game.info().extra, [("ROUND".to_string(), "3".to_string())]);
+        assert_eq!(usi(&game), ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+        let elapsed: Vec<_> = game.history().iter().map(|entry| entry.elapsed).collect();
+        assert_eq!(elapsed, [Some(12), Some(3), Some(40), Some(1), Some(7)]);
+        assert_eq!(game.comment(0), "開始");
+        assert_eq!(game.comment(2), "角道");
+        // Gote, to move after the bishop drop, resigns.
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: EndReason::Resignation,
+            })
+        );
+    }
+
+    #[test]
+    fn reads_handicap_from_pi() {
+        let game = parse_text("PI82HI22KA\n-\n-3334FU\n+7776FU\n%TORYO\n");
+        assert_eq!(
+            game.start_sfen(),
+            Handicap::from_kif_name("二枚落ち").unwrap().sfen
+        );
+        assert_eq!(usi(&game), ["3c3d", "7g7f"]);
+        assert_eq!(
+            game.result().map(|result| result.winner),
+            Some(Some(Color::Black))
+        );
+    }
+
+    #[test]
+    fn reads_placements_and_hands() {
+        let game = parse_text("P-51OU\nP+59OU\nP+00KI\nP-00FU\n+\n+0052KI\n");
+        assert_eq!(game.start_sfen(), "4k4/9/9/9/9/9/9/9/4K4 b Gp 1");
+        assert_eq!(usi(&game), ["G*5b"]);
+    }
+
+    #[test]
+    fn rejects_a_move_out_of_turn() {
+        crate::core::init();
+        let Err(err) = parse("PI\n+\n-3334FU\n") else {
+            panic!("Gote cannot move first in an even game");
+        };
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn round_trips() {
+        let game = parse_text(EVEN_GAME);
+        let written = write(&game);
+        assert_eq!(write(&parse_text(&written)), written);
+
+        let handicap = parse_text("PI82HI22KA\n-\n-3334FU\n%TORYO\n");
+        let written = write(&handicap);
+        assert!(written.contains("\nPI22KA82HI\n-\n"), "{written}");
+        assert_eq!(parse_text(&written).start_sfen(), handicap.start_sfen());
+    }
+
+    #[test]
+    fn survives_a_trip_through_kif() {
+        let game = parse_text(EVEN_GAME);
+        let through_kif = kif::parse(&kif::write(&game)).expect("written KIF reads back");
+        assert_eq!(write(&through_kif), write(&game));
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashSet;
+use std::fmt;
 
-use shogi::{Color, Move, Piece, PieceType, Position, Square};
+use shogi::{Color, Move, MoveError, Piece, PieceType, Position, SfenError, Square};
 
-const START_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+use super::movegen::{Board, LegalMoves, SquareSet};
+use super::sfen::{BoardSetup, HAND_PIECE_TYPES};
+use super::usi::parse_usi_move;
+use super::variation::{NodeId, ROOT, VariationTree};
+
+pub const START_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
 #[allow(dead_code)]
 const BOARD_SIZE: u8 = 9;
 
@@ -13,13 +18,122 @@ struct PendingPromotion {
     non_promote: Move,
 }
 
+/// One ply of the game record, captured at the time the move was played.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveEntry {
+    pub mv: Move,
+    pub piece: Piece,
+    pub captured: Option<Piece>,
+    pub comment: String,
+    /// Thinking time spent on this move, in seconds.
+    pub elapsed: Option<u32>,
+}
+
+impl MoveEntry {
+    pub fn from(&self) -> Option<Square> {
+        match self.mv {
+            Move::Normal { from, .. } => Some(from),
+            Move::Drop { .. } => None,
+        }
+    }
+
+    pub fn to(&self) -> Square {
+        match self.mv {
+            Move::Normal { to, .. } | Move::Drop { to, .. } => to,
+        }
+    }
+}
+
+/// Record metadata that survives import and export.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameInfo {
+    pub black: Option<String>,
+    pub white: Option<String>,
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub time_control: Option<String>,
+    pub opening: Option<String>,
+    /// Headers without a dedicated field, kept in file order.
+    pub extra: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    Resignation,
+    Checkmate,
+    /// The side to move has no legal move without being in check.
+    NoLegalMoves,
+    Repetition,
+    PerpetualCheck,
+    Impasse,
+    Timeout,
+    IllegalMove,
+    DrawAgreement,
+    Interrupted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    /// `None` for draws and unfinished games.
+    pub winner: Option<Color>,
+    pub reason: EndReason,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            EndReason::Resignation => "resignation",
+            EndReason::Checkmate => "checkmate",
+            EndReason::NoLegalMoves => "no legal moves",
+            EndReason::Repetition => "repetition",
+            EndReason::PerpetualCheck => "perpetual check",
+            EndReason::Impasse => "impasse",
+            EndReason::Timeout => "timeout",
+            EndReason::IllegalMove => "illegal move",
+            EndReason::DrawAgreement => "agreement",
+            EndReason::Interrupted => return write!(f, "Game interrupted"),
+        };
+        match self.winner {
+            Some(winner) => write!(f, "{} wins by {reason}", color_name(winner)),
+            None => write!(f, "Draw by {reason}"),
+        }
+    }
+}
+
+/// Point rule used to judge an impasse (entering king) declaration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImpasseRule {
+    /// 31 points or more wins, 24 to 30 points draws.
+    TwentyFourPoint,
+    /// Sente needs 28 points and Gote 27 to win.
+    TwentySevenPoint,
+}
+
 #[derive(Default)]
 pub struct GameState {
     pos: Position,
+    start_sfen: String,
+    tree: VariationTree,
+    /// Nodes of the displayed line, from the first move to the line's end.
+    line: Vec<NodeId>,
+    /// Entries of `line`, kept in step with the tree.
+    history: Vec<MoveEntry>,
+    cursor: usize,
+    info: GameInfo,
+    start_comment: String,
+    /// Set when `pos` applied a move that shogi-rs rejected as a repetition,
+    /// leaving its internal move list one short of the board.
+    pos_out_of_sync: bool,
     selected: Option<Square>,
     selected_hand: Option<PieceType>,
+    /// Legal moves of the displayed position, generated on first use after
+    /// each change of position.
+    legal: Option<LegalMoves>,
+    /// Moves of the selected piece, taken from `legal`.
     legal_moves: Vec<Move>,
-    legal_destinations: HashSet<Square>,
+    legal_destinations: SquareSet,
     pending_promotion: Option<PendingPromotion>,
     last_action_from: Option<Square>,
     last_action_to: Option<Square>,
@@ -27,34 +141,154 @@ pub struct GameState {
     status: String,
 }
 
+#[derive(Debug)]
+pub enum SfenLoadError {
+    Empty,
+    Invalid {
+        sfen: String,
+        reason: SfenError,
+    },
+    /// A position was expected, but the text goes on with `moves`.
+    UnexpectedMoves,
+    /// A move after `moves` does not parse or is not legal; `ply` counts from 1.
+    IllegalMove {
+        ply: usize,
+        text: String,
+    },
+}
+
+impl fmt::Display for SfenLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty SFEN"),
+            Self::Invalid { sfen, reason } => write!(f, "invalid SFEN `{sfen}`: {reason:?}"),
+            Self::UnexpectedMoves => write!(f, "expected a position, found a move list"),
+            Self::IllegalMove { ply, text } => write!(f, "illegal move {ply} `{text}`"),
+        }
+    }
+}
+
+impl std::error::Error for SfenLoadError {}
+
+/// Normalizes a position-only SFEN: drops a leading `sfen` keyword and adds
+/// a missing move number. A trailing `moves` list is rejected; use
+/// [`GameState::from_sfen`] to play it.
+pub(crate) fn normalize_sfen(input: &str) -> Result<String, SfenLoadError> {
+    let (position, moves) = split_sfen_moves(input);
+    if moves.is_some() {
+        return Err(SfenLoadError::UnexpectedMoves);
+    }
+    let fields: Vec<&str> = position.split_whitespace().collect();
+    match fields.len() {
+        0 => Err(SfenLoadError::Empty),
+        3 => Ok(format!("{} 1", fields.join(" "))),
+        _ => Ok(fields.join(" ")),
+    }
+}
+
+/// Splits `input` at a `moves` keyword into the position and the USI moves
+/// after it, dropping a leading `sfen` keyword.
+fn split_sfen_moves(input: &str) -> (&str, Option<&str>) {
+    let trimmed = input.trim();
+    let trimmed = trimmed.strip_prefix("sfen ").unwrap_or(trimmed);
+    match trimmed.split_once(" moves") {
+        Some((position, moves)) if moves.is_empty() || moves.starts_with(char::is_whitespace) => {
+            (position, Some(moves))
+        }
+        _ => (trimmed, None),
+    }
+}
+
+/// SFEN without the move number, for comparing positions.
+pub(crate) fn position_key(sfen: &str) -> String {
+    sfen.split_whitespace()
+        .take(3)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SoundCue {
     Move,
     Capture,
     Error,
+    Victory,
+    Defeat,
+    Draw,
+}
+
+impl SoundCue {
+    /// Victory, defeat or draw cue for `result` as heard by `viewer`.
+    pub fn for_result(result: GameResult, viewer: Color) -> Self {
+        match result.winner {
+            None => Self::Draw,
+            Some(winner) if winner == viewer => Self::Victory,
+            Some(_) => Self::Defeat,
+        }
+    }
 }
 
 #[allow(dead_code)]
 impl GameState {
     pub fn new() -> Self {
+        Self::from_sfen(START_SFEN).unwrap_or_else(|err| Self {
+            status: format!("Failed to load initial SFEN: {err}"),
+            ..Self::default()
+        })
+    }
+
+    /// Builds a fresh game whose record starts at `sfen`.
+    ///
+    /// A leading `sfen` keyword is accepted and a missing move number defaults to 1.
+    /// Moves after a `moves` keyword are played into the record, and the game
+    /// shows the position after the last of them.
+    pub fn from_sfen(sfen: &str) -> Result<Self, SfenLoadError> {
+        let (position, moves) = split_sfen_moves(sfen);
+        let mut game = Self::from_position_sfen(position)?;
+        for (idx, text) in moves.unwrap_or_default().split_whitespace().enumerate() {
+            let illegal = || SfenLoadError::IllegalMove {
+                ply: idx + 1,
+                text: text.to_string(),
+            };
+            let mv = parse_usi_move(text).ok_or_else(illegal)?;
+            game.push_move(mv).map_err(|_| illegal())?;
+        }
+        Ok(game)
+    }
+
+    fn from_position_sfen(sfen: &str) -> Result<Self, SfenLoadError> {
+        let sfen = normalize_sfen(sfen)?;
         let mut pos = Position::new();
-        let mut status = String::new();
-        if let Err(err) = pos.set_sfen(START_SFEN) {
-            status = format!("Failed to load initial SFEN: {err:?}");
+        if let Err(reason) = pos.set_sfen(&sfen) {
+            return Err(SfenLoadError::Invalid { sfen, reason });
         }
 
-        Self {
+        Ok(Self {
             pos,
+            start_sfen: sfen,
+            tree: VariationTree::default(),
+            line: Vec::new(),
+            history: Vec::new(),
+            cursor: 0,
+            info: GameInfo::default(),
+            start_comment: String::new(),
+            pos_out_of_sync: false,
             selected: None,
             selected_hand: None,
+            legal: None,
             legal_moves: Vec::new(),
-            legal_destinations: HashSet::new(),
+            legal_destinations: SquareSet::default(),
             pending_promotion: None,
             last_action_from: None,
             last_action_to: None,
             pending_sound: None,
-            status,
-        }
+            status: String::new(),
+        })
+    }
+
+    /// SFEN of the displayed position, with its move number.
+    pub fn to_sfen(&self) -> String {
+        BoardSetup::from_position(&self.pos).to_sfen()
     }
 
     pub fn side_to_move(&self) -> Color {
@@ -65,10 +299,268 @@ impl GameState {
         self.pos.ply()
     }
 
+    /// Number of moves from the start position to the displayed position.
+    pub fn current_ply(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves of the displayed line, including those after the displayed position.
+    pub fn history(&self) -> &[MoveEntry] {
+        &self.history
+    }
+
+    pub fn tree(&self) -> &VariationTree {
+        &self.tree
+    }
+
+    pub fn start_sfen(&self) -> &str {
+        &self.start_sfen
+    }
+
+    pub fn info(&self) -> &GameInfo {
+        &self.info
+    }
+
+    pub fn info_mut(&mut self) -> &mut GameInfo {
+        &mut self.info
+    }
+
+    /// How the displayed line ends.
+    pub fn result(&self) -> Option<GameResult> {
+        self.tree.result(self.node_at(self.history.len()))
+    }
+
+    pub fn set_result(&mut self, result: Option<GameResult>) {
+        let end = self.node_at(self.history.len());
+        self.tree.set_result(end, result);
+    }
+
+    /// Comment shown at `ply`; ply 0 is the start position.
+    pub fn comment(&self, ply: usize) -> &str {
+        match ply.checked_sub(1) {
+            None => &self.start_comment,
+            Some(idx) => self.history.get(idx).map_or("", |entry| &entry.comment),
+        }
+    }
+
+    pub fn set_comment(&mut self, ply: usize, comment: impl Into<String>) {
+        let comment = comment.into();
+        if ply == 0 {
+            self.start_comment = comment;
+        } else {
+            self.update_entry(ply, |entry| entry.comment = comment.clone());
+        }
+    }
+
+    pub fn set_elapsed(&mut self, ply: usize, seconds: Option<u32>) {
+        self.update_entry(ply, |entry| entry.elapsed = seconds);
+    }
+
+    /// Edits the displayed line's move at `ply` in the tree and in `history`.
+    fn update_entry(&mut self, ply: usize, edit: impl Fn(&mut MoveEntry)) {
+        let Some(idx) = ply.checked_sub(1).filter(|&idx| idx < self.line.len()) else {
+            return;
+        };
+        edit(self.tree.entry_mut(self.line[idx]));
+        edit(&mut self.history[idx]);
+    }
+
+    /// Tree node of the displayed line after `ply` moves; the root for ply 0.
+    fn node_at(&self, ply: usize) -> NodeId {
+        ply.checked_sub(1).map_or(ROOT, |idx| self.line[idx])
+    }
+
+    /// Makes the displayed line run through `node`, which follows the
+    /// displayed position at `ply`, and on along its main continuation.
+    fn set_line(&mut self, ply: usize, node: NodeId) {
+        self.line.truncate(ply);
+        self.line.push(node);
+        self.line.extend(self.tree.main_line(node));
+        self.history.truncate(ply);
+        self.history.extend(
+            self.line[ply..]
+                .iter()
+                .map(|&id| self.tree.entry(id).clone()),
+        );
+    }
+
+    /// Moves recorded as move `ply` after the displayed line's first
+    /// `ply - 1` moves, main line first.
+    pub fn variations(&self, ply: usize) -> Vec<&MoveEntry> {
+        let Some(parent) = ply.checked_sub(1).filter(|&idx| idx <= self.line.len()) else {
+            return Vec::new();
+        };
+        let parent = self.node_at(parent);
+        self.tree
+            .children(parent)
+            .iter()
+            .map(|&id| self.tree.entry(id))
+            .collect()
+    }
+
+    /// Index in [`Self::variations`] of the displayed line's move `ply`.
+    pub fn variation_index(&self, ply: usize) -> Option<usize> {
+        let idx = ply.checked_sub(1)?;
+        self.line.get(idx).map(|&id| self.tree.sibling_index(id))
+    }
+
+    /// Whether the displayed line follows the main line up to `ply`.
+    pub fn is_main_line(&self, ply: usize) -> bool {
+        self.line[..ply.min(self.line.len())]
+            .iter()
+            .all(|&id| self.tree.sibling_index(id) == 0)
+    }
+
+    /// Shows the line that plays the `index`-th of [`Self::variations`] as
+    /// move `ply`, displaying the position after it.
+    pub fn select_variation(&mut self, ply: usize, index: usize) -> bool {
+        let Some(idx) = ply.checked_sub(1).filter(|&idx| idx <= self.line.len()) else {
+            return false;
+        };
+        let Some(&node) = self.tree.children(self.node_at(idx)).get(index) else {
+            return false;
+        };
+        if self.line.get(idx) != Some(&node) {
+            if self.cursor > idx {
+                self.jump_to_ply(idx);
+            }
+            self.set_line(idx, node);
+        }
+        self.jump_to_ply(ply);
+        self.cursor == ply
+    }
+
+    /// Makes the displayed line's move `ply` the main continuation of the
+    /// position before it.
+    pub fn promote_variation(&mut self, ply: usize) {
+        if let Some(idx) = ply.checked_sub(1).filter(|&idx| idx < self.line.len()) {
+            self.tree.promote(self.line[idx]);
+        }
+    }
+
+    /// Makes the whole displayed line the main line.
+    pub fn promote_to_main_line(&mut self) {
+        for &id in &self.line {
+            self.tree.promote(id);
+        }
+    }
+
+    /// Deletes the displayed line's move `ply` with everything after it and
+    /// shows the position before it, continuing along the main line.
+    pub fn delete_variation(&mut self, ply: usize) -> bool {
+        let Some(idx) = ply.checked_sub(1).filter(|&idx| idx < self.line.len()) else {
+            return false;
+        };
+        if self.cursor > idx {
+            self.jump_to_ply(idx);
+        }
+        self.tree.remove(self.line[idx]);
+        self.line.truncate(idx);
+        self.history.truncate(idx);
+        if let Some(&next) = self.tree.children(self.node_at(idx)).first() {
+            self.set_line(idx, next);
+        }
+        true
+    }
+
+    /// Shows the main line and its final position.
+    pub fn show_main_line(&mut self) {
+        self.jump_to_ply(0);
+        self.line.clear();
+        self.history.clear();
+        if let Some(&first) = self.tree.children(ROOT).first() {
+            self.set_line(0, first);
+        }
+        self.jump_to_ply(self.history.len());
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.history.len()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        self.jump_to_ply(self.cursor - 1)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.jump_to_ply(self.cursor + 1)
+    }
+
+    /// Moves the displayed position to `ply` moves after the start, keeping the
+    /// record intact so later moves can be redone.
+    pub fn jump_to_ply(&mut self, ply: usize) -> bool {
+        let target = ply.min(self.history.len());
+        if target == self.cursor {
+            return false;
+        }
+        let forward = target > self.cursor;
+
+        if !forward
+            && self.pos_out_of_sync
+            && let Err(err) = self.rebuild_position(target)
+        {
+            self.status = format!("Undo failed: {err:?}");
+        }
+        while self.cursor > target {
+            if let Err(err) = self.pos.unmake_move() {
+                self.status = format!("Undo failed: {err:?}");
+                break;
+            }
+            self.cursor -= 1;
+        }
+        while self.cursor < target {
+            let mv = self.history[self.cursor].mv;
+            if let Err(err) = self.apply_recorded_move(mv) {
+                self.status = format!("Redo failed: {err:?}");
+                break;
+            }
+            self.cursor += 1;
+        }
+        self.legal = None;
+        self.pending_promotion = None;
+        self.clear_selection();
+        self.sync_last_action();
+        if self.cursor != target {
+            self.pending_sound = Some(SoundCue::Error);
+            return false;
+        }
+
+        self.status = match self.result() {
+            Some(result) if self.is_game_over() => result.to_string(),
+            _ => String::new(),
+        };
+        let captured = forward && self.history[self.cursor - 1].captured.is_some();
+        self.pending_sound = Some(if captured {
+            SoundCue::Capture
+        } else {
+            SoundCue::Move
+        });
+        true
+    }
+
     pub fn status(&self) -> &str {
         &self.status
     }
 
+    pub fn set_status(&mut self, status: impl Into<String>) {
+        self.status = status.into();
+    }
+
+    pub fn report_error(&mut self, status: impl Into<String>) {
+        self.status = status.into();
+        self.pending_sound = Some(SoundCue::Error);
+    }
+
     pub fn selected(&self) -> Option<Square> {
         self.selected
     }
@@ -98,7 +590,12 @@ impl GameState {
     }
 
     pub fn is_legal_destination(&self, sq: Square) -> bool {
-        self.legal_destinations.contains(&sq)
+        self.legal_destinations.contains(sq)
+    }
+
+    /// The displayed position.
+    pub fn position(&self) -> &Position {
+        &self.pos
     }
 
     pub fn piece_at(&self, sq: Square) -> Option<Piece> {
@@ -110,7 +607,7 @@ impl GameState {
     }
 
     pub fn select_hand_piece(&mut self, piece_type: PieceType) {
-        if self.pending_promotion.is_some() {
+        if self.pending_promotion.is_some() || self.is_game_over() {
             return;
         }
 
@@ -127,18 +624,12 @@ impl GameState {
 
         self.selected = None;
         self.selected_hand = Some(piece_type);
-        self.legal_moves = self.legal_drops_for(piece_type);
-        self.legal_destinations = self
-            .legal_moves
-            .iter()
-            .map(|m| match *m {
-                Move::Normal { to, .. } | Move::Drop { to, .. } => to,
-            })
-            .collect();
+        self.legal_moves = self.legal().drops(piece_type).to_vec();
+        self.legal_destinations = self.legal().drop_targets(piece_type);
     }
 
     pub fn on_square_clicked(&mut self, sq: Square) {
-        if self.pending_promotion.is_some() {
+        if self.pending_promotion.is_some() || self.is_game_over() {
             return;
         }
 
@@ -146,14 +637,14 @@ impl GameState {
         let side_to_move = self.pos.side_to_move();
 
         if let Some(piece_type) = self.selected_hand {
-            if self.legal_destinations.contains(&sq) {
+            if self.legal_destinations.contains(sq) {
                 self.execute_move(Move::Drop { to: sq, piece_type });
                 return;
             }
 
             if let Some(piece) = clicked_piece {
                 if piece.color == side_to_move {
-                    self.select_square(sq, piece);
+                    self.select_square(sq);
                 } else {
                     self.clear_selection();
                 }
@@ -169,7 +660,7 @@ impl GameState {
                 return;
             }
 
-            if self.legal_destinations.contains(&sq) {
+            if self.legal_destinations.contains(sq) {
                 match self.pick_move_to(sq) {
                     MoveChoice::Single(chosen) => self.execute_move(chosen),
                     MoveChoice::NeedsPromotion {
@@ -189,7 +680,7 @@ impl GameState {
 
         if let Some(piece) = clicked_piece {
             if piece.color == side_to_move {
-                self.select_square(sq, piece);
+                self.select_square(sq);
             } else {
                 self.clear_selection();
             }
@@ -198,6 +689,29 @@ impl GameState {
         }
     }
 
+    /// Clicks the piece `mv` moves and then its destination, so a move that
+    /// may also be played promoted asks for the choice like on the board.
+    pub fn enter_move(&mut self, mv: Move) {
+        self.clear_selection();
+        match mv {
+            Move::Normal { from, .. } => self.preview_board_drag_from(from),
+            Move::Drop { piece_type, .. } => self.select_hand_piece(piece_type),
+        }
+        if self.selected.is_some() || self.selected_hand.is_some() {
+            self.on_square_clicked(match mv {
+                Move::Normal { to, .. } | Move::Drop { to, .. } => to,
+            });
+        }
+    }
+
+    /// Every legal move of the side to move in the displayed position.
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        if self.is_game_over() {
+            return Vec::new();
+        }
+        self.legal().all().to_vec()
+    }
+
     pub fn choose_promotion(&mut self, promote: bool) {
         if let Some(pending) = self.pending_promotion.take() {
             let mv = if promote {
@@ -218,7 +732,7 @@ impl GameState {
     }
 
     pub fn perform_board_drag(&mut self, from: Square, to: Square) {
-        if self.pending_promotion.is_some() {
+        if self.pending_promotion.is_some() || self.is_game_over() {
             return;
         }
         if from == to {
@@ -226,16 +740,16 @@ impl GameState {
             return;
         }
 
-        let piece = match *self.pos.piece_at(from) {
-            Some(piece) if piece.color == self.pos.side_to_move() => piece,
-            _ => {
-                self.clear_selection();
-                return;
-            }
-        };
+        if self
+            .piece_at(from)
+            .is_none_or(|piece| piece.color != self.pos.side_to_move())
+        {
+            self.clear_selection();
+            return;
+        }
 
-        self.select_square(from, piece);
-        if !self.legal_destinations.contains(&to) {
+        self.select_square(from);
+        if !self.legal_destinations.contains(to) {
             self.clear_selection();
             return;
         }
@@ -256,7 +770,7 @@ impl GameState {
     }
 
     pub fn perform_hand_drag(&mut self, piece_type: PieceType, to: Square) {
-        if self.pending_promotion.is_some() {
+        if self.pending_promotion.is_some() || self.is_game_over() {
             return;
         }
 
@@ -268,16 +782,10 @@ impl GameState {
 
         self.selected = None;
         self.selected_hand = Some(piece_type);
-        self.legal_moves = self.legal_drops_for(piece_type);
-        self.legal_destinations = self
-            .legal_moves
-            .iter()
-            .map(|m| match *m {
-                Move::Normal { to, .. } | Move::Drop { to, .. } => to,
-            })
-            .collect();
+        self.legal_moves = self.legal().drops(piece_type).to_vec();
+        self.legal_destinations = self.legal().drop_targets(piece_type);
 
-        if self.legal_destinations.contains(&to) {
+        if self.legal_destinations.contains(to) {
             self.execute_move(Move::Drop { to, piece_type });
         } else {
             self.clear_selection();
@@ -285,17 +793,17 @@ impl GameState {
     }
 
     pub fn preview_board_drag_from(&mut self, from: Square) {
-        if self.pending_promotion.is_some() {
+        if self.pending_promotion.is_some() || self.is_game_over() {
             return;
         }
-        let piece = match *self.pos.piece_at(from) {
-            Some(piece) if piece.color == self.pos.side_to_move() => piece,
-            _ => {
-                self.clear_selection();
-                return;
-            }
-        };
-        self.select_square(from, piece);
+        if self
+            .piece_at(from)
+            .is_none_or(|piece| piece.color != self.pos.side_to_move())
+        {
+            self.clear_selection();
+            return;
+        }
+        self.select_square(from);
     }
 
     pub fn preview_hand_drag_from(&mut self, piece_type: PieceType) {
@@ -318,24 +826,31 @@ impl GameState {
         }
     }
 
-    fn select_square(&mut self, sq: Square, piece: Piece) {
+    fn select_square(&mut self, sq: Square) {
         self.selected = Some(sq);
         self.selected_hand = None;
-        self.legal_moves = self.legal_moves_from(sq, piece);
-        self.legal_destinations = self
-            .legal_moves
-            .iter()
-            .map(|m| match *m {
-                Move::Normal { to, .. } | Move::Drop { to, .. } => to,
-            })
-            .collect();
+        self.legal_moves = self.legal().from_square(sq).to_vec();
+        self.legal_destinations = self.legal().targets_from(sq);
+    }
+
+    fn sync_last_action(&mut self) {
+        match self.cursor.checked_sub(1).map(|idx| &self.history[idx]) {
+            Some(entry) => {
+                self.last_action_from = entry.from();
+                self.last_action_to = Some(entry.to());
+            }
+            None => {
+                self.last_action_from = None;
+                self.last_action_to = None;
+            }
+        }
     }
 
     fn clear_selection(&mut self) {
         self.selected = None;
         self.selected_hand = None;
         self.legal_moves.clear();
-        self.legal_destinations.clear();
+        self.legal_destinations = SquareSet::default();
     }
 
     fn pick_move_to(&self, to: Square) -> MoveChoice {
@@ -374,75 +889,103 @@ impl GameState {
         }
     }
 
-    fn legal_moves_from(&mut self, from: Square, piece: Piece) -> Vec<Move> {
-        let mut out = Vec::new();
-        let candidates = self.pos.move_candidates(from, piece);
-        for to in candidates {
-            let normal = Move::Normal {
-                from,
-                to,
-                promote: false,
-            };
-            if self.try_move_legality(normal) {
-                out.push(normal);
-            }
+    /// Legal moves of the displayed position, generated once per position.
+    fn legal(&mut self) -> &LegalMoves {
+        let pos = &self.pos;
+        self.legal
+            .get_or_insert_with(|| Board::from_position(pos).legal_moves())
+    }
 
-            let promote = Move::Normal {
-                from,
-                to,
-                promote: true,
-            };
-            if self.try_move_legality(promote) {
-                out.push(promote);
+    /// Applies a move that is already part of the record.
+    fn apply_recorded_move(&mut self, mv: Move) -> Result<(), MoveError> {
+        match self.pos.make_move(mv) {
+            Err(err) if ends_by_repetition(&err) => {
+                self.pos_out_of_sync = true;
+                Ok(())
             }
+            other => other,
         }
-        out
     }
 
-    fn legal_drops_for(&mut self, piece_type: PieceType) -> Vec<Move> {
-        let mut out = Vec::new();
-        for file in 0..BOARD_SIZE {
-            for rank in 0..BOARD_SIZE {
-                let to = Square::new(file, rank).expect("valid board coordinate");
-                if self.pos.piece_at(to).is_some() {
-                    continue;
-                }
-                let drop_mv = Move::Drop { to, piece_type };
-                if self.try_move_legality(drop_mv) {
-                    out.push(drop_mv);
-                }
-            }
+    /// Resets `pos` to the start position and replays the first `ply` moves.
+    fn rebuild_position(&mut self, ply: usize) -> Result<(), MoveError> {
+        let mut pos = Position::new();
+        pos.set_sfen(&self.start_sfen)
+            .expect("start SFEN was validated when the game was created");
+        self.pos = pos;
+        self.legal = None;
+        self.pos_out_of_sync = false;
+        self.cursor = 0;
+        while self.cursor < ply {
+            let mv = self.history[self.cursor].mv;
+            self.apply_recorded_move(mv)?;
+            self.cursor += 1;
         }
-        out
+        Ok(())
     }
 
-    fn try_move_legality(&mut self, mv: Move) -> bool {
-        if self.pos.make_move(mv).is_ok() {
-            let _ = self.pos.unmake_move();
-            true
-        } else {
-            false
+    /// Plays `mv` at the displayed position and records it. A move that differs
+    /// from the recorded continuations starts a new variation.
+    pub fn push_move(&mut self, mv: Move) -> Result<(), MoveError> {
+        let piece = match mv {
+            Move::Normal { from, .. } => *self.pos.piece_at(from),
+            Move::Drop { piece_type, .. } => Some(Piece {
+                piece_type,
+                color: self.pos.side_to_move(),
+            }),
+        };
+        let captured = match mv {
+            Move::Normal { to, .. } => *self.pos.piece_at(to),
+            Move::Drop { .. } => None,
+        };
+        let repetition = match self.pos.make_move(mv) {
+            Ok(()) => None,
+            Err(err) if ends_by_repetition(&err) => Some(err),
+            Err(err) => return Err(err),
+        };
+        self.legal = None;
+
+        let parent = self.node_at(self.cursor);
+        let node = match self.tree.find_child(parent, mv) {
+            Some(node) => node,
+            None => self.tree.add_child(
+                parent,
+                MoveEntry {
+                    mv,
+                    piece: piece.expect("a legal move starts from an occupied square"),
+                    captured,
+                    comment: String::new(),
+                    elapsed: None,
+                },
+            ),
+        };
+        if self.line.get(self.cursor) != Some(&node) {
+            self.set_line(self.cursor, node);
         }
+        self.cursor += 1;
+        if let Some(err) = repetition {
+            self.pos_out_of_sync = true;
+            let result = repetition_result(&err, self.pos.side_to_move());
+            self.tree.set_result(node, Some(result));
+        }
+        Ok(())
+    }
+
+    /// Plays `mv` as if it had been entered on the board, with the same
+    /// sounds, highlighting and result detection.
+    pub fn play_move(&mut self, mv: Move) -> bool {
+        let ply = self.cursor;
+        self.clear_selection();
+        self.execute_move(mv);
+        self.cursor == ply + 1
     }
 
     fn execute_move(&mut self, mv: Move) {
-        let is_capture = match mv {
-            Move::Normal { to, .. } => self.pos.piece_at(to).is_some(),
-            Move::Drop { .. } => false,
-        };
         self.pending_promotion = None;
-        match self.pos.make_move(mv) {
+        match self.push_move(mv) {
             Ok(()) => {
-                match mv {
-                    Move::Normal { from, to, .. } => {
-                        self.last_action_from = Some(from);
-                        self.last_action_to = Some(to);
-                    }
-                    Move::Drop { to, .. } => {
-                        self.last_action_from = None;
-                        self.last_action_to = Some(to);
-                    }
-                }
+                let is_capture = self.history[self.cursor - 1].captured.is_some();
+                self.sync_last_action();
                 self.pending_sound = Some(if is_capture {
                     SoundCue::Capture
                 } else {
@@ -450,6 +993,11 @@ impl GameState {
                 });
                 self.status.clear();
                 self.clear_selection();
+                let mover = self.history[self.cursor - 1].piece.color;
+                let recorded = self.tree.result(self.node_at(self.cursor));
+                if let Some(result) = recorded.or_else(|| self.detect_result()) {
+                    self.finish(result, mover);
+                }
             }
             Err(err) => {
                 self.pending_sound = Some(SoundCue::Error);
@@ -458,6 +1006,197 @@ impl GameState {
             }
         }
     }
+
+    /// Whether the displayed position is the final position of a finished game.
+    pub fn is_game_over(&self) -> bool {
+        self.cursor == self.history.len() && self.result().is_some()
+    }
+
+    /// Result forced by the displayed position: checkmate or no legal moves.
+    ///
+    /// Repetition is detected while the move is played, see [`Self::push_move`].
+    pub fn detect_result(&mut self) -> Option<GameResult> {
+        if self.pos_out_of_sync || self.has_legal_move() {
+            return None;
+        }
+        let side = self.pos.side_to_move();
+        let reason = if self.pos.in_check(side) {
+            EndReason::Checkmate
+        } else {
+            EndReason::NoLegalMoves
+        };
+        Some(GameResult {
+            winner: Some(side.flip()),
+            reason,
+        })
+    }
+
+    /// `color` resigns.
+    pub fn resign(&mut self, color: Color) {
+        self.forfeit(color, EndReason::Resignation);
+    }
+
+    /// Ends the game at the current ply with `color` losing for `reason`.
+    pub fn forfeit(&mut self, color: Color, reason: EndReason) {
+        if self.is_game_over() {
+            return;
+        }
+        self.end_at_cursor(
+            GameResult {
+                winner: Some(color.flip()),
+                reason,
+            },
+            color,
+        );
+    }
+
+    pub fn agree_draw(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+        let side = self.pos.side_to_move();
+        self.end_at_cursor(
+            GameResult {
+                winner: None,
+                reason: EndReason::DrawAgreement,
+            },
+            side,
+        );
+    }
+
+    /// The side to move declares an impasse win under `rule`; a declaration
+    /// that does not meet the conditions loses.
+    pub fn declare_impasse(&mut self, rule: ImpasseRule) {
+        if self.is_game_over() {
+            return;
+        }
+        let side = self.pos.side_to_move();
+        let result = match self.impasse_points(side) {
+            Some(points) if rule.wins(side, points) => GameResult {
+                winner: Some(side),
+                reason: EndReason::Impasse,
+            },
+            Some(points) if rule == ImpasseRule::TwentyFourPoint && points >= 24 => GameResult {
+                winner: None,
+                reason: EndReason::Impasse,
+            },
+            _ => GameResult {
+                winner: Some(side.flip()),
+                reason: EndReason::IllegalMove,
+            },
+        };
+        self.end_at_cursor(result, side);
+    }
+
+    /// Declaration points of `color`, or `None` when the king has not entered
+    /// the enemy camp with ten other pieces or is in check.
+    pub fn impasse_points(&self, color: Color) -> Option<u32> {
+        let in_camp = |sq: Square| match color {
+            Color::Black => sq.rank() <= 2,
+            Color::White => sq.rank() >= 6,
+        };
+        let value = |piece_type: PieceType| match unpromoted_piece_type(piece_type) {
+            PieceType::Rook | PieceType::Bishop => 5,
+            _ => 1,
+        };
+
+        let mut king_entered = false;
+        let mut pieces = 0;
+        let mut points = 0;
+        for file in 0..BOARD_SIZE {
+            for rank in 0..BOARD_SIZE {
+                let sq = Square::new(file, rank).expect("valid board coordinate");
+                let Some(piece) = *self.pos.piece_at(sq) else {
+                    continue;
+                };
+                if piece.color != color || !in_camp(sq) {
+                    continue;
+                }
+                if piece.piece_type == PieceType::King {
+                    king_entered = true;
+                } else {
+                    pieces += 1;
+                    points += value(piece.piece_type);
+                }
+            }
+        }
+        if !king_entered || pieces < 10 || self.pos.in_check(color) {
+            return None;
+        }
+        for piece_type in HAND_PIECE_TYPES {
+            points += value(piece_type) * u32::from(self.hand_count(color, piece_type));
+        }
+        Some(points)
+    }
+
+    /// Ends the game at the displayed position, discarding any later moves.
+    fn end_at_cursor(&mut self, result: GameResult, actor: Color) {
+        self.tree.truncate(self.node_at(self.cursor));
+        self.line.truncate(self.cursor);
+        self.history.truncate(self.cursor);
+        self.pending_promotion = None;
+        self.clear_selection();
+        self.finish(result, actor);
+    }
+
+    /// Records `result` and plays the cue from the point of view of `actor`,
+    /// the side whose move or declaration ended the game.
+    fn finish(&mut self, result: GameResult, actor: Color) {
+        self.tree
+            .set_result(self.node_at(self.cursor), Some(result));
+        self.status = result.to_string();
+        self.pending_sound = Some(SoundCue::for_result(result, actor));
+    }
+
+    fn has_legal_move(&mut self) -> bool {
+        !self.legal().is_empty()
+    }
+}
+
+impl ImpasseRule {
+    fn wins(self, color: Color, points: u32) -> bool {
+        match self {
+            Self::TwentyFourPoint => points >= 31,
+            Self::TwentySevenPoint => match color {
+                Color::Black => points >= 28,
+                Color::White => points >= 27,
+            },
+        }
+    }
+}
+
+fn ends_by_repetition(err: &MoveError) -> bool {
+    matches!(
+        err,
+        MoveError::Repetition | MoveError::PerpetualCheckWin | MoveError::PerpetualCheckLose
+    )
+}
+
+/// Maps a repetition error raised by the move just played. shogi-rs reports
+/// perpetual check from the point of view of the player who made that move.
+fn repetition_result(err: &MoveError, side_to_move: Color) -> GameResult {
+    let mover = side_to_move.flip();
+    match err {
+        MoveError::PerpetualCheckWin => GameResult {
+            winner: Some(mover),
+            reason: EndReason::PerpetualCheck,
+        },
+        MoveError::PerpetualCheckLose => GameResult {
+            winner: Some(side_to_move),
+            reason: EndReason::PerpetualCheck,
+        },
+        _ => GameResult {
+            winner: None,
+            reason: EndReason::Repetition,
+        },
+    }
+}
+
+pub fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::Black => "Sente",
+        Color::White => "Gote",
+    }
 }
 
 enum MoveChoice {
@@ -512,6 +1251,19 @@ pub fn piece_type_label(piece_type: PieceType) -> &'static str {
     }
 }
 
+#[allow(dead_code)]
+pub fn unpromoted_piece_type(piece_type: PieceType) -> PieceType {
+    match piece_type {
+        PieceType::ProRook => PieceType::Rook,
+        PieceType::ProBishop => PieceType::Bishop,
+        PieceType::ProSilver => PieceType::Silver,
+        PieceType::ProKnight => PieceType::Knight,
+        PieceType::ProLance => PieceType::Lance,
+        PieceType::ProPawn => PieceType::Pawn,
+        other => other,
+    }
+}
+
 #[allow(dead_code)]
 pub fn promoted_piece_type(piece_type: PieceType) -> PieceType {
     match piece_type {
@@ -524,3 +1276,162 @@ pub fn promoted_piece_type(piece_type: PieceType) -> PieceType {
         other => other,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(sfen: &str) -> GameState {
+        crate::core::init();
+        GameState::from_sfen(sfen).expect("valid test SFEN")
+    }
+
+    /// Plays the USI moves of `cycle` over and over until the game ends,
+    /// and returns the number of moves played.
+    fn repeat_until_over(game: &mut GameState, cycle: &[&str]) -> usize {
+        for (played, text) in cycle.iter().cycle().take(40).enumerate() {
+            let mv = parse_usi_move(text).expect("valid test move");
+            assert!(game.play_move(mv), "move {} `{text}` failed", played + 1);
+            if game.result().is_some() {
+                return played + 1;
+            }
+        }
+        panic!("no result after 40 moves");
+    }
+
+    #[test]
+    fn fourfold_repetition_is_a_draw() {
+        let mut game = game(START_SFEN);
+        let moves = repeat_until_over(&mut game, &["2h3h", "8b7b", "3h2h", "7b8b"]);
+        assert_eq!(moves, 12);
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::Repetition,
+            })
+        );
+        assert!(game.is_game_over());
+    }
+
+    #[test]
+    fn perpetual_check_loses_for_the_checking_side() {
+        // Sente's rook checks from 1e and 2e while Gote's king shuttles.
+        let mut game = game("8k/9/9/9/7R1/9/9/9/4K4 b - 1");
+        repeat_until_over(&mut game, &["2e1e", "1a2a", "1e2e", "2a1a"]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: EndReason::PerpetualCheck,
+            })
+        );
+    }
+
+    /// Sente's king in camp with both rooks, both bishops and nine pawns:
+    /// 29 points from the board plus whatever is in `hand`.
+    fn sente_entered(hand: &str) -> GameState {
+        game(&format!("9/RB2K2BR/PPPPPPPPP/9/9/9/9/9/4k4 b {hand} 1"))
+    }
+
+    fn declared(mut game: GameState, rule: ImpasseRule) -> GameResult {
+        game.declare_impasse(rule);
+        game.result().expect("a declaration ends the game")
+    }
+
+    const SENTE_WINS: GameResult = GameResult {
+        winner: Some(Color::Black),
+        reason: EndReason::Impasse,
+    };
+    const DRAW: GameResult = GameResult {
+        winner: None,
+        reason: EndReason::Impasse,
+    };
+    const SENTE_LOSES: GameResult = GameResult {
+        winner: Some(Color::White),
+        reason: EndReason::IllegalMove,
+    };
+
+    #[test]
+    fn impasse_points() {
+        assert_eq!(sente_entered("-").impasse_points(Color::Black), Some(29));
+        assert_eq!(sente_entered("2G").impasse_points(Color::Black), Some(31));
+        assert_eq!(game(START_SFEN).impasse_points(Color::Black), None);
+        // Ten pieces besides the king are needed in the camp.
+        let few = game("9/4K4/PPPPPPPPP/9/9/9/9/9/4k4 b 2R2B 1");
+        assert_eq!(few.impasse_points(Color::Black), None);
+    }
+
+    #[test]
+    fn twenty_four_point_declarations() {
+        let rule = ImpasseRule::TwentyFourPoint;
+        assert_eq!(declared(sente_entered("2G"), rule), SENTE_WINS);
+        assert_eq!(declared(sente_entered("-"), rule), DRAW);
+        // 9 pawns, a rook and two bishops: 24 points draws.
+        let low = game("9/1B2K2BR/PPPPPPPPP/9/9/9/9/9/4k4 b - 1");
+        assert_eq!(declared(low, rule), DRAW);
+        let lower = game("9/1B2K3R/PPPPPPPPP/9/9/9/9/9/4k4 b - 1");
+        assert_eq!(declared(lower, rule), SENTE_LOSES);
+    }
+
+    #[test]
+    fn twenty_seven_point_declarations() {
+        let rule = ImpasseRule::TwentySevenPoint;
+        assert_eq!(declared(sente_entered("-"), rule), SENTE_WINS);
+        // 27 points falls one short for Sente but is enough for Gote.
+        let sente = game("9/1B2K2BR/PPPPPPPPP/9/9/9/9/9/4k4 b 3G 1");
+        assert_eq!(declared(sente, rule), SENTE_LOSES);
+        let gote = game("4K4/9/9/9/9/9/ppppppppp/1b2k2br/9 w 3g 1");
+        assert_eq!(gote.impasse_points(Color::White), Some(27));
+        assert_eq!(
+            declared(gote, rule),
+            GameResult {
+                winner: Some(Color::White),
+                reason: EndReason::Impasse,
+            }
+        );
+    }
+
+    #[test]
+    fn from_sfen_plays_moves_and_reports_the_illegal_one() {
+        let played = game(&format!("sfen {START_SFEN} moves 7g7f 3c3d"));
+        assert_eq!(played.current_ply(), 2);
+        assert_eq!(played.start_sfen(), START_SFEN);
+
+        let illegal =
+            |moves: &str| match GameState::from_sfen(&format!("{START_SFEN} moves {moves}")) {
+                Err(SfenLoadError::IllegalMove { ply, text }) => (ply, text),
+                Err(err) => panic!("{moves}: unexpected error {err}"),
+                Ok(_) => panic!("{moves}: expected an error"),
+            };
+        assert_eq!(illegal("7g7f 7g7f"), (2, "7g7f".to_string()));
+        assert_eq!(illegal("7g7f 3c3d x"), (3, "x".to_string()));
+        assert!(matches!(
+            GameState::from_sfen("startpos"),
+            Err(SfenLoadError::Invalid { .. })
+        ));
+        assert!(matches!(
+            GameState::from_sfen(""),
+            Err(SfenLoadError::Empty)
+        ));
+    }
+
+    #[test]
+    fn moves_off_the_line_become_variations() {
+        let mut game = game(START_SFEN);
+        for text in ["7g7f", "3c3d"] {
+            game.push_move(parse_usi_move(text).unwrap()).unwrap();
+        }
+        game.jump_to_ply(1);
+        game.push_move(parse_usi_move("8c8d").unwrap()).unwrap();
+        assert_eq!(game.variations(2).len(), 2);
+        assert_eq!(game.variation_index(2), Some(1));
+        assert!(!game.is_main_line(2));
+
+        game.promote_variation(2);
+        assert!(game.is_main_line(2));
+        assert!(game.delete_variation(2));
+        assert_eq!(game.history().len(), 2);
+        assert_eq!(game.variations(2).len(), 1);
+    }
+}
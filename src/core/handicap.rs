@@ -0,0 +1,99 @@
+//! Standard starting positions. In a handicap game Gote, the stronger player
+//! (上手), plays without some pieces and moves first.
+
+use super::game::{START_SFEN, position_key};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handicap {
+    /// English name, e.g. `Rook+Lance`.
+    pub name: &'static str,
+    /// `手合割` name written in KIF headers, e.g. `飛香落ち`.
+    pub kif_name: &'static str,
+    pub sfen: &'static str,
+}
+
+pub const EVEN: Handicap = Handicap {
+    name: "Even",
+    kif_name: "平手",
+    sfen: START_SFEN,
+};
+
+/// Every preset, even game first.
+pub const HANDICAPS: [Handicap; 12] = [
+    EVEN,
+    Handicap {
+        name: "Lance",
+        kif_name: "香落ち",
+        sfen: "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "Right Lance",
+        kif_name: "右香落ち",
+        sfen: "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "Bishop",
+        kif_name: "角落ち",
+        sfen: "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "Rook",
+        kif_name: "飛車落ち",
+        sfen: "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "Rook+Lance",
+        kif_name: "飛香落ち",
+        sfen: "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "2-Piece",
+        kif_name: "二枚落ち",
+        sfen: "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "4-Piece",
+        kif_name: "四枚落ち",
+        sfen: "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "6-Piece",
+        kif_name: "六枚落ち",
+        sfen: "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "8-Piece",
+        kif_name: "八枚落ち",
+        sfen: "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "10-Piece",
+        kif_name: "十枚落ち",
+        sfen: "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+    Handicap {
+        name: "Naked King",
+        kif_name: "裸玉",
+        sfen: "4k4/9/9/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    },
+];
+
+impl Handicap {
+    /// The preset that starts from `sfen`, whatever its move number.
+    pub fn find(sfen: &str) -> Option<Self> {
+        let key = position_key(sfen);
+        HANDICAPS
+            .into_iter()
+            .find(|handicap| position_key(handicap.sfen) == key)
+    }
+
+    pub fn from_kif_name(name: &str) -> Option<Self> {
+        HANDICAPS
+            .into_iter()
+            .find(|handicap| handicap.kif_name == name)
+    }
+
+    pub fn is_even(self) -> bool {
+        self == EVEN
+    }
+}
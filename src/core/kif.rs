@@ -0,0 +1,873 @@
+use std::fmt;
+use std::fmt::Write as _;
+
+use shogi::{Color, Move, Piece, Square};
+
+use super::game::{EndReason, GameInfo, GameResult, GameState, START_SFEN};
+use super::handicap::Handicap;
+use super::notation::{
+    japanese_move, kanji_number, kanji_piece_char, kanji_piece_name, parse_file_char,
+    parse_kanji_number, parse_kanji_piece, parse_rank_char, rank_char,
+};
+use super::sfen::{BoardSetup, HAND_PIECE_TYPES};
+use super::variation::{NodeId, ROOT, VariationTree};
+
+const MOVE_TEXT_WIDTH: usize = 14;
+
+#[derive(Debug)]
+pub struct KifError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl KifError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for KifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for KifError {}
+
+/// Reads a KIF record into a game positioned after its last move.
+pub fn parse(text: &str) -> Result<GameState, KifError> {
+    let mut reader = KifReader::default();
+    let text = text.trim_start_matches('\u{feff}');
+    for (idx, line) in text.lines().enumerate() {
+        reader.read_line(idx + 1, line)?;
+    }
+    reader.finish()
+}
+
+/// Serializes the whole record of `game` with its variations, regardless of
+/// the displayed line and ply.
+pub fn write(game: &GameState) -> String {
+    let mut out = String::new();
+    let setup = BoardSetup::from_sfen(game.start_sfen()).unwrap_or_default();
+    let handicap = Handicap::find(game.start_sfen());
+    let labels = if handicap.is_some_and(|handicap| !handicap.is_even()) {
+        ("下手", "上手")
+    } else {
+        ("先手", "後手")
+    };
+
+    out.push_str("# ---- rshogi 棋譜ファイル ----\n");
+    let info = game.info();
+    write_header(&mut out, "開始日時", &info.start_time);
+    write_header(&mut out, "終了日時", &info.end_time);
+    write_header(&mut out, "棋戦", &info.event);
+    write_header(&mut out, "場所", &info.site);
+    write_header(&mut out, "持ち時間", &info.time_control);
+    match handicap {
+        Some(handicap) => {
+            let _ = writeln!(out, "手合割：{}", handicap.kif_name);
+        }
+        None => write_board_diagram(&mut out, &setup, labels),
+    }
+    write_header(&mut out, labels.0, &info.black);
+    write_header(&mut out, labels.1, &info.white);
+    write_header(&mut out, "戦型", &info.opening);
+    for (key, value) in &info.extra {
+        let _ = writeln!(out, "{key}：{value}");
+    }
+
+    out.push_str("手数----指手---------消費時間--\n");
+    write_comment(&mut out, game.comment(0));
+    let tree = game.tree();
+    let mut section = MoveSection {
+        out: &mut out,
+        tree,
+        first_mover: setup.side_to_move(),
+        labels,
+    };
+    section.write_line(ROOT, &tree.main_line(ROOT), 1, None, [0; 2], true);
+    out
+}
+
+/// Writes the moves of a record: the main line, then its variations as
+/// `変化：` blocks.
+struct MoveSection<'a> {
+    out: &'a mut String,
+    tree: &'a VariationTree,
+    /// Side to move in the start position.
+    first_mover: Color,
+    labels: (&'a str, &'a str),
+}
+
+impl MoveSection<'_> {
+    /// Writes `line`, which follows `parent` from move `first_ply` on, and
+    /// then the variations branching off it; only the main line gets the
+    /// closing `まで` line.
+    ///
+    /// Kifu for Windows attaches a variation to the latest line with a `+`
+    /// mark at its ply, so deeper branches are written first and each
+    /// variation is followed by its next sibling.
+    ///
+    /// `totals` holds each player's thinking time so far, indexed by color.
+    fn write_line(
+        &mut self,
+        parent: NodeId,
+        line: &[NodeId],
+        first_ply: usize,
+        mut prev_to: Option<Square>,
+        mut totals: [u32; 2],
+        main: bool,
+    ) {
+        let mut before = Vec::with_capacity(line.len());
+        for (offset, &id) in line.iter().enumerate() {
+            before.push((prev_to, totals));
+            let entry = self.tree.entry(id);
+            let ply = first_ply + offset;
+            write_move_line(
+                self.out,
+                ply,
+                &japanese_move(entry, prev_to),
+                self.next_sibling(id).is_some(),
+                entry.elapsed,
+                &mut totals[self.mover(ply) as usize],
+            );
+            write_comment(self.out, &entry.comment);
+            prev_to = Some(entry.to());
+        }
+
+        let end = line.last().copied().unwrap_or(parent);
+        let moves = first_ply - 1 + line.len();
+        if let Some(result) = self.tree.result(end) {
+            let side_to_move = if moves.is_multiple_of(2) {
+                self.first_mover
+            } else {
+                self.first_mover.flip()
+            };
+            write_move_line(
+                self.out,
+                moves + 1,
+                special_move_text(result, side_to_move),
+                !self.tree.children(end).is_empty(),
+                None,
+                &mut totals[side_to_move as usize],
+            );
+            if main {
+                let _ = writeln!(self.out, "{}", result_line(result, moves, self.labels));
+            }
+            // Moves recorded after the game ended branch off the result.
+            if let Some(&first) = self.tree.children(end).first() {
+                self.write_variation(first, moves + 1, prev_to, totals);
+            }
+        }
+
+        for (offset, &id) in line.iter().enumerate().rev() {
+            if let Some(sibling) = self.next_sibling(id) {
+                let (prev_to, totals) = before[offset];
+                self.write_variation(sibling, first_ply + offset, prev_to, totals);
+            }
+        }
+    }
+
+    fn write_variation(
+        &mut self,
+        head: NodeId,
+        ply: usize,
+        prev_to: Option<Square>,
+        totals: [u32; 2],
+    ) {
+        let _ = writeln!(self.out, "\n変化：{ply}手");
+        let mut line = vec![head];
+        line.extend(self.tree.main_line(head));
+        let parent = self.tree.parent(head).unwrap_or(ROOT);
+        self.write_line(parent, &line, ply, prev_to, totals, false);
+    }
+
+    /// The side that plays move `ply`, counting from 1.
+    fn mover(&self, ply: usize) -> Color {
+        if ply.is_multiple_of(2) {
+            self.first_mover.flip()
+        } else {
+            self.first_mover
+        }
+    }
+
+    fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let parent = self.tree.parent(id)?;
+        let index = self.tree.sibling_index(id);
+        self.tree.children(parent).get(index + 1).copied()
+    }
+}
+
+#[derive(Default)]
+struct KifReader {
+    info: GameInfo,
+    handicap: Option<&'static str>,
+    board_rows: Vec<String>,
+    hands: [Option<String>; 2],
+    side_to_move: Option<Color>,
+    game: Option<GameState>,
+    last_to: Option<Square>,
+    finished: bool,
+    result_line: Option<String>,
+}
+
+impl KifReader {
+    fn read_line(&mut self, line_no: usize, raw: &str) -> Result<(), KifError> {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('&') {
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("変化：") {
+            return self.read_variation(line_no, rest);
+        }
+
+        if let Some(comment) = line.strip_prefix('*') {
+            let game = self.game(line_no)?;
+            let ply = game.current_ply();
+            let mut text = game.comment(ply).to_string();
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(comment);
+            game.set_comment(ply, text);
+            return Ok(());
+        }
+        if line.starts_with('|') {
+            self.board_rows.push(line.to_string());
+            return Ok(());
+        }
+        // Board diagram frame and file labels.
+        if line.starts_with('+') || line.starts_with('９') {
+            return Ok(());
+        }
+        if line.starts_with("手数") {
+            self.game(line_no)?;
+            return Ok(());
+        }
+        if line.starts_with("まで") {
+            self.result_line = Some(line.to_string());
+            return Ok(());
+        }
+        match line {
+            "先手番" | "下手番" => {
+                self.side_to_move = Some(Color::Black);
+                return Ok(());
+            }
+            "後手番" | "上手番" => {
+                self.side_to_move = Some(Color::White);
+                return Ok(());
+            }
+            _ => {}
+        }
+        if line.starts_with(|c: char| c.is_ascii_digit()) {
+            return self.read_move(line_no, line);
+        }
+        if let Some((key, value)) = line.split_once('：') {
+            self.read_header(line_no, key.trim(), value.trim())?;
+        }
+        Ok(())
+    }
+
+    fn read_header(&mut self, line_no: usize, key: &str, value: &str) -> Result<(), KifError> {
+        let owned = Some(value.to_string());
+        match key {
+            "先手" | "下手" => self.info.black = owned,
+            "後手" | "上手" => self.info.white = owned,
+            "棋戦" => self.info.event = owned,
+            "場所" => self.info.site = owned,
+            "開始日時" => self.info.start_time = owned,
+            "終了日時" => self.info.end_time = owned,
+            "持ち時間" => self.info.time_control = owned,
+            "戦型" => self.info.opening = owned,
+            "先手の持駒" | "下手の持駒" => self.hands[Color::Black as usize] = owned,
+            "後手の持駒" | "上手の持駒" => self.hands[Color::White as usize] = owned,
+            "手合割" => match Handicap::from_kif_name(value) {
+                Some(handicap) => self.handicap = Some(handicap.sfen),
+                None if value == "その他" => {}
+                None => {
+                    return Err(KifError::new(
+                        line_no,
+                        format!("unsupported handicap `{value}`"),
+                    ));
+                }
+            },
+            _ => self.info.extra.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    fn read_move(&mut self, line_no: usize, line: &str) -> Result<(), KifError> {
+        if self.finished {
+            return Ok(());
+        }
+        let digits_end = line
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(line.len());
+        let (body, elapsed) = split_time(&line[digits_end..]);
+        let text: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+        // A trailing `+` only marks that the move has variations.
+        let text = text.trim_end_matches('+');
+        let last_to = self.last_to;
+
+        let game = self.game(line_no)?;
+        if let Some(result) = special_move(text, game.side_to_move()) {
+            // A game end beside recorded moves has no place in the tree.
+            if game.current_ply() == game.history().len() {
+                game.set_result(Some(result));
+            }
+            self.finished = true;
+            return Ok(());
+        }
+
+        let mv =
+            parse_move_text(text, last_to).map_err(|message| KifError::new(line_no, message))?;
+        game.push_move(mv)
+            .map_err(|err| KifError::new(line_no, format!("illegal move `{text}`: {err:?}")))?;
+        let ply = game.current_ply();
+        game.set_elapsed(ply, elapsed);
+        self.last_to = Some(match mv {
+            Move::Normal { to, .. } | Move::Drop { to, .. } => to,
+        });
+        Ok(())
+    }
+
+    /// Goes back to the position before move `手` of `3手` so the moves that
+    /// follow are recorded as a variation.
+    fn read_variation(&mut self, line_no: usize, rest: &str) -> Result<(), KifError> {
+        let ply = rest
+            .trim()
+            .trim_end_matches('手')
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&ply| ply > 0)
+            .ok_or_else(|| KifError::new(line_no, format!("bad variation header `{rest}`")))?;
+        let game = self.game(line_no)?;
+        if ply > game.history().len() + 1 {
+            return Err(KifError::new(
+                line_no,
+                format!("variation starts at move {ply} after a shorter line"),
+            ));
+        }
+        game.jump_to_ply(ply - 1);
+        self.last_to = (ply - 1).checked_sub(1).map(|idx| game.history()[idx].to());
+        self.finished = false;
+        Ok(())
+    }
+
+    fn game(&mut self, line_no: usize) -> Result<&mut GameState, KifError> {
+        if self.game.is_none() {
+            let sfen = self.start_sfen(line_no)?;
+            let game = GameState::from_sfen(&sfen)
+                .map_err(|err| KifError::new(line_no, err.to_string()))?;
+            self.game = Some(game);
+        }
+        Ok(self.game.as_mut().expect("game was just created"))
+    }
+
+    fn start_sfen(&self, line_no: usize) -> Result<String, KifError> {
+        if self.board_rows.is_empty() {
+            return Ok(self.handicap.unwrap_or(START_SFEN).to_string());
+        }
+        if self.board_rows.len() != 9 {
+            return Err(KifError::new(
+                line_no,
+                format!("board diagram has {} rows", self.board_rows.len()),
+            ));
+        }
+
+        let mut setup = BoardSetup::empty();
+        for (rank, row) in self.board_rows.iter().enumerate() {
+            let cells: Vec<char> = row.trim_start_matches('|').chars().collect();
+            for col in 0..9 {
+                let (Some(&marker), Some(&name)) = (cells.get(col * 2), cells.get(col * 2 + 1))
+                else {
+                    return Err(KifError::new(line_no, format!("short board row `{row}`")));
+                };
+                if name == '・' {
+                    continue;
+                }
+                let mut buf = [0; 4];
+                let Some((piece_type, _)) = parse_kanji_piece(name.encode_utf8(&mut buf)) else {
+                    return Err(KifError::new(line_no, format!("unknown piece `{name}`")));
+                };
+                let color = if marker == 'v' {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                let sq = Square::new(8 - col as u8, rank as u8).expect("valid board coordinate");
+                setup.set_piece(sq, Some(Piece { piece_type, color }));
+            }
+        }
+
+        for color in [Color::Black, Color::White] {
+            if let Some(hand) = &self.hands[color as usize] {
+                parse_hand(&mut setup, color, hand)
+                    .map_err(|message| KifError::new(line_no, message))?;
+            }
+        }
+        setup.set_side_to_move(self.side_to_move.unwrap_or(Color::Black));
+        Ok(setup.to_sfen())
+    }
+
+    fn finish(mut self) -> Result<GameState, KifError> {
+        self.game(0)?;
+        let mut game = self.game.take().expect("game was just created");
+        game.show_main_line();
+        let _ = game.take_pending_sound();
+        game.set_status("");
+        if game.result().is_none()
+            && let Some(line) = &self.result_line
+        {
+            game.set_result(parse_result_line(line, game.side_to_move()));
+        }
+        *game.info_mut() = self.info;
+        Ok(game)
+    }
+}
+
+/// Splits `７六歩(77)   ( 0:05/00:00:05)` into the move text and seconds used.
+fn split_time(rest: &str) -> (&str, Option<u32>) {
+    let Some(open) = rest.rfind('(') else {
+        return (rest, None);
+    };
+    let time = &rest[open + 1..];
+    if !time.contains(':') {
+        return (rest, None);
+    }
+    let per_move = time.split('/').next().unwrap_or("");
+    let mut parts = per_move.split(':').map(|part| part.trim().parse::<u32>());
+    let elapsed = match (parts.next(), parts.next()) {
+        (Some(Ok(minutes)), Some(Ok(seconds))) => Some(minutes * 60 + seconds),
+        _ => None,
+    };
+    (&rest[..open], elapsed)
+}
+
+fn parse_move_text(text: &str, last_to: Option<Square>) -> Result<Move, String> {
+    let text = text.trim_start_matches(['▲', '△', '☗', '☖']);
+    let (to, rest) = match text.strip_prefix('同') {
+        Some(rest) => (
+            last_to.ok_or_else(|| format!("`{text}` refers to a missing previous move"))?,
+            rest,
+        ),
+        None => {
+            let mut chars = text.chars();
+            let file = chars.next().and_then(parse_file_char);
+            let rank = chars.next().and_then(parse_rank_char);
+            let (Some(file), Some(rank)) = (file, rank) else {
+                return Err(format!("cannot read destination of `{text}`"));
+            };
+            (
+                Square::new(file, rank).expect("valid board coordinate"),
+                chars.as_str(),
+            )
+        }
+    };
+
+    let (piece_type, len) =
+        parse_kanji_piece(rest).ok_or_else(|| format!("cannot read piece of `{text}`"))?;
+    let mut rest = &rest[len..];
+    let mut promote = false;
+    if let Some(after) = rest
+        .strip_prefix("不成")
+        .or_else(|| rest.strip_prefix('生'))
+    {
+        rest = after;
+    } else if let Some(after) = rest.strip_prefix('成') {
+        promote = true;
+        rest = after;
+    }
+    let rest = rest.strip_prefix('打').unwrap_or(rest);
+
+    match parse_origin(rest) {
+        Some(from) => Ok(Move::Normal { from, to, promote }),
+        None => Ok(Move::Drop { to, piece_type }),
+    }
+}
+
+fn parse_origin(text: &str) -> Option<Square> {
+    let inner = text.strip_prefix('(')?.strip_suffix(')')?;
+    let mut digits = inner.chars().filter_map(|c| c.to_digit(10));
+    let file = digits.next()?;
+    let rank = digits.next()?;
+    Square::new(file.checked_sub(1)? as u8, rank.checked_sub(1)? as u8)
+}
+
+/// Reads a hand such as `飛 角 金四`. Mate problems write `残り全部` for a
+/// defender holding every piece not placed elsewhere.
+fn parse_hand(setup: &mut BoardSetup, color: Color, text: &str) -> Result<(), String> {
+    for token in text.split_whitespace() {
+        match token {
+            "なし" => continue,
+            "残り全部" => {
+                setup.give_remaining_pieces(color);
+                continue;
+            }
+            _ => {}
+        }
+        let (piece_type, len) =
+            parse_kanji_piece(token).ok_or_else(|| format!("unknown hand piece `{token}`"))?;
+        let count = if token.len() == len {
+            1
+        } else {
+            parse_kanji_number(&token[len..])
+                .ok_or_else(|| format!("unknown hand count `{token}`"))?
+        };
+        setup.set_hand(color, piece_type, count as u8);
+    }
+    Ok(())
+}
+
+fn special_move(text: &str, side_to_move: Color) -> Option<GameResult> {
+    let opponent = Some(side_to_move.flip());
+    let (winner, reason) = match text {
+        "投了" => (opponent, EndReason::Resignation),
+        "詰み" => (opponent, EndReason::Checkmate),
+        "中断" => (None, EndReason::Interrupted),
+        "千日手" => (None, EndReason::Repetition),
+        "引き分け" => (None, EndReason::DrawAgreement),
+        "持将棋" => (None, EndReason::Impasse),
+        "入玉勝ち" | "宣言勝ち" => (Some(side_to_move), EndReason::Impasse),
+        "切れ負け" | "時間切れ" => (opponent, EndReason::Timeout),
+        "反則勝ち" => (Some(side_to_move), EndReason::IllegalMove),
+        "反則負け" => (opponent, EndReason::IllegalMove),
+        _ => return None,
+    };
+    Some(GameResult { winner, reason })
+}
+
+fn parse_result_line(line: &str, side_to_move: Color) -> Option<GameResult> {
+    let winner = if line.contains("先手の") || line.contains("下手の") {
+        Some(Color::Black)
+    } else if line.contains("後手の") || line.contains("上手の") {
+        Some(Color::White)
+    } else {
+        None
+    };
+    let reason = if line.contains("引き分け") {
+        EndReason::DrawAgreement
+    } else if line.contains("千日手") {
+        EndReason::Repetition
+    } else if line.contains("持将棋") || line.contains("入玉") {
+        EndReason::Impasse
+    } else if line.contains("中断") {
+        EndReason::Interrupted
+    } else if line.contains("時間切れ") || line.contains("切れ負け") {
+        EndReason::Timeout
+    } else if line.contains("反則") {
+        EndReason::IllegalMove
+    } else if line.contains("詰") {
+        EndReason::Checkmate
+    } else if line.contains("勝ち") {
+        EndReason::Resignation
+    } else {
+        return None;
+    };
+    let winner = match reason {
+        EndReason::Checkmate => winner.or(Some(side_to_move.flip())),
+        _ => winner,
+    };
+    Some(GameResult { winner, reason })
+}
+
+fn special_move_text(result: GameResult, side_to_move: Color) -> &'static str {
+    match result.reason {
+        EndReason::Resignation => "投了",
+        EndReason::Checkmate | EndReason::NoLegalMoves => "詰み",
+        EndReason::Repetition => "千日手",
+        EndReason::DrawAgreement => "引き分け",
+        EndReason::Interrupted => "中断",
+        EndReason::Timeout => "切れ負け",
+        EndReason::Impasse if result.winner.is_some() => "入玉勝ち",
+        EndReason::Impasse => "持将棋",
+        EndReason::IllegalMove | EndReason::PerpetualCheck => {
+            if result.winner == Some(side_to_move) {
+                "反則勝ち"
+            } else {
+                "反則負け"
+            }
+        }
+    }
+}
+
+fn result_line(result: GameResult, moves: usize, labels: (&str, &str)) -> String {
+    let side = |color: Color| {
+        if color == Color::Black {
+            labels.0
+        } else {
+            labels.1
+        }
+    };
+    let outcome = match (result.reason, result.winner) {
+        (EndReason::Repetition, _) => "千日手".to_string(),
+        (EndReason::DrawAgreement, _) => "引き分け".to_string(),
+        (EndReason::Impasse, None) => "持将棋".to_string(),
+        (_, None) => "中断".to_string(),
+        (EndReason::Timeout, Some(winner)) => format!("時間切れにより{}の勝ち", side(winner)),
+        (EndReason::IllegalMove | EndReason::PerpetualCheck, Some(winner)) => {
+            format!("{}の反則勝ち", side(winner))
+        }
+        (EndReason::Impasse, Some(winner)) => format!("{}の入玉勝ち", side(winner)),
+        (_, Some(winner)) => format!("{}の勝ち", side(winner)),
+    };
+    format!("まで{moves}手で{outcome}")
+}
+
+fn write_header(out: &mut String, key: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        let _ = writeln!(out, "{key}：{value}");
+    }
+}
+
+fn write_comment(out: &mut String, comment: &str) {
+    for line in comment.lines() {
+        let _ = writeln!(out, "*{line}");
+    }
+}
+
+/// `branches` adds the `+` that marks a move with later variations, and
+/// `total` is the mover's own thinking time so far.
+fn write_move_line(
+    out: &mut String,
+    number: usize,
+    text: &str,
+    branches: bool,
+    elapsed: Option<u32>,
+    total: &mut u32,
+) {
+    let _ = write!(out, "{number:>4} {text}");
+    if let Some(seconds) = elapsed {
+        *total += seconds;
+        let pad = MOVE_TEXT_WIDTH.saturating_sub(display_width(text));
+        let _ = write!(
+            out,
+            "{}({:>2}:{:02}/{:02}:{:02}:{:02})",
+            " ".repeat(pad),
+            seconds / 60,
+            seconds % 60,
+            *total / 3600,
+            *total / 60 % 60,
+            *total % 60
+        );
+    }
+    if branches {
+        out.push('+');
+    }
+    out.push('\n');
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+/// The position as a KIF board diagram (BOD), with both hands and, when
+/// Gote is to move, the `後手番` line.
+pub fn board_diagram(setup: &BoardSetup) -> String {
+    let mut out = String::new();
+    write_board_diagram(&mut out, setup, ("先手", "後手"));
+    out
+}
+
+fn write_board_diagram(out: &mut String, setup: &BoardSetup, labels: (&str, &str)) {
+    let _ = writeln!(
+        out,
+        "{}の持駒：{}",
+        labels.1,
+        hand_text(setup, Color::White)
+    );
+    out.push_str("  ９ ８ ７ ６ ５ ４ ３ ２ １\n");
+    out.push_str("+---------------------------+\n");
+    for rank in 0..9 {
+        out.push('|');
+        for file in (0..9).rev() {
+            let sq = Square::new(file, rank).expect("valid board coordinate");
+            match setup.piece_at(sq) {
+                Some(piece) => {
+                    out.push(if piece.color == Color::White {
+                        'v'
+                    } else {
+                        ' '
+                    });
+                    out.push(kanji_piece_char(piece.piece_type));
+                }
+                None => out.push_str(" ・"),
+            }
+        }
+        out.push('|');
+        out.push(rank_char(rank));
+        out.push('\n');
+    }
+    out.push_str("+---------------------------+\n");
+    let _ = writeln!(
+        out,
+        "{}の持駒：{}",
+        labels.0,
+        hand_text(setup, Color::Black)
+    );
+    if setup.side_to_move() == Color::White {
+        let _ = writeln!(out, "{}番", labels.1);
+    }
+}
+
+fn hand_text(setup: &BoardSetup, color: Color) -> String {
+    let mut text = String::new();
+    for piece_type in HAND_PIECE_TYPES {
+        let count = setup.hand(color, piece_type);
+        if count == 0 {
+            continue;
+        }
+        text.push_str(kanji_piece_name(piece_type));
+        if count > 1 {
+            text.push_str(&kanji_number(u32::from(count)));
+        }
+        text.push('　');
+    }
+    if text.is_empty() {
+        text.push_str("なし");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usi::move_to_usi;
+
+    /// A lance handicap game with timed moves, comments and a variation on
+    /// Shitate's first move.
+    const HANDICAP_GAME: &str = "\
+# ---- テスト棋譜 ----
+手合割：香落ち
+下手：Shitate
+上手：Uwate
+手数----指手---------消費時間--
+*開始局面
+   1 ３四歩(33)   ( 0:05/00:00:05)
+   2 ７六歩(77)   ( 0:10/00:00:10)+
+*角道を開ける
+   3 ４四歩(43)   ( 1:03/00:01:08)
+   4 投了
+まで3手で上手の勝ち
+
+変化：2手
+   2 ２六歩(27)   ( 0:20/00:00:20)
+*居飛車
+   3 ８四歩(83)   ( 0:01/00:00:06)
+";
+
+    fn parse_text(text: &str) -> GameState {
+        crate::core::init();
+        parse(text).expect("valid test record")
+    }
+
+    fn usi(game: &GameState) -> Vec<String> {
+        game.history()
+            .iter()
+            .map(|entry| move_to_usi(entry.mv))
+            .collect()
+    }
+
+    #[test]
+    fn reads_handicap_header_players_and_moves() {
+        let game = parse_text(HANDICAP_GAME);
+        assert_eq!(
+            game.start_sfen(),
+            Handicap::from_kif_name("香落ち").unwrap().sfen
+        );
+        assert_eq!(game.info().black.as_deref(), Some("Shitate"));
+        assert_eq!(game.info().white.as_deref(), Some("Uwate"));
+        assert_eq!(usi(&game), ["3c3d", "7g7f", "4c4d"]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: EndReason::Resignation,
+            })
+        );
+    }
+
+    #[test]
+    fn reads_comments_and_time_columns() {
+        let game = parse_text(HANDICAP_GAME);
+        assert_eq!(game.comment(0), "開始局面");
+        assert_eq!(game.comment(2), "角道を開ける");
+        let elapsed: Vec<_> = game.history().iter().map(|entry| entry.elapsed).collect();
+        assert_eq!(elapsed, [Some(5), Some(10), Some(63)]);
+    }
+
+    #[test]
+    fn reads_variations() {
+        let mut game = parse_text(HANDICAP_GAME);
+        let second: Vec<_> = game
+            .variations(2)
+            .iter()
+            .map(|entry| move_to_usi(entry.mv))
+            .collect();
+        assert_eq!(second, ["7g7f", "2g2f"]);
+
+        assert!(game.select_variation(2, 1));
+        game.jump_to_ply(3);
+        assert_eq!(usi(&game), ["3c3d", "2g2f", "8c8d"]);
+        assert_eq!(game.comment(2), "居飛車");
+        assert_eq!(game.history()[2].elapsed, Some(1));
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn same_square_and_drops() {
+        let game = parse_text(
+            "\
+手数----指手---------消費時間--
+   1 ７六歩(77)
+   2 ３四歩(33)
+   3 ２二角成(88)
+   4 同　銀(31)
+   5 ４五角打
+",
+        );
+        assert_eq!(usi(&game), ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+    }
+
+    #[test]
+    fn rejects_illegal_moves_with_their_line() {
+        crate::core::init();
+        let Err(err) = parse("手数----指手---------消費時間--\n   1 ７五歩(77)\n")
+        else {
+            panic!("a pawn cannot move two squares");
+        };
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn board_diagram_with_hands_and_gote_to_move() {
+        let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 w Gp 1";
+        let setup = BoardSetup::from_sfen(sfen).expect("valid test SFEN");
+        let diagram = board_diagram(&setup);
+        assert!(diagram.starts_with("後手の持駒：歩　\n"), "{diagram}");
+        assert!(diagram.contains("先手の持駒：金　\n"), "{diagram}");
+        assert!(diagram.ends_with("後手番\n"), "{diagram}");
+
+        let game = parse_text(&format!("{diagram}手数----指手---------消費時間--\n"));
+        assert_eq!(game.start_sfen(), sfen);
+    }
+
+    #[test]
+    fn variations_survive_a_round_trip() {
+        let game = parse_text(HANDICAP_GAME);
+        let written = write(&game);
+        assert!(written.contains("手合割：香落ち\n"), "{written}");
+        assert!(written.contains("\n変化：2手\n"), "{written}");
+
+        let reread = parse_text(&written);
+        assert_eq!(write(&reread), written);
+        assert_eq!(usi(&reread), usi(&game));
+        assert_eq!(reread.variations(2).len(), 2);
+        assert_eq!(reread.info(), game.info());
+        assert_eq!(reread.result(), game.result());
+    }
+}
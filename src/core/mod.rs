@@ -1 +1,26 @@
+pub mod clock;
+pub mod csa;
 pub mod game;
+pub mod handicap;
+pub mod kif;
+pub mod move_input;
+pub mod movegen;
+pub mod notation;
+pub mod perft;
+pub mod problems;
+pub mod record;
+pub mod sfen;
+pub mod tsume;
+pub mod usi;
+pub mod variation;
+
+use std::sync::Once;
+
+use shogi::bitboard::Factory as BBFactory;
+
+/// Builds the `shogi` crate's attack tables, which every `Position` relies
+/// on. Only the first call does any work.
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(BBFactory::init);
+}
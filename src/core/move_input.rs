@@ -0,0 +1,334 @@
+//! Moves typed as text, matched against the legal moves of the displayed
+//! position.
+//!
+//! Accepts USI (`7g7f`, `P*5e`), Hodges (`P-7f`, `Bx3c+`, `G6i-5h`), CSA
+//! (`7776FU`, `+7776FU`) and Japanese move text written in kanji or with CSA
+//! piece codes (`７六歩`, `76fu`, `同歩`, `58金右`, `55ka*`). `+` or `成` takes
+//! a promotion and `=` or `不成` declines it; when the text says neither, a
+//! move that may promote is left for the player to decide.
+
+use shogi::{Move, Piece, Position, Square};
+
+use super::csa::{piece_code, square_code};
+use super::game::{GameState, MoveEntry, promoted_piece_type};
+use super::notation::{
+    Notation, can_promote, parse_kanji_piece, parse_rank_char, relative_words, western_piece,
+    western_square,
+};
+use super::usi::move_to_usi;
+
+/// Legal moves that fit some typed text.
+#[derive(Clone, Debug, Default)]
+pub struct TypedMove {
+    /// Moves with a spelling that starts with the text.
+    pub candidates: Vec<Move>,
+    /// Moves spelled exactly by the text.
+    pub exact: Vec<Move>,
+}
+
+/// What typed text settles on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveMatch {
+    /// No legal move is spelled this way, even in part.
+    None,
+    /// A single move, promotion included.
+    Move(Move),
+    /// A piece move the text names without saying whether it promotes; holds
+    /// the non-promoting move.
+    Promotion(Move),
+    /// Several moves fit; more text is needed.
+    Ambiguous,
+}
+
+impl TypedMove {
+    /// Exact spellings win over completions, so `7g7f` is not ambiguous with
+    /// `7g7f+`.
+    pub fn resolve(&self) -> MoveMatch {
+        let moves = if self.exact.is_empty() {
+            &self.candidates
+        } else {
+            &self.exact
+        };
+        match moves.as_slice() {
+            [] => MoveMatch::None,
+            &[mv] => MoveMatch::Move(mv),
+            &[a, b] => match promotion_pair(a, b) {
+                Some(non_promote) => MoveMatch::Promotion(non_promote),
+                None => MoveMatch::Ambiguous,
+            },
+            _ => MoveMatch::Ambiguous,
+        }
+    }
+}
+
+/// Legal moves of the displayed position that `text` spells or begins to spell.
+pub fn match_moves(game: &mut GameState, text: &str) -> TypedMove {
+    let typed = normalize(text);
+    let mut out = TypedMove::default();
+    if typed.is_empty() {
+        return out;
+    }
+    let prev_to = game.last_action_to();
+    for mv in game.legal_moves() {
+        let entry = move_entry(game, mv);
+        let spellings = spellings(game.position(), &entry, prev_to);
+        if spellings.contains(&typed) {
+            out.exact.push(mv);
+        }
+        if spellings
+            .iter()
+            .any(|spelling| spelling.starts_with(&typed))
+        {
+            out.candidates.push(mv);
+        }
+    }
+    out
+}
+
+/// Text of `mv` played from the displayed position, for listing completions.
+pub fn describe(game: &GameState, mv: Move, notation: Notation) -> String {
+    let entry = move_entry(game, mv);
+    notation.format_move(game.position(), &entry, game.last_action_to())
+}
+
+fn move_entry(game: &GameState, mv: Move) -> MoveEntry {
+    let (piece, captured) = match mv {
+        Move::Normal { from, to, .. } => (game.piece_at(from), game.piece_at(to)),
+        Move::Drop { piece_type, .. } => (
+            Some(Piece {
+                piece_type,
+                color: game.side_to_move(),
+            }),
+            None,
+        ),
+    };
+    MoveEntry {
+        mv,
+        piece: piece.expect("a legal move starts from an occupied square"),
+        captured,
+        comment: String::new(),
+        elapsed: None,
+    }
+}
+
+/// The non-promoting move when `a` and `b` are one move with and without
+/// promotion.
+fn promotion_pair(a: Move, b: Move) -> Option<Move> {
+    match (a, b) {
+        (
+            Move::Normal {
+                from: from_a,
+                to: to_a,
+                promote: promote_a,
+            },
+            Move::Normal {
+                from: from_b,
+                to: to_b,
+                promote: promote_b,
+            },
+        ) if from_a == from_b && to_a == to_b && promote_a != promote_b => {
+            Some(if promote_a { b } else { a })
+        }
+        _ => None,
+    }
+}
+
+/// Every accepted spelling of `entry`, already normalized.
+fn spellings(pos: &Position, entry: &MoveEntry, prev_to: Option<Square>) -> Vec<String> {
+    let to = entry.to();
+    let piece_type = entry.piece.piece_type;
+    let mut out = vec![move_to_usi(entry.mv).to_lowercase()];
+
+    // Leaving the promotion out keeps both moves in play.
+    let promotion: &[&str] = match entry.mv {
+        Move::Drop { .. } => &["", "*"],
+        Move::Normal { promote: true, .. } => &["", "+"],
+        Move::Normal { from, .. } if can_promote(entry, from, to) => &["", "="],
+        Move::Normal { .. } => &[""],
+    };
+
+    // Japanese: destination or 同, piece, the words that tell it from its
+    // rivals, promotion and the KIF origin, e.g. `76fu77`.
+    let code = piece_code(piece_type).to_lowercase();
+    let mut destinations = vec![square_code(to)];
+    if prev_to == Some(to) {
+        destinations.push("do".to_string());
+    }
+    let mut words = vec![String::new()];
+    let relative = normalize(&relative_words(pos, entry));
+    if !relative.is_empty() {
+        words.push(relative);
+    }
+    let mut origins = vec![String::new()];
+    if let Some(from) = entry.from() {
+        origins.push(square_code(from));
+    }
+    for destination in &destinations {
+        for word in &words {
+            for suffix in promotion {
+                for origin in &origins {
+                    out.push(format!("{destination}{code}{word}{suffix}{origin}"));
+                }
+            }
+        }
+    }
+
+    // CSA: origin, or 00 for a drop, destination and the piece after the move.
+    let after = match entry.mv {
+        Move::Normal { promote: true, .. } => promoted_piece_type(piece_type),
+        _ => piece_type,
+    };
+    out.push(format!(
+        "{}{}{}",
+        entry.from().map_or_else(|| "00".to_string(), square_code),
+        square_code(to),
+        piece_code(after).to_lowercase()
+    ));
+
+    // Hodges, with the origin, capture mark and promotion optional.
+    let piece = western_piece(piece_type).to_lowercase();
+    let destination = western_square(to);
+    match entry.mv {
+        Move::Drop { .. } => out.push(format!("{piece}*{destination}")),
+        Move::Normal { from, .. } => {
+            let actions: &[&str] = if entry.captured.is_some() {
+                &["", "x"]
+            } else {
+                &[""]
+            };
+            for origin in [String::new(), western_square(from)] {
+                for action in actions {
+                    for suffix in promotion {
+                        out.push(format!("{piece}{origin}{action}{destination}{suffix}"));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Lowercase form of typed text: kanji pieces become CSA codes,
+/// numerals become digits, `成`/`不成`/`打`/`同` become `+`/`=`/`*`/`do`,
+/// and spaces, side marks, hyphens and brackets are dropped. The `+` side
+/// mark of a CSA move such as `+7776FU` goes too; elsewhere `+` promotes.
+fn normalize(text: &str) -> String {
+    let text = text.trim_start();
+    let text = match text.strip_prefix('+') {
+        Some(rest) if is_csa_move_prefix(rest) => rest,
+        _ => text,
+    };
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some((piece_type, len)) = parse_kanji_piece(rest) {
+            out.push_str(&piece_code(piece_type).to_lowercase());
+            rest = &rest[len..];
+            continue;
+        }
+        if let Some(tail) = rest.strip_prefix("不成") {
+            out.push('=');
+            rest = tail;
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '成' => out.push('+'),
+            '打' => out.push('*'),
+            '同' => out.push_str("do"),
+            '▲' | '△' | '☗' | '☖' | '-' | '(' | ')' | '（' | '）' => {}
+            c if c.is_whitespace() => {}
+            c => match parse_rank_char(c) {
+                Some(index) => out.push(char::from(b'1' + index)),
+                None => out.extend(c.to_lowercase()),
+            },
+        }
+    }
+    out
+}
+
+/// Whether `text` is, or begins, a CSA move without its side mark: four
+/// digits and a two-letter piece code.
+fn is_csa_move_prefix(text: &str) -> bool {
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    let code = &text[digits..];
+    digits > 0
+        && (digits == 4 || code.is_empty())
+        && code.len() <= 2
+        && code.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The starting position with the 5g pawn in Sente's hand.
+    const PAWN_IN_HAND: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPP1PPPP/1B5R1/LNSGKGSNL b P 1";
+
+    fn matched(text: &str) -> MoveMatch {
+        crate::core::init();
+        let mut game = GameState::from_sfen(PAWN_IN_HAND).expect("valid test SFEN");
+        match_moves(&mut game, text).resolve()
+    }
+
+    fn usi(text: &str) -> Option<String> {
+        match matched(text) {
+            MoveMatch::Move(mv) => Some(move_to_usi(mv)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn every_notation_spells_the_pawn_push() {
+        for text in [
+            "7g7f",
+            "76fu",
+            "７六歩",
+            "7776FU",
+            "+7776FU",
+            "P-7f",
+            "P7g-7f",
+        ] {
+            assert_eq!(usi(text).as_deref(), Some("7g7f"), "{text}");
+        }
+    }
+
+    #[test]
+    fn drops() {
+        for text in ["P*5e", "55fu", "0055FU", "+0055FU", "５五歩打"] {
+            assert_eq!(usi(text).as_deref(), Some("P*5e"), "{text}");
+        }
+    }
+
+    #[test]
+    fn partial_csa_move_with_side_mark_is_a_candidate() {
+        crate::core::init();
+        let mut game = GameState::from_sfen(PAWN_IN_HAND).expect("valid test SFEN");
+        let typed = match_moves(&mut game, "+77");
+        assert!(!typed.candidates.is_empty());
+        assert!(typed.exact.is_empty());
+    }
+
+    #[test]
+    fn plus_after_a_move_still_promotes() {
+        crate::core::init();
+        // The rook on 2h has a clear file up to 2c.
+        let mut game =
+            GameState::from_sfen("lnsgkgsnl/1r5b1/ppppppp1p/9/9/9/PPPPPPP1P/1B5R1/LNSGKGSNL b P 1")
+                .expect("valid test SFEN");
+        let promoting = match_moves(&mut game, "2h2c+").resolve();
+        assert!(matches!(
+            promoting,
+            MoveMatch::Move(Move::Normal { promote: true, .. })
+        ));
+        assert!(matches!(
+            match_moves(&mut game, "R-2c").resolve(),
+            MoveMatch::Promotion(_)
+        ));
+    }
+
+    #[test]
+    fn unknown_text_matches_nothing() {
+        assert_eq!(matched("9a9b"), MoveMatch::None);
+    }
+}
@@ -0,0 +1,787 @@
+//! Legal move generation.
+//!
+//! [`Board`] is a plain copy of a position that lists its legal moves
+//! directly: checks and pins are read off from the king, and drops follow the
+//! nifu, uchifuzume and dead-piece rules, so no candidate has to be tried on a
+//! [`Position`]. Repetition is left to the caller, as it depends on the game's
+//! history rather than the position.
+
+use std::ops::Range;
+
+use shogi::{Color, Move, Piece, PieceType, Position, Square};
+
+use super::game::{promoted_piece_type, unpromoted_piece_type};
+use super::sfen::{BoardSetup, HAND_PIECE_TYPES, squares};
+
+const SQUARES: usize = 81;
+
+/// Board piece types in code order: a square holds 0 when empty, else one
+/// plus the type's index, plus `PIECE_TYPES.len()` for Gote.
+const PIECE_TYPES: [PieceType; 14] = [
+    PieceType::King,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+    PieceType::ProRook,
+    PieceType::ProBishop,
+    PieceType::ProSilver,
+    PieceType::ProKnight,
+    PieceType::ProLance,
+    PieceType::ProPawn,
+];
+
+// Offsets as (file, ranks forward) from the mover's point of view.
+const KING_STEPS: &[(i8, i8)] = &[
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const GOLD_STEPS: &[(i8, i8)] = &[(-1, 1), (0, 1), (1, 1), (-1, 0), (1, 0), (0, -1)];
+const SILVER_STEPS: &[(i8, i8)] = &[(-1, 1), (0, 1), (1, 1), (-1, -1), (1, -1)];
+const KNIGHT_STEPS: &[(i8, i8)] = &[(-1, 2), (1, 2)];
+const FORWARD: &[(i8, i8)] = &[(0, 1)];
+const ORTHOGONALS: &[(i8, i8)] = &[(0, 1), (-1, 0), (1, 0), (0, -1)];
+const DIAGONALS: &[(i8, i8)] = &[(-1, 1), (1, 1), (-1, -1), (1, -1)];
+
+/// A set of squares as a bit mask.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SquareSet(u128);
+
+#[allow(dead_code)]
+impl SquareSet {
+    pub fn contains(self, sq: Square) -> bool {
+        self.has(index(sq))
+    }
+
+    pub fn insert(&mut self, sq: Square) {
+        self.add(index(sq));
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    fn has(self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+
+    fn add(&mut self, index: usize) {
+        self.0 |= 1 << index;
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn and(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Indices in the set, in ascending order.
+    fn indices(self) -> impl Iterator<Item = usize> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let index = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            Some(index)
+        })
+    }
+}
+
+/// The squares of file `file`, counted from 0.
+const fn file_squares(file: usize) -> SquareSet {
+    SquareSet(0x1ff << (file * 9))
+}
+
+/// The squares of rank `rank`, counted from 0.
+const fn rank_squares(rank: usize) -> SquareSet {
+    let mut bits = 0;
+    let mut file = 0;
+    while file < 9 {
+        bits |= 1 << (file * 9 + rank);
+        file += 1;
+    }
+    SquareSet(bits)
+}
+
+/// Legal moves of one position, grouped by the square or hand piece they
+/// start from.
+#[derive(Clone, Debug, Default)]
+pub struct LegalMoves {
+    moves: Vec<Move>,
+    /// Slice of `moves` and destinations for each origin square.
+    board: Vec<(Range<usize>, SquareSet)>,
+    /// The same for each of [`HAND_PIECE_TYPES`].
+    drops: Vec<(Range<usize>, SquareSet)>,
+}
+
+impl LegalMoves {
+    pub fn all(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Moves of the piece on `from`, promotions included.
+    pub fn from_square(&self, from: Square) -> &[Move] {
+        self.board
+            .get(index(from))
+            .map_or(&[], |(range, _)| &self.moves[range.clone()])
+    }
+
+    pub fn targets_from(&self, from: Square) -> SquareSet {
+        self.board
+            .get(index(from))
+            .map_or_else(SquareSet::default, |&(_, targets)| targets)
+    }
+
+    pub fn drops(&self, piece_type: PieceType) -> &[Move] {
+        hand_index(piece_type)
+            .and_then(|idx| self.drops.get(idx))
+            .map_or(&[], |(range, _)| &self.moves[range.clone()])
+    }
+
+    pub fn drop_targets(&self, piece_type: PieceType) -> SquareSet {
+        hand_index(piece_type)
+            .and_then(|idx| self.drops.get(idx))
+            .map_or_else(SquareSet::default, |&(_, targets)| targets)
+    }
+
+    pub fn contains(&self, mv: Move) -> bool {
+        match mv {
+            Move::Normal { from, .. } => self.from_square(from).contains(&mv),
+            Move::Drop { piece_type, .. } => self.drops(piece_type).contains(&mv),
+        }
+    }
+}
+
+/// Pieces, hands and side to move, without any history. Equal boards are the
+/// same position, so a board can key a transposition table.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Board {
+    squares: [u8; SQUARES],
+    /// Counts by [`HAND_PIECE_TYPES`], Sente's first.
+    hands: [[u8; 7]; 2],
+    white_to_move: bool,
+}
+
+#[allow(dead_code)]
+impl Board {
+    pub fn from_position(pos: &Position) -> Self {
+        Self::from_setup(&BoardSetup::from_position(pos))
+    }
+
+    pub fn from_setup(setup: &BoardSetup) -> Self {
+        let mut board = Self {
+            squares: [0; SQUARES],
+            hands: [[0; 7]; 2],
+            white_to_move: setup.side_to_move() == Color::White,
+        };
+        for sq in squares() {
+            board.squares[index(sq)] = setup.piece_at(sq).map_or(0, code);
+        }
+        for color in [Color::Black, Color::White] {
+            for (idx, piece_type) in HAND_PIECE_TYPES.into_iter().enumerate() {
+                board.hands[color as usize][idx] = setup.hand(color, piece_type);
+            }
+        }
+        board
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        if self.white_to_move {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.piece(index(sq))
+    }
+
+    pub fn hand(&self, color: Color, piece_type: PieceType) -> u8 {
+        hand_index(piece_type).map_or(0, |idx| self.hands[color as usize][idx])
+    }
+
+    pub fn in_check(&self, color: Color) -> bool {
+        self.king(color)
+            .is_some_and(|king| self.is_attacked(king, color.flip()))
+    }
+
+    /// Plays `mv`, which must be legal here.
+    pub fn play(&mut self, mv: Move) {
+        let us = self.side_to_move();
+        match mv {
+            Move::Normal { from, to, promote } => {
+                let (from, to) = (index(from), index(to));
+                let Some(mut piece) = self.piece(from) else {
+                    return;
+                };
+                if let Some(captured) = self.piece(to)
+                    && let Some(idx) = hand_index(unpromoted_piece_type(captured.piece_type))
+                {
+                    self.hands[us as usize][idx] += 1;
+                }
+                if promote {
+                    piece.piece_type = promoted_piece_type(piece.piece_type);
+                }
+                self.squares[to] = code(piece);
+                self.squares[from] = 0;
+            }
+            Move::Drop { to, piece_type } => {
+                if let Some(idx) = hand_index(piece_type) {
+                    self.hands[us as usize][idx] -= 1;
+                }
+                self.squares[index(to)] = code(Piece {
+                    piece_type,
+                    color: us,
+                });
+            }
+        }
+        self.white_to_move = !self.white_to_move;
+    }
+
+    pub fn legal_moves(&self) -> LegalMoves {
+        let us = self.side_to_move();
+        let them = us.flip();
+        let king = self.king(us);
+        let checkers: Vec<usize> = king.map_or_else(Vec::new, |king| self.attackers(king, them));
+        let pins = king.map_or_else(Vec::new, |king| self.pins(king, us));
+        // Where a move other than the king's must land while in check.
+        let evasions = match (king, checkers.as_slice()) {
+            (Some(king), &[checker]) => {
+                let mut squares = between(king, checker);
+                squares.add(checker);
+                Some(squares)
+            }
+            (_, []) => None,
+            _ => Some(SquareSet::default()),
+        };
+
+        let mut out = LegalMoves {
+            board: vec![(0..0, SquareSet::default()); SQUARES],
+            ..LegalMoves::default()
+        };
+        for sq in squares() {
+            let from = index(sq);
+            let start = out.moves.len();
+            let mut targets = SquareSet::default();
+            if let Some(piece) = self.piece(from)
+                && piece.color == us
+            {
+                if piece.piece_type == PieceType::King {
+                    let mut without_king = self.clone();
+                    without_king.squares[from] = 0;
+                    self.for_each_target(from, piece, |to| {
+                        if !without_king.is_attacked(to, them) {
+                            targets.add(to);
+                            out.moves.push(Move::Normal {
+                                from: sq,
+                                to: square(to),
+                                promote: false,
+                            });
+                        }
+                    });
+                } else {
+                    let pin = pins
+                        .iter()
+                        .find(|(pinned, _)| *pinned == from)
+                        .map(|&(_, ray)| ray);
+                    self.for_each_target(from, piece, |to| {
+                        if evasions.is_some_and(|allowed| !allowed.has(to))
+                            || pin.is_some_and(|ray| !ray.has(to))
+                        {
+                            return;
+                        }
+                        targets.add(to);
+                        push_piece_moves(&mut out.moves, sq, square(to), piece);
+                    });
+                }
+            }
+            out.board[from] = (start..out.moves.len(), targets);
+        }
+
+        // Drops land on empty squares, and on the checking line when in check.
+        let mut open = SquareSet::default();
+        let mut pawn_files = SquareSet::default();
+        let pawn = code(Piece {
+            piece_type: PieceType::Pawn,
+            color: us,
+        });
+        for (at, &code) in self.squares.iter().enumerate() {
+            if code == 0 {
+                open.add(at);
+            } else if code == pawn {
+                pawn_files = pawn_files.or(file_squares(at / 9));
+            }
+        }
+        if let Some(allowed) = evasions {
+            open = open.and(allowed);
+        }
+        for (idx, piece_type) in HAND_PIECE_TYPES.into_iter().enumerate() {
+            let start = out.moves.len();
+            let mut targets = SquareSet::default();
+            if self.hands[us as usize][idx] > 0 && checkers.len() < 2 {
+                targets = open.without(dead_ranks(piece_type, us));
+                if piece_type == PieceType::Pawn {
+                    targets = targets.without(pawn_files);
+                    // Only a pawn dropped right in front of the king can mate.
+                    if let Some(front) = self
+                        .king(them)
+                        .and_then(|king| offset(king, 0, -forward(us)))
+                        && targets.has(front)
+                        && self.is_pawn_drop_mate(front)
+                    {
+                        targets = targets.without(SquareSet(1 << front));
+                    }
+                }
+                for to in targets.indices() {
+                    out.moves.push(Move::Drop {
+                        to: square(to),
+                        piece_type,
+                    });
+                }
+            }
+            out.drops.push((start..out.moves.len(), targets));
+        }
+        out
+    }
+
+    fn piece(&self, index: usize) -> Option<Piece> {
+        decode(self.squares[index])
+    }
+
+    fn king(&self, color: Color) -> Option<usize> {
+        let king = code(Piece {
+            piece_type: PieceType::King,
+            color,
+        });
+        self.squares.iter().position(|&code| code == king)
+    }
+
+    fn is_attacked(&self, target: usize, by: Color) -> bool {
+        !self.attackers(target, by).is_empty()
+    }
+
+    /// Squares of `by`'s pieces that attack `target`. Apart from knights, an
+    /// attacker is the first piece met in one of the eight directions.
+    fn attackers(&self, target: usize, by: Color) -> Vec<usize> {
+        let mut found = Vec::new();
+        for &(df, dr) in KING_STEPS {
+            let mut at = target;
+            while let Some(next) = offset(at, df, dr) {
+                at = next;
+                let Some(piece) = self.piece(next) else {
+                    continue;
+                };
+                if piece.color == by && self.attacks(next, piece, target) {
+                    found.push(next);
+                }
+                break;
+            }
+        }
+        for df in [-1, 1] {
+            if let Some(from) = offset(target, df, -2 * forward(by))
+                && self.piece(from)
+                    == Some(Piece {
+                        piece_type: PieceType::Knight,
+                        color: by,
+                    })
+            {
+                found.push(from);
+            }
+        }
+        found
+    }
+
+    /// Whether `piece` on `from` attacks `target`.
+    fn attacks(&self, from: usize, piece: Piece, target: usize) -> bool {
+        let forward = forward(piece.color);
+        let df = file_of(target) - file_of(from);
+        let dr = (rank_of(target) - rank_of(from)) * forward;
+        if steps(piece.piece_type).contains(&(df, dr)) {
+            return true;
+        }
+        let distance = df.abs().max(dr.abs());
+        slides(piece.piece_type).iter().any(|&(sf, sr)| {
+            distance > 0
+                && df == sf * distance
+                && dr == sr * distance
+                && (1..distance).all(|k| {
+                    offset(from, sf * k, sr * k * forward).is_some_and(|at| self.squares[at] == 0)
+                })
+        })
+    }
+
+    /// Calls `f` with every square `piece` on `from` could move to, ignoring
+    /// checks and pins.
+    fn for_each_target(&self, from: usize, piece: Piece, mut f: impl FnMut(usize)) {
+        let forward = forward(piece.color);
+        let open = |to: usize| {
+            self.piece(to)
+                .is_none_or(|other| other.color != piece.color)
+        };
+        for &(df, dr) in steps(piece.piece_type) {
+            if let Some(to) = offset(from, df, dr * forward)
+                && open(to)
+            {
+                f(to);
+            }
+        }
+        for &(df, dr) in slides(piece.piece_type) {
+            let mut at = from;
+            while let Some(to) = offset(at, df, dr * forward) {
+                if open(to) {
+                    f(to);
+                }
+                if self.squares[to] != 0 {
+                    break;
+                }
+                at = to;
+            }
+        }
+    }
+
+    /// Pieces of `us` pinned to the king on `king`, each with the squares it
+    /// may still move to: the line up to and including the pinning piece.
+    fn pins(&self, king: usize, us: Color) -> Vec<(usize, SquareSet)> {
+        let mut pins = Vec::new();
+        for &(df, dr) in KING_STEPS {
+            let mut ray = SquareSet::default();
+            let mut blocker = None;
+            let mut at = king;
+            while let Some(next) = offset(at, df, dr) {
+                ray.add(next);
+                at = next;
+                let Some(piece) = self.piece(next) else {
+                    continue;
+                };
+                if piece.color == us {
+                    if blocker.is_some() {
+                        break;
+                    }
+                    blocker = Some(next);
+                    continue;
+                }
+                // The pinner slides back along the ray, in its own frame.
+                let toward_king = (-df, -dr * forward(piece.color));
+                if let Some(pinned) = blocker
+                    && slides(piece.piece_type).contains(&toward_king)
+                {
+                    pins.push((pinned, ray));
+                }
+                break;
+            }
+        }
+        pins
+    }
+
+    /// Whether dropping a pawn on `to`, in front of the enemy king,
+    /// checkmates (uchifuzume).
+    fn is_pawn_drop_mate(&self, to: usize) -> bool {
+        let mut after = self.clone();
+        after.play(Move::Drop {
+            to: square(to),
+            piece_type: PieceType::Pawn,
+        });
+        after.legal_moves().is_empty()
+    }
+}
+
+/// Pushes the move from `from` to `to` with and without promotion, as the
+/// zones and dead-piece ranks allow.
+fn push_piece_moves(moves: &mut Vec<Move>, from: Square, to: Square, piece: Piece) {
+    let (from_idx, to_idx) = (index(from), index(to));
+    let can_promote = promoted_piece_type(piece.piece_type) != piece.piece_type
+        && (ranks_ahead(from_idx, piece.color) <= 2 || ranks_ahead(to_idx, piece.color) <= 2);
+    if ranks_ahead(to_idx, piece.color) >= min_ranks_ahead(piece.piece_type) {
+        moves.push(Move::Normal {
+            from,
+            to,
+            promote: false,
+        });
+    }
+    if can_promote {
+        moves.push(Move::Normal {
+            from,
+            to,
+            promote: true,
+        });
+    }
+}
+
+/// Ranks an unpromoted piece needs ahead of it to move again.
+fn min_ranks_ahead(piece_type: PieceType) -> i8 {
+    match piece_type {
+        PieceType::Pawn | PieceType::Lance => 1,
+        PieceType::Knight => 2,
+        _ => 0,
+    }
+}
+
+/// Squares where `color` may not drop `piece_type`, as it could never move.
+fn dead_ranks(piece_type: PieceType, color: Color) -> SquareSet {
+    let mut squares = SquareSet::default();
+    for ahead in 0..min_ranks_ahead(piece_type) as usize {
+        let rank = match color {
+            Color::Black => ahead,
+            Color::White => 8 - ahead,
+        };
+        squares = squares.or(rank_squares(rank));
+    }
+    squares
+}
+
+/// Ranks between `index` and the far edge for `color`.
+fn ranks_ahead(index: usize, color: Color) -> i8 {
+    match color {
+        Color::Black => rank_of(index),
+        Color::White => 8 - rank_of(index),
+    }
+}
+
+/// Squares strictly between `a` and `b` when they share a line.
+fn between(a: usize, b: usize) -> SquareSet {
+    let mut squares = SquareSet::default();
+    let df = file_of(b) - file_of(a);
+    let dr = rank_of(b) - rank_of(a);
+    if df != 0 && dr != 0 && df.abs() != dr.abs() {
+        return squares;
+    }
+    let (sf, sr) = (df.signum(), dr.signum());
+    let mut at = a;
+    while let Some(next) = offset(at, sf, sr) {
+        if next == b {
+            break;
+        }
+        squares.add(next);
+        at = next;
+    }
+    squares
+}
+
+fn steps(piece_type: PieceType) -> &'static [(i8, i8)] {
+    match piece_type {
+        PieceType::King => KING_STEPS,
+        PieceType::Gold
+        | PieceType::ProSilver
+        | PieceType::ProKnight
+        | PieceType::ProLance
+        | PieceType::ProPawn => GOLD_STEPS,
+        PieceType::Silver => SILVER_STEPS,
+        PieceType::Knight => KNIGHT_STEPS,
+        PieceType::Pawn => FORWARD,
+        PieceType::ProRook => DIAGONALS,
+        PieceType::ProBishop => ORTHOGONALS,
+        _ => &[],
+    }
+}
+
+fn slides(piece_type: PieceType) -> &'static [(i8, i8)] {
+    match piece_type {
+        PieceType::Rook | PieceType::ProRook => ORTHOGONALS,
+        PieceType::Bishop | PieceType::ProBishop => DIAGONALS,
+        PieceType::Lance => FORWARD,
+        _ => &[],
+    }
+}
+
+/// Rank step that moves `color` forward.
+fn forward(color: Color) -> i8 {
+    match color {
+        Color::Black => -1,
+        Color::White => 1,
+    }
+}
+
+fn code(piece: Piece) -> u8 {
+    let idx = PIECE_TYPES
+        .iter()
+        .position(|&piece_type| piece_type == piece.piece_type)
+        .expect("every piece type is listed");
+    let color = match piece.color {
+        Color::Black => 0,
+        Color::White => PIECE_TYPES.len(),
+    };
+    (1 + idx + color) as u8
+}
+
+fn decode(code: u8) -> Option<Piece> {
+    let idx = usize::from(code.checked_sub(1)?);
+    let color = if idx < PIECE_TYPES.len() {
+        Color::Black
+    } else {
+        Color::White
+    };
+    Some(Piece {
+        piece_type: PIECE_TYPES[idx % PIECE_TYPES.len()],
+        color,
+    })
+}
+
+fn hand_index(piece_type: PieceType) -> Option<usize> {
+    HAND_PIECE_TYPES.iter().position(|&pt| pt == piece_type)
+}
+
+fn index(sq: Square) -> usize {
+    sq.file() as usize * 9 + sq.rank() as usize
+}
+
+fn square(index: usize) -> Square {
+    Square::new((index / 9) as u8, (index % 9) as u8).expect("valid board coordinate")
+}
+
+fn file_of(index: usize) -> i8 {
+    (index / 9) as i8
+}
+
+fn rank_of(index: usize) -> i8 {
+    (index % 9) as i8
+}
+
+fn offset(index: usize, df: i8, dr: i8) -> Option<usize> {
+    let file = file_of(index) + df;
+    let rank = rank_of(index) + dr;
+    ((0..9).contains(&file) && (0..9).contains(&rank)).then(|| (file * 9 + rank) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usi::{parse_usi_move, parse_usi_square};
+
+    fn board(sfen: &str) -> Board {
+        Board::from_setup(&BoardSetup::from_sfen(sfen).expect("valid test SFEN"))
+    }
+
+    fn mv(text: &str) -> Move {
+        parse_usi_move(text).expect("valid test move")
+    }
+
+    fn sq(text: &str) -> Square {
+        parse_usi_square(text).expect("valid test square")
+    }
+
+    fn usi(moves: &[Move]) -> Vec<String> {
+        moves
+            .iter()
+            .map(|&mv| crate::core::usi::move_to_usi(mv))
+            .collect()
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        // The rook on 5e is pinned along the file, the knight on 4h along a
+        // diagonal.
+        let moves = board("k3r4/9/9/9/4R4/7b1/9/5N3/4K4 b - 1").legal_moves();
+        assert_eq!(
+            usi(moves.from_square(sq("5e"))),
+            [
+                "5e5d", "5e5c", "5e5c+", "5e5b", "5e5b+", "5e5a", "5e5a+", "5e5f", "5e5g", "5e5h"
+            ]
+        );
+        assert!(!moves.contains(mv("5e4e")));
+        assert!(moves.from_square(sq("4h")).is_empty());
+        assert!(moves.targets_from(sq("4h")).is_empty());
+    }
+
+    #[test]
+    fn double_check_allows_only_king_moves() {
+        // Rook and bishop both check; the gold could block the bishop alone.
+        let moves = board("k3r4/9/9/9/8b/9/9/9/4KG3 b G 1").legal_moves();
+        assert_eq!(usi(moves.all()), ["5i6h", "5i6i"]);
+        assert!(moves.drop_targets(PieceType::Gold).is_empty());
+    }
+
+    #[test]
+    fn single_check_is_blocked_or_the_checker_taken() {
+        let moves = board("k3r4/9/9/9/9/9/9/9/4KG3 b G 1").legal_moves();
+        assert!(moves.contains(mv("G*5e")));
+        assert!(!moves.contains(mv("G*4e")));
+        assert!(!moves.contains(mv("4i4h")));
+        assert!(moves.contains(mv("5i4h")));
+    }
+
+    #[test]
+    fn pawn_drop_mate_is_illegal() {
+        // The silver guards 1b and 2b, the gold 2a.
+        let moves = board("6G1k/9/7S1/9/9/9/9/9/K8 b P 1").legal_moves();
+        assert!(!moves.contains(mv("P*1b")));
+        assert!(!moves.drop_targets(PieceType::Pawn).contains(sq("1b")));
+        assert!(moves.contains(mv("P*1c")));
+    }
+
+    #[test]
+    fn pawn_drop_check_that_is_not_mate_is_legal() {
+        // Without the silver the king takes the pawn.
+        let moves = board("6G1k/9/9/9/9/9/9/9/K8 b P 1").legal_moves();
+        assert!(moves.contains(mv("P*1b")));
+    }
+
+    #[test]
+    fn no_second_pawn_on_a_file() {
+        // A tokin does not count as a pawn.
+        let moves = board("k8/9/6+P2/9/9/9/4P4/9/8K b P 1").legal_moves();
+        let targets = moves.drop_targets(PieceType::Pawn);
+        for rank in 'a'..='i' {
+            assert!(!targets.contains(sq(&format!("5{rank}"))));
+        }
+        assert!(targets.contains(sq("3d")));
+        assert!(targets.contains(sq("3i")));
+    }
+
+    #[test]
+    fn no_drops_or_moves_onto_dead_ranks() {
+        let moves = board("9/5P3/7L1/3N5/k8/9/9/9/8K b NLP 1").legal_moves();
+        assert_eq!(usi(moves.from_square(sq("4b"))), ["4b4a+"]);
+        assert_eq!(usi(moves.from_square(sq("6d"))), ["6d5b+", "6d7b+"]);
+        assert_eq!(usi(moves.from_square(sq("2c"))), ["2c2b", "2c2b+", "2c2a+"]);
+
+        let knight = moves.drop_targets(PieceType::Knight);
+        let lance = moves.drop_targets(PieceType::Lance);
+        let pawn = moves.drop_targets(PieceType::Pawn);
+        for file in 1..=9 {
+            let a = sq(&format!("{file}a"));
+            let b = sq(&format!("{file}b"));
+            assert!(!knight.contains(a) && !knight.contains(b));
+            assert!(!lance.contains(a) && !pawn.contains(a));
+        }
+        assert!(knight.contains(sq("5c")));
+        assert!(lance.contains(sq("5b")));
+        assert!(pawn.contains(sq("5b")));
+        assert!(!pawn.contains(sq("4c")));
+    }
+
+    #[test]
+    fn gote_drops_avoid_its_own_dead_ranks() {
+        let moves = board("8k/9/9/9/9/9/9/9/K8 w nlp 1").legal_moves();
+        let knight = moves.drop_targets(PieceType::Knight);
+        assert!(!knight.contains(sq("5i")) && !knight.contains(sq("5h")));
+        assert!(knight.contains(sq("5g")));
+        assert!(!moves.drop_targets(PieceType::Lance).contains(sq("5i")));
+        assert!(moves.drop_targets(PieceType::Lance).contains(sq("5h")));
+    }
+}
@@ -0,0 +1,425 @@
+use std::fmt::Write as _;
+
+use shogi::{Color, Move, PieceType, Position, Square};
+
+use super::game::{GameState, MoveEntry, piece_type_label, promoted_piece_type};
+
+const FULLWIDTH_DIGITS: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+const KANJI_NUMERALS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// Piece names used in Japanese move text, longest spellings first so that
+/// prefix matching prefers `成香` over `成`.
+const KANJI_PIECE_NAMES: [(&str, PieceType); 19] = [
+    ("成香", PieceType::ProLance),
+    ("成桂", PieceType::ProKnight),
+    ("成銀", PieceType::ProSilver),
+    ("歩", PieceType::Pawn),
+    ("香", PieceType::Lance),
+    ("桂", PieceType::Knight),
+    ("銀", PieceType::Silver),
+    ("金", PieceType::Gold),
+    ("角", PieceType::Bishop),
+    ("飛", PieceType::Rook),
+    ("玉", PieceType::King),
+    ("王", PieceType::King),
+    ("と", PieceType::ProPawn),
+    ("杏", PieceType::ProLance),
+    ("圭", PieceType::ProKnight),
+    ("全", PieceType::ProSilver),
+    ("馬", PieceType::ProBishop),
+    ("龍", PieceType::ProRook),
+    ("竜", PieceType::ProRook),
+];
+
+/// Name of `piece_type` in Japanese move text, e.g. `歩`, `成銀`, `龍`.
+pub fn kanji_piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::King => "玉",
+        PieceType::Rook => "飛",
+        PieceType::Bishop => "角",
+        PieceType::Gold => "金",
+        PieceType::Silver => "銀",
+        PieceType::Knight => "桂",
+        PieceType::Lance => "香",
+        PieceType::Pawn => "歩",
+        PieceType::ProRook => "龍",
+        PieceType::ProBishop => "馬",
+        PieceType::ProSilver => "成銀",
+        PieceType::ProKnight => "成桂",
+        PieceType::ProLance => "成香",
+        PieceType::ProPawn => "と",
+    }
+}
+
+/// Single-character name of `piece_type`, as used in board diagrams.
+pub fn kanji_piece_char(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::ProSilver => '全',
+        PieceType::ProKnight => '圭',
+        PieceType::ProLance => '杏',
+        other => kanji_piece_name(other)
+            .chars()
+            .next()
+            .expect("piece names are not empty"),
+    }
+}
+
+/// Matches a piece name at the start of `text`, returning it with the byte length consumed.
+pub fn parse_kanji_piece(text: &str) -> Option<(PieceType, usize)> {
+    KANJI_PIECE_NAMES
+        .iter()
+        .find(|(name, _)| text.starts_with(name))
+        .map(|&(name, piece_type)| (piece_type, name.len()))
+}
+
+/// Full-width digit for a 0-based file, e.g. `７` for file index 6.
+pub fn file_char(file: u8) -> char {
+    FULLWIDTH_DIGITS[file as usize]
+}
+
+/// Kanji numeral for a 0-based rank, e.g. `六` for rank index 5.
+pub fn rank_char(rank: u8) -> char {
+    KANJI_NUMERALS[rank as usize]
+}
+
+/// Japanese square name, e.g. `７六`.
+pub fn japanese_square(sq: Square) -> String {
+    format!("{}{}", file_char(sq.file()), rank_char(sq.rank()))
+}
+
+/// Parses a file written as a full-width or ASCII digit into a 0-based index.
+pub fn parse_file_char(c: char) -> Option<u8> {
+    FULLWIDTH_DIGITS
+        .iter()
+        .position(|&d| d == c)
+        .map(|idx| idx as u8)
+        .or_else(|| parse_ascii_digit(c))
+}
+
+/// Parses a rank written as a kanji numeral or digit into a 0-based index.
+pub fn parse_rank_char(c: char) -> Option<u8> {
+    KANJI_NUMERALS
+        .iter()
+        .position(|&d| d == c)
+        .map(|idx| idx as u8)
+        .or_else(|| parse_file_char(c))
+}
+
+/// Kanji rendering of small counts, e.g. `二`, `十`, `十八`.
+pub fn kanji_number(n: u32) -> String {
+    let tens = n / 10;
+    let ones = n % 10;
+    let mut out = String::new();
+    if tens > 1 {
+        out.push(KANJI_NUMERALS[tens as usize - 1]);
+    }
+    if tens > 0 {
+        out.push('十');
+    }
+    if ones > 0 {
+        out.push(KANJI_NUMERALS[ones as usize - 1]);
+    }
+    out
+}
+
+/// Inverse of [`kanji_number`]; also accepts ASCII digits.
+pub fn parse_kanji_number(text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(n) = text.parse() {
+        return Some(n);
+    }
+
+    let mut total = 0;
+    let mut pending = 0;
+    for c in text.chars() {
+        if c == '十' {
+            total += if pending == 0 { 10 } else { pending * 10 };
+            pending = 0;
+        } else {
+            let idx = KANJI_NUMERALS.iter().position(|&d| d == c)?;
+            pending = idx as u32 + 1;
+        }
+    }
+    Some(total + pending)
+}
+
+/// KIF move text such as `７六歩(77)`, `同　角成(88)` or `５五銀打`; `prev_to`
+/// is the previous move's destination, written as `同`.
+pub fn japanese_move(entry: &MoveEntry, prev_to: Option<Square>) -> String {
+    let to = entry.to();
+    let mut text = if prev_to == Some(to) {
+        "同　".to_string()
+    } else {
+        japanese_square(to)
+    };
+    text.push_str(kanji_piece_name(entry.piece.piece_type));
+    match entry.mv {
+        Move::Drop { .. } => text.push('打'),
+        Move::Normal { from, promote, .. } => {
+            if promote {
+                text.push('成');
+            } else if can_promote(entry, from, to) {
+                text.push_str("不成");
+            }
+            let _ = write!(text, "({}{})", from.file() + 1, from.rank() + 1);
+        }
+    }
+    text
+}
+
+/// How moves are written in the move list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Notation {
+    /// `▲７六歩`, with the JSA words that tell identical pieces apart.
+    #[default]
+    Japanese,
+    /// Hodges notation, e.g. `P-7f`, `Bx2b+`, `G6i-5h`.
+    Western,
+}
+
+impl Notation {
+    pub fn all() -> [Self; 2] {
+        [Self::Japanese, Self::Western]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Japanese => "Japanese (▲７六歩)",
+            Self::Western => "Western (P-7f)",
+        }
+    }
+
+    /// Name of `piece_type` written out, e.g. `銀` or `Silver`.
+    pub fn piece_name(self, piece_type: PieceType) -> &'static str {
+        match self {
+            Self::Japanese => kanji_piece_name(piece_type),
+            Self::Western => piece_type_label(piece_type),
+        }
+    }
+
+    /// Text of `entry` played from `pos`, the position before it; `prev_to`
+    /// is the previous move's destination.
+    pub fn format_move(self, pos: &Position, entry: &MoveEntry, prev_to: Option<Square>) -> String {
+        match self {
+            Self::Japanese => japanese_move_label(pos, entry, prev_to),
+            Self::Western => western_move(pos, entry),
+        }
+    }
+}
+
+/// The moves recorded at one ply of a line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlyMoves {
+    /// Text of every recorded move, main line first.
+    pub texts: Vec<String>,
+    /// Index in `texts` of the move on the line.
+    pub selected: usize,
+}
+
+/// Texts for every ply of the displayed line of `game`, with the
+/// alternatives recorded at each ply.
+pub fn line_moves(game: &GameState, notation: Notation) -> Vec<PlyMoves> {
+    let mut pos = Position::new();
+    if pos.set_sfen(game.start_sfen()).is_err() {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let mut prev_to = None;
+    for (idx, entry) in game.history().iter().enumerate() {
+        let ply = idx + 1;
+        out.push(PlyMoves {
+            texts: game
+                .variations(ply)
+                .into_iter()
+                .map(|alt| notation.format_move(&pos, alt, prev_to))
+                .collect(),
+            selected: game.variation_index(ply).unwrap_or(0),
+        });
+        // A repetition is reported as an error but leaves the move on the
+        // board, and can only end the line anyway.
+        if pos.make_move(entry.mv).is_err() {
+            break;
+        }
+        prev_to = Some(entry.to());
+    }
+    out
+}
+
+/// Move list text such as `▲７六歩`, `△同歩`, `▲５八金右` or `▲２二角成`.
+pub fn japanese_move_label(pos: &Position, entry: &MoveEntry, prev_to: Option<Square>) -> String {
+    let to = entry.to();
+    let mut text = String::from(match entry.piece.color {
+        Color::Black => '▲',
+        Color::White => '△',
+    });
+    if prev_to == Some(to) {
+        text.push('同');
+    } else {
+        text.push_str(&japanese_square(to));
+    }
+    text.push_str(kanji_piece_name(entry.piece.piece_type));
+    text.push_str(&relative_words(pos, entry));
+    if let Move::Normal { from, promote, .. } = entry.mv {
+        if promote {
+            text.push('成');
+        } else if can_promote(entry, from, to) {
+            text.push_str("不成");
+        }
+    }
+    text
+}
+
+/// Words that tell the moving piece from others of its kind that could
+/// also reach the destination: the direction of the move (`上`, `引`,
+/// `寄`), else the piece's side (`右`, `左`, `直`), combining both when the
+/// side alone is not enough. `打` marks a drop that a piece on the board
+/// could have played as a move.
+pub(crate) fn relative_words(pos: &Position, entry: &MoveEntry) -> String {
+    let rivals = rivals(pos, entry);
+    let Some(from) = entry.from() else {
+        return if rivals.is_empty() { "" } else { "打" }.to_string();
+    };
+    if rivals.is_empty() {
+        return String::new();
+    }
+
+    let color = entry.piece.color;
+    let to = entry.to();
+    // Coordinates seen from the mover's side of the board.
+    let forward = |sq: Square| match color {
+        Color::Black => 8 - sq.rank() as i8,
+        Color::White => sq.rank() as i8,
+    };
+    let rightward = |sq: Square| match color {
+        Color::Black => 8 - sq.file() as i8,
+        Color::White => sq.file() as i8,
+    };
+    let direction = |sq: Square| match forward(to) - forward(sq) {
+        d if d > 0 => "上",
+        0 => "寄",
+        _ => "引",
+    };
+
+    let moving = direction(from);
+    let same_direction: Vec<Square> = rivals
+        .iter()
+        .copied()
+        .filter(|&sq| direction(sq) == moving)
+        .collect();
+    if same_direction.is_empty() {
+        return moving.to_string();
+    }
+    // Dragons and horses are told apart by side only.
+    let ranged = matches!(
+        entry.piece.piece_type,
+        PieceType::ProRook | PieceType::ProBishop
+    );
+    if !ranged && from.file() == to.file() && moving == "上" {
+        return "直".to_string();
+    }
+
+    let x = rightward(from);
+    let right_of = |squares: &[Square]| squares.iter().all(|&sq| rightward(sq) < x);
+    let left_of = |squares: &[Square]| squares.iter().all(|&sq| rightward(sq) > x);
+    let (side, alone) = if right_of(&same_direction) {
+        ("右", right_of(&rivals))
+    } else if left_of(&same_direction) {
+        ("左", left_of(&rivals))
+    } else {
+        return moving.to_string();
+    };
+    if alone {
+        side.to_string()
+    } else {
+        format!("{side}{moving}")
+    }
+}
+
+/// Other pieces of the mover's kind that could move to its destination.
+fn rivals(pos: &Position, entry: &MoveEntry) -> Vec<Square> {
+    let to = entry.to();
+    let mut out = Vec::new();
+    for file in 0..9 {
+        for rank in 0..9 {
+            let sq = Square::new(file, rank).expect("valid board coordinate");
+            if Some(sq) == entry.from() || *pos.piece_at(sq) != Some(entry.piece) {
+                continue;
+            }
+            if pos.move_candidates(sq, entry.piece).any(|dest| dest == to) {
+                out.push(sq);
+            }
+        }
+    }
+    out
+}
+
+/// Hodges notation: piece, origin when another piece of its kind could make
+/// the same move, `-`, `x` or `*`, destination, and `+` or `=` for a
+/// promotion taken or declined.
+pub fn western_move(pos: &Position, entry: &MoveEntry) -> String {
+    let piece = western_piece(entry.piece.piece_type);
+    match entry.mv {
+        Move::Drop { to, .. } => format!("{piece}*{}", western_square(to)),
+        Move::Normal { from, to, promote } => {
+            let origin = if rivals(pos, entry).is_empty() {
+                String::new()
+            } else {
+                western_square(from)
+            };
+            let action = if entry.captured.is_some() { 'x' } else { '-' };
+            let suffix = if promote {
+                "+"
+            } else if can_promote(entry, from, to) {
+                "="
+            } else {
+                ""
+            };
+            format!("{piece}{origin}{action}{}{suffix}", western_square(to))
+        }
+    }
+}
+
+pub(crate) fn western_piece(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::King => "K",
+        PieceType::Rook => "R",
+        PieceType::Bishop => "B",
+        PieceType::Gold => "G",
+        PieceType::Silver => "S",
+        PieceType::Knight => "N",
+        PieceType::Lance => "L",
+        PieceType::Pawn => "P",
+        PieceType::ProRook => "+R",
+        PieceType::ProBishop => "+B",
+        PieceType::ProSilver => "+S",
+        PieceType::ProKnight => "+N",
+        PieceType::ProLance => "+L",
+        PieceType::ProPawn => "+P",
+    }
+}
+
+/// Square as file digit and rank letter, e.g. `7f`.
+pub(crate) fn western_square(sq: Square) -> String {
+    format!("{}{}", sq.file() + 1, char::from(b'a' + sq.rank()))
+}
+
+pub(crate) fn can_promote(entry: &MoveEntry, from: Square, to: Square) -> bool {
+    let piece = entry.piece;
+    if promoted_piece_type(piece.piece_type) == piece.piece_type {
+        return false;
+    }
+    let in_zone = |sq: Square| match piece.color {
+        Color::Black => sq.rank() <= 2,
+        Color::White => sq.rank() >= 6,
+    };
+    in_zone(from) || in_zone(to)
+}
+
+fn parse_ascii_digit(c: char) -> Option<u8> {
+    match c {
+        '1'..='9' => Some(c as u8 - b'1'),
+        _ => None,
+    }
+}
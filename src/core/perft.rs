@@ -0,0 +1,271 @@
+//! Perft: counts the leaf positions of the legal move tree to a fixed depth.
+//!
+//! Counts are compared against published figures for a few well-known
+//! positions in [`PERFT_SUITE`], and [`compare_with_shogi`] walks the same tree
+//! with the `shogi` crate's own move validation, reporting the first position
+//! where the two disagree.
+
+use std::fmt;
+
+use shogi::{Move, Piece, Position};
+
+use super::movegen::Board;
+use super::sfen::{HAND_PIECE_TYPES, squares};
+use super::usi::move_to_usi;
+
+/// A position with its published perft counts, from depth 1 upwards.
+#[derive(Clone, Copy, Debug)]
+pub struct PerftCase {
+    pub name: &'static str,
+    pub sfen: &'static str,
+    pub counts: &'static [u64],
+}
+
+pub const PERFT_SUITE: &[PerftCase] = &[
+    PerftCase {
+        name: "start position",
+        sfen: "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        counts: &[30, 900, 25_470, 719_731, 19_861_490],
+    },
+    PerftCase {
+        // Promoted pieces, pieces in hand for both sides and Gote to move.
+        name: "matsuri",
+        sfen: "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        counts: &[207, 28_684, 4_809_015],
+    },
+    PerftCase {
+        // The most legal moves known in one position, mostly drops and
+        // promotions.
+        name: "593 moves",
+        sfen: "R8/2K1S1SSk/4B4/9/9/9/9/9/1L1L1L3 b RBGSNLP3g3n17p 1",
+        counts: &[593, 105_677, 53_393_368],
+    },
+];
+
+/// Leaf positions `depth` plies below `board`.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .all()
+        .iter()
+        .map(|&mv| {
+            let mut child = board.clone();
+            child.play(mv);
+            perft(&child, depth - 1)
+        })
+        .sum()
+}
+
+/// [`perft`] split by the first move, in generation order.
+pub fn divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    board
+        .legal_moves()
+        .all()
+        .iter()
+        .map(|&mv| {
+            let mut child = board.clone();
+            child.play(mv);
+            (mv, perft(&child, depth - 1))
+        })
+        .collect()
+}
+
+/// A position where the generator and the `shogi` crate list different moves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub sfen: String,
+    /// Accepted by the `shogi` crate but not generated.
+    pub missing: Vec<Move>,
+    /// Generated but rejected by the `shogi` crate.
+    pub extra: Vec<Move>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |moves: &[Move]| {
+            moves
+                .iter()
+                .map(|&mv| move_to_usi(mv))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        write!(f, "moves differ in `{}`", self.sfen)?;
+        if !self.missing.is_empty() {
+            write!(f, "; missing: {}", list(&self.missing))?;
+        }
+        if !self.extra.is_empty() {
+            write!(f, "; not legal: {}", list(&self.extra))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Walks `depth` plies from `pos`, checking at every position that the
+/// generated moves are exactly those `shogi::Position::make_move` accepts.
+/// Returns the perft count when they agree throughout.
+pub fn compare_with_shogi(pos: &Position, depth: u32) -> Result<u64, Divergence> {
+    if depth == 0 {
+        return Ok(1);
+    }
+    let sfen = pos.to_sfen();
+    let generated = Board::from_position(pos).legal_moves();
+    let accepted = accepted_moves(&sfen);
+    let missing: Vec<Move> = accepted
+        .iter()
+        .map(|(mv, _)| *mv)
+        .filter(|&mv| !generated.contains(mv))
+        .collect();
+    let extra: Vec<Move> = generated
+        .all()
+        .iter()
+        .copied()
+        .filter(|mv| !accepted.iter().any(|(accepted, _)| accepted == mv))
+        .collect();
+    if !missing.is_empty() || !extra.is_empty() {
+        return Err(Divergence {
+            sfen,
+            missing,
+            extra,
+        });
+    }
+
+    let mut nodes = 0;
+    for (_, child) in accepted {
+        // A fresh position per node keeps repetition history out of the walk.
+        let mut child_pos = Position::new();
+        child_pos
+            .set_sfen(&child)
+            .expect("the shogi crate reads back its own SFEN");
+        nodes += compare_with_shogi(&child_pos, depth - 1)?;
+    }
+    Ok(nodes)
+}
+
+/// Every move the `shogi` crate plays from `sfen`, with the SFEN it leads to.
+fn accepted_moves(sfen: &str) -> Vec<(Move, String)> {
+    let mut pos = Position::new();
+    if pos.set_sfen(sfen).is_err() {
+        return Vec::new();
+    }
+    let mover = pos.side_to_move();
+    let mut candidates = Vec::new();
+    for from in squares() {
+        let Some(piece) = *pos.piece_at(from) else {
+            continue;
+        };
+        if piece.color != mover {
+            continue;
+        }
+        for to in pos.move_candidates(from, piece) {
+            for promote in [false, true] {
+                candidates.push(Move::Normal { from, to, promote });
+            }
+        }
+    }
+    for piece_type in HAND_PIECE_TYPES {
+        if pos.hand(Piece {
+            piece_type,
+            color: mover,
+        }) == 0
+        {
+            continue;
+        }
+        for to in squares() {
+            if pos.piece_at(to).is_none() {
+                candidates.push(Move::Drop { to, piece_type });
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for mv in candidates {
+        if pos.make_move(mv).is_ok() {
+            out.push((mv, pos.to_sfen()));
+            let _ = pos.unmake_move();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sfen::BoardSetup;
+
+    /// Counts up to this many nodes run by default, the rest in `suite_deep`.
+    const QUICK_NODES: u64 = 30_000;
+
+    fn board(sfen: &str) -> Board {
+        Board::from_setup(&BoardSetup::from_sfen(sfen).expect("suite SFENs are valid"))
+    }
+
+    fn position(sfen: &str) -> Position {
+        crate::core::init();
+        let mut pos = Position::new();
+        pos.set_sfen(sfen).expect("suite SFENs are valid");
+        pos
+    }
+
+    fn check_suite(max_nodes: u64) {
+        for case in PERFT_SUITE {
+            let board = board(case.sfen);
+            for (depth, &expected) in (1..).zip(case.counts) {
+                if expected <= max_nodes {
+                    assert_eq!(
+                        perft(&board, depth),
+                        expected,
+                        "{} depth {depth}",
+                        case.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn suite_shallow() {
+        check_suite(QUICK_NODES);
+    }
+
+    #[test]
+    #[ignore = "takes minutes; run with --ignored"]
+    fn suite_deep() {
+        check_suite(u64::MAX);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let board = board(PERFT_SUITE[1].sfen);
+        let split = divide(&board, 2);
+        assert_eq!(split.len(), 207);
+        assert_eq!(split.iter().map(|(_, nodes)| nodes).sum::<u64>(), 28_684);
+    }
+
+    #[test]
+    fn agrees_with_shogi_on_drops_and_promotions() {
+        for (sfen, depth) in [
+            (PERFT_SUITE[0].sfen, 2),
+            // Matsuri: promoted pieces and both hands, Gote to move.
+            (PERFT_SUITE[1].sfen, 1),
+            // 593 moves: mostly drops and promotions.
+            (PERFT_SUITE[2].sfen, 1),
+            // Pawns and lances one step from promoting, every kind in hand.
+            ("4k4/1P1P1P1P1/9/9/9/9/9/9/L3K3L b RBGSNLPrbgsnlp 1", 2),
+        ] {
+            if let Err(divergence) = compare_with_shogi(&position(sfen), depth) {
+                panic!("{divergence}");
+            }
+        }
+    }
+}
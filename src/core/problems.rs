@@ -0,0 +1,310 @@
+//! Tsume problem sets for the trainer, and the user's progress through them.
+//!
+//! A set is either a text file with one problem per line, an SFEN or a USI
+//! `position` line whose moves give the solution, or a KIF file holding one
+//! or more problems, each a board diagram followed by its solution.
+//!
+//! Progress is stored as `tsume_progress.ini` in the settings directory, one
+//! problem per line keyed by its position:
+//!
+//! ```text
+//! lnsgkgsnl/9/... b - = solved 3
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use shogi::Move;
+
+use super::game::{GameState, position_key};
+use super::record::{self, RecordFormat};
+use super::{kif, usi};
+use crate::config;
+
+const PROGRESS_FILE: &str = "tsume_progress.ini";
+
+/// KIF header keys that name a problem, in order of preference.
+const TITLE_KEYS: [&str; 3] = ["作品名", "表題", "題名"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub title: String,
+    pub sfen: String,
+    /// Solution given in the file; empty when the solver has to find one.
+    pub solution: Vec<Move>,
+}
+
+#[derive(Debug)]
+pub enum ProblemSetError {
+    Io(io::Error),
+    Invalid { line: usize, message: String },
+    Empty,
+}
+
+impl fmt::Display for ProblemSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Invalid { line, message } => write!(f, "line {line}: {message}"),
+            Self::Empty => write!(f, "no problems found"),
+        }
+    }
+}
+
+impl std::error::Error for ProblemSetError {}
+
+/// Reads a problem set; `.kif` and `.kifu` files are read as KIF, anything
+/// else as one position per line.
+pub fn load_problems(path: &Path) -> Result<Vec<Problem>, ProblemSetError> {
+    let bytes = fs::read(path).map_err(ProblemSetError::Io)?;
+    let text = record::decode_text(&bytes);
+    let problems = if RecordFormat::from_path(path) == Some(RecordFormat::Kif) {
+        parse_kif_problems(&text)?
+    } else {
+        parse_sfen_problems(&text)?
+    };
+    if problems.is_empty() {
+        return Err(ProblemSetError::Empty);
+    }
+    Ok(problems)
+}
+
+/// One problem per line: an SFEN, or a `position` line such as
+/// `sfen ... moves 2c2b+ 3a2b 3c2b`. Blank lines and `#` comments are skipped.
+pub fn parse_sfen_problems(text: &str) -> Result<Vec<Problem>, ProblemSetError> {
+    let mut problems = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let position = if line.starts_with("position") || line.starts_with("sfen") {
+            line.to_string()
+        } else {
+            format!("sfen {line}")
+        };
+        let game = usi::parse_position(&position).map_err(|err| ProblemSetError::Invalid {
+            line: idx + 1,
+            message: err.to_string(),
+        })?;
+        problems.push(problem(&game, format!("Problem {}", problems.len() + 1)));
+    }
+    Ok(problems)
+}
+
+/// Problems written one after another in KIF, each with its main line as
+/// the solution.
+pub fn parse_kif_problems(text: &str) -> Result<Vec<Problem>, ProblemSetError> {
+    let mut problems = Vec::new();
+    for (first_line, chunk) in split_kif(text) {
+        let game = kif::parse(&chunk).map_err(|err| ProblemSetError::Invalid {
+            line: first_line + err.line.saturating_sub(1),
+            message: err.message,
+        })?;
+        let title = TITLE_KEYS
+            .iter()
+            .find_map(|key| {
+                game.info()
+                    .extra
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.clone())
+            })
+            .or_else(|| game.info().event.clone())
+            .unwrap_or_else(|| format!("Problem {}", problems.len() + 1));
+        problems.push(problem(&game, title));
+    }
+    Ok(problems)
+}
+
+fn problem(game: &GameState, title: String) -> Problem {
+    Problem {
+        title,
+        sfen: game.start_sfen().to_string(),
+        solution: game.history().iter().map(|entry| entry.mv).collect(),
+    }
+}
+
+/// Splits KIF text into records with the line each starts on. A new record
+/// starts at a header line after moves or a finished board diagram, or at a
+/// second board diagram.
+fn split_kif(text: &str) -> Vec<(usize, String)> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut first_line = 1;
+    let mut has_moves = false;
+    let mut board_rows = 0;
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        // Sente's hand comes right after the diagram it belongs to.
+        let header = line.contains('：')
+            && !line.starts_with(['*', '#'])
+            && !line.starts_with("先手の持駒")
+            && !line.starts_with("下手の持駒");
+        let board_row = line.starts_with('|');
+        let ends_record =
+            (header && (has_moves || board_rows == 9)) || (board_row && board_rows == 9);
+        if ends_record && !current.trim().is_empty() {
+            chunks.push((first_line, std::mem::take(&mut current)));
+            first_line = idx + 1;
+            has_moves = false;
+            board_rows = 0;
+        }
+        if board_row {
+            board_rows += 1;
+        }
+        if line.starts_with(|c: char| c.is_ascii_digit()) {
+            has_moves = true;
+        }
+        current.push_str(raw);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        chunks.push((first_line, current));
+    }
+    chunks
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProblemStatus {
+    #[default]
+    Unsolved,
+    /// Tried and never solved.
+    Failed,
+    Solved,
+}
+
+impl ProblemStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unsolved => "unsolved",
+            Self::Failed => "failed",
+            Self::Solved => "solved",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "unsolved" => Some(Self::Unsolved),
+            "failed" => Some(Self::Failed),
+            "solved" => Some(Self::Solved),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProblemRecord {
+    pub status: ProblemStatus,
+    pub attempts: u32,
+}
+
+#[derive(Debug)]
+pub struct ProgressError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ProgressError {}
+
+/// Outcome of every problem tried, whichever set it came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TsumeProgress {
+    records: BTreeMap<String, ProblemRecord>,
+}
+
+impl TsumeProgress {
+    /// Reads progress from the settings directory. A missing or unreadable
+    /// file starts afresh.
+    pub fn load() -> Self {
+        let Some(path) = config::config_file(PROGRESS_FILE) else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).unwrap_or_else(|err| {
+                eprintln!("ignoring {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config::config_file(PROGRESS_FILE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no settings directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.write())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ProgressError> {
+        let mut progress = Self::default();
+        for (idx, raw) in text.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| ProgressError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let (key, value) = line
+                .rsplit_once('=')
+                .ok_or_else(|| error("expected `position = status attempts`"))?;
+            let mut fields = value.split_whitespace();
+            let status = fields
+                .next()
+                .and_then(ProblemStatus::parse)
+                .ok_or_else(|| error("expected `solved`, `failed` or `unsolved`"))?;
+            let attempts = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| error("expected an attempt count"))?;
+            progress
+                .records
+                .insert(key.trim().to_string(), ProblemRecord { status, attempts });
+        }
+        Ok(progress)
+    }
+
+    pub fn write(&self) -> String {
+        let mut out = String::from("# rshogi tsume progress\n");
+        for (key, record) in &self.records {
+            out.push_str(&format!(
+                "{key} = {} {}\n",
+                record.status.label(),
+                record.attempts
+            ));
+        }
+        out
+    }
+
+    pub fn record(&self, sfen: &str) -> ProblemRecord {
+        self.records
+            .get(&position_key(sfen))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Counts an attempt; a problem once solved stays solved.
+    pub fn record_attempt(&mut self, sfen: &str, solved: bool) {
+        let record = self.records.entry(position_key(sfen)).or_default();
+        record.attempts += 1;
+        if solved {
+            record.status = ProblemStatus::Solved;
+        } else if record.status != ProblemStatus::Solved {
+            record.status = ProblemStatus::Failed;
+        }
+    }
+}
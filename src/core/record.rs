@@ -0,0 +1,184 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::csa::{self, CsaError};
+use super::game::{GameState, SfenLoadError};
+use super::kif::{self, KifError};
+use super::usi::{self, UsiError};
+
+/// Record file types recognised by extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Kakinoki format; `.kif` is Shift_JIS, `.kifu` is UTF-8.
+    Kif,
+    /// CSA standard format, written as UTF-8.
+    Csa,
+    /// A USI `position` line; only the displayed line's moves are kept.
+    Usi,
+    /// A single SFEN of the displayed position, without any moves.
+    Sfen,
+}
+
+impl RecordFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(&extension(path)?)
+    }
+
+    /// Reads a format name as given on the command line, e.g. `kif`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "kif" | "kifu" => Some(Self::Kif),
+            "csa" => Some(Self::Csa),
+            "usi" => Some(Self::Usi),
+            "sfen" => Some(Self::Sfen),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of record text whose file name says nothing.
+    pub fn detect(text: &str) -> Self {
+        let first = text
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(['#', '\'']))
+            .unwrap_or("");
+        if ["position", "startpos", "sfen "]
+            .iter()
+            .any(|prefix| first.starts_with(prefix))
+        {
+            Self::Usi
+        } else if first
+            .split_whitespace()
+            .next()
+            .is_some_and(|board| board.matches('/').count() == 8)
+        {
+            Self::Sfen
+        } else if first.starts_with(['V', 'N', '$', 'P', '+', '-']) && first.is_ascii() {
+            Self::Csa
+        } else {
+            Self::Kif
+        }
+    }
+
+    /// Extension used when naming new files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Kif => "kif",
+            Self::Csa => "csa",
+            Self::Usi => "usi",
+            Self::Sfen => "sfen",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    UnsupportedFormat(PathBuf),
+    Kif(KifError),
+    Csa(CsaError),
+    Usi(UsiError),
+    Sfen(SfenLoadError),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::UnsupportedFormat(path) => {
+                write!(f, "unsupported record format: {}", path.display())
+            }
+            Self::Kif(err) => write!(f, "KIF {err}"),
+            Self::Csa(err) => write!(f, "CSA {err}"),
+            Self::Usi(err) => write!(f, "USI {err}"),
+            Self::Sfen(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<KifError> for RecordError {
+    fn from(err: KifError) -> Self {
+        Self::Kif(err)
+    }
+}
+
+impl From<CsaError> for RecordError {
+    fn from(err: CsaError) -> Self {
+        Self::Csa(err)
+    }
+}
+
+impl From<UsiError> for RecordError {
+    fn from(err: UsiError) -> Self {
+        Self::Usi(err)
+    }
+}
+
+impl From<SfenLoadError> for RecordError {
+    fn from(err: SfenLoadError) -> Self {
+        Self::Sfen(err)
+    }
+}
+
+pub fn read_record(path: &Path) -> Result<GameState, RecordError> {
+    let format = RecordFormat::from_path(path)
+        .ok_or_else(|| RecordError::UnsupportedFormat(path.to_path_buf()))?;
+    parse_record(&decode_text(&fs::read(path)?), format)
+}
+
+pub fn parse_record(text: &str, format: RecordFormat) -> Result<GameState, RecordError> {
+    match format {
+        RecordFormat::Kif => Ok(kif::parse(text)?),
+        RecordFormat::Csa => Ok(csa::parse(text)?),
+        RecordFormat::Usi => Ok(usi::parse_position(text.trim())?),
+        RecordFormat::Sfen => Ok(GameState::from_sfen(text)?),
+    }
+}
+
+pub fn write_record(game: &GameState, path: &Path) -> Result<(), RecordError> {
+    let format = RecordFormat::from_path(path)
+        .ok_or_else(|| RecordError::UnsupportedFormat(path.to_path_buf()))?;
+    let text = format_record(game, format);
+    let bytes = if extension(path).as_deref() == Some("kif") {
+        encoding_rs::SHIFT_JIS.encode(&text).0.into_owned()
+    } else {
+        text.into_bytes()
+    };
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+pub fn format_record(game: &GameState, format: RecordFormat) -> String {
+    match format {
+        RecordFormat::Kif => kif::write(game),
+        RecordFormat::Csa => csa::write(game),
+        RecordFormat::Usi => usi::position_command_at(game, game.history().len()) + "\n",
+        RecordFormat::Sfen => game.to_sfen() + "\n",
+    }
+}
+
+/// Decodes record bytes, falling back to Shift_JIS for files that are not UTF-8.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::SHIFT_JIS.decode(bytes).0.into_owned(),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
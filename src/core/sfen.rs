@@ -0,0 +1,469 @@
+use std::fmt;
+
+use shogi::{Color, Piece, PieceType, Position, Square};
+
+use super::game::{
+    SfenLoadError, color_name, normalize_sfen, piece_type_label, unpromoted_piece_type,
+};
+
+/// Hand pieces in the order SFEN and most record formats list them.
+pub const HAND_PIECE_TYPES: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+/// Number of pieces of each unpromoted type in a full set, kings aside.
+pub const PIECE_SET: [(PieceType, u8); 7] = [
+    (PieceType::Rook, 2),
+    (PieceType::Bishop, 2),
+    (PieceType::Gold, 4),
+    (PieceType::Silver, 4),
+    (PieceType::Knight, 4),
+    (PieceType::Lance, 4),
+    (PieceType::Pawn, 18),
+];
+
+const BOARD_SIZE: u8 = 9;
+
+/// Why a [`BoardSetup`] cannot start a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupError {
+    /// Gote has no king, or a side has more than one. Sente may go without
+    /// one, as the attacker in a mate problem does.
+    KingCount { color: Color, count: usize },
+    /// More pieces of a kind than a set holds, counting promoted ones and hands.
+    TooMany {
+        piece_type: PieceType,
+        count: u8,
+        max: u8,
+    },
+    /// A pawn, lance or knight on a rank it could never move from.
+    DeadPiece { square: Square, piece: Piece },
+    /// Two unpromoted pawns of one side on the same file (nifu).
+    DoublePawn { color: Color, file: u8 },
+    /// The side that just moved has left its king in check.
+    OpponentInCheck,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KingCount { color, count } => {
+                write!(f, "{} has {count} kings", color_name(*color))
+            }
+            Self::TooMany {
+                piece_type,
+                count,
+                max,
+            } => {
+                let name = piece_type_label(*piece_type);
+                write!(f, "{name}: {count} pieces, but a set holds {max}")
+            }
+            Self::DeadPiece { square, piece } => write!(
+                f,
+                "{} {} on {}{} can never move",
+                color_name(piece.color),
+                piece_type_label(piece.piece_type),
+                square.file() + 1,
+                square.rank() + 1
+            ),
+            Self::DoublePawn { color, file } => {
+                write!(
+                    f,
+                    "{} has two pawns on file {}",
+                    color_name(*color),
+                    file + 1
+                )
+            }
+            Self::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+/// A freely editable position: board, hands and side to move.
+///
+/// Unlike `shogi::Position` this does not enforce any rule, so it can hold
+/// intermediate states while a record is being read or a position is being set up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardSetup {
+    squares: [Option<Piece>; 81],
+    hands: [[u8; 7]; 2],
+    side_to_move: Color,
+    move_number: u16,
+}
+
+#[allow(dead_code)]
+impl BoardSetup {
+    pub fn empty() -> Self {
+        Self {
+            squares: [None; 81],
+            hands: [[0; 7]; 2],
+            side_to_move: Color::Black,
+            move_number: 1,
+        }
+    }
+
+    pub fn from_position(pos: &Position) -> Self {
+        let mut setup = Self::empty();
+        for sq in squares() {
+            setup.set_piece(sq, *pos.piece_at(sq));
+        }
+        for color in [Color::Black, Color::White] {
+            for piece_type in HAND_PIECE_TYPES {
+                setup.set_hand(color, piece_type, pos.hand(Piece { piece_type, color }));
+            }
+        }
+        setup.side_to_move = pos.side_to_move();
+        setup.move_number = pos.ply();
+        setup
+    }
+
+    pub fn from_sfen(sfen: &str) -> Result<Self, SfenLoadError> {
+        let sfen = normalize_sfen(sfen)?;
+        let mut pos = Position::new();
+        if let Err(reason) = pos.set_sfen(&sfen) {
+            return Err(SfenLoadError::Invalid { sfen, reason });
+        }
+        Ok(Self::from_position(&pos))
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.squares[square_index(sq)]
+    }
+
+    pub fn set_piece(&mut self, sq: Square, piece: Option<Piece>) {
+        self.squares[square_index(sq)] = piece;
+    }
+
+    pub fn hand(&self, color: Color, piece_type: PieceType) -> u8 {
+        hand_index(piece_type).map_or(0, |idx| self.hands[color as usize][idx])
+    }
+
+    pub fn set_hand(&mut self, color: Color, piece_type: PieceType, count: u8) {
+        if let Some(idx) = hand_index(piece_type) {
+            self.hands[color as usize][idx] = count;
+        }
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.side_to_move = color;
+    }
+
+    pub fn move_number(&self) -> u16 {
+        self.move_number
+    }
+
+    pub fn set_move_number(&mut self, move_number: u16) {
+        self.move_number = move_number.max(1);
+    }
+
+    /// Pieces of the unpromoted type `piece_type` on the board, promoted or
+    /// not, and in both hands.
+    pub fn count(&self, piece_type: PieceType) -> u8 {
+        let on_board = squares()
+            .filter_map(|sq| self.piece_at(sq))
+            .filter(|piece| unpromoted_piece_type(piece.piece_type) == piece_type)
+            .count() as u8;
+        on_board + self.hand(Color::Black, piece_type) + self.hand(Color::White, piece_type)
+    }
+
+    /// Hands every piece not on the board or in a hand to `color`.
+    pub fn give_remaining_pieces(&mut self, color: Color) {
+        for (piece_type, total) in PIECE_SET {
+            let remaining = total.saturating_sub(self.count(piece_type));
+            let count = self.hand(color, piece_type);
+            self.set_hand(color, piece_type, count + remaining);
+        }
+    }
+
+    /// Checks that the position could start a game: kings, piece counts,
+    /// pieces that can never move, nifu and a king left in check.
+    pub fn validate(&self) -> Result<(), SetupError> {
+        for color in [Color::White, Color::Black] {
+            let count = squares()
+                .filter(|&sq| {
+                    self.piece_at(sq)
+                        == Some(Piece {
+                            piece_type: PieceType::King,
+                            color,
+                        })
+                })
+                .count();
+            if count > 1 || (count == 0 && color == Color::White) {
+                return Err(SetupError::KingCount { color, count });
+            }
+        }
+
+        for (piece_type, max) in PIECE_SET {
+            let count = self.count(piece_type);
+            if count > max {
+                return Err(SetupError::TooMany {
+                    piece_type,
+                    count,
+                    max,
+                });
+            }
+        }
+
+        for square in squares() {
+            let Some(piece) = self.piece_at(square) else {
+                continue;
+            };
+            // Ranks left in front of the piece.
+            let ahead = match piece.color {
+                Color::Black => square.rank(),
+                Color::White => BOARD_SIZE - 1 - square.rank(),
+            };
+            let dead = match piece.piece_type {
+                PieceType::Pawn | PieceType::Lance => ahead == 0,
+                PieceType::Knight => ahead < 2,
+                _ => false,
+            };
+            if dead {
+                return Err(SetupError::DeadPiece { square, piece });
+            }
+        }
+
+        for color in [Color::Black, Color::White] {
+            let pawn = Some(Piece {
+                piece_type: PieceType::Pawn,
+                color,
+            });
+            for file in 0..BOARD_SIZE {
+                let pawns = (0..BOARD_SIZE)
+                    .filter(|&rank| {
+                        let sq = Square::new(file, rank).expect("valid board coordinate");
+                        self.piece_at(sq) == pawn
+                    })
+                    .count();
+                if pawns > 1 {
+                    return Err(SetupError::DoublePawn { color, file });
+                }
+            }
+        }
+
+        let mut pos = Position::new();
+        if pos.set_sfen(&self.to_sfen()).is_ok() && pos.in_check(self.side_to_move.flip()) {
+            return Err(SetupError::OpponentInCheck);
+        }
+        Ok(())
+    }
+
+    pub fn to_sfen(&self) -> String {
+        let mut out = String::new();
+        for rank in 0..BOARD_SIZE {
+            if rank > 0 {
+                out.push('/');
+            }
+            let mut empty = 0;
+            for file in (0..BOARD_SIZE).rev() {
+                let sq = Square::new(file, rank).expect("valid board coordinate");
+                match self.piece_at(sq) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            out.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        out.push_str(&sfen_piece(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                out.push_str(&empty.to_string());
+            }
+        }
+
+        out.push(' ');
+        out.push(if self.side_to_move == Color::Black {
+            'b'
+        } else {
+            'w'
+        });
+
+        out.push(' ');
+        let mut hand = String::new();
+        for color in [Color::Black, Color::White] {
+            for piece_type in HAND_PIECE_TYPES {
+                let count = self.hand(color, piece_type);
+                if count == 0 {
+                    continue;
+                }
+                if count > 1 {
+                    hand.push_str(&count.to_string());
+                }
+                hand.push_str(&sfen_piece(Piece { piece_type, color }));
+            }
+        }
+        if hand.is_empty() {
+            hand.push('-');
+        }
+        out.push_str(&hand);
+
+        out.push(' ');
+        out.push_str(&self.move_number.to_string());
+        out
+    }
+}
+
+impl Default for BoardSetup {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// All 81 squares, file by file.
+pub fn squares() -> impl Iterator<Item = Square> {
+    (0..BOARD_SIZE).flat_map(|file| {
+        (0..BOARD_SIZE).map(move |rank| Square::new(file, rank).expect("valid board coordinate"))
+    })
+}
+
+/// SFEN letter for `piece`, e.g. `P`, `+r`.
+pub fn sfen_piece(piece: Piece) -> String {
+    let base = unpromoted_piece_type(piece.piece_type);
+    let letter = match base {
+        PieceType::King => 'K',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Gold => 'G',
+        PieceType::Silver => 'S',
+        PieceType::Knight => 'N',
+        PieceType::Lance => 'L',
+        _ => 'P',
+    };
+    let letter = if piece.color == Color::Black {
+        letter
+    } else {
+        letter.to_ascii_lowercase()
+    };
+    if base != piece.piece_type {
+        format!("+{letter}")
+    } else {
+        letter.to_string()
+    }
+}
+
+fn square_index(sq: Square) -> usize {
+    sq.file() as usize * BOARD_SIZE as usize + sq.rank() as usize
+}
+
+fn hand_index(piece_type: PieceType) -> Option<usize> {
+    HAND_PIECE_TYPES.iter().position(|&pt| pt == piece_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::game::START_SFEN;
+
+    fn setup(sfen: &str) -> BoardSetup {
+        crate::core::init();
+        BoardSetup::from_sfen(sfen).expect("valid test SFEN")
+    }
+
+    #[test]
+    fn from_sfen_normalizes_keyword_and_move_number() {
+        assert_eq!(setup(START_SFEN).to_sfen(), START_SFEN);
+        let short = START_SFEN.trim_end_matches(" 1");
+        assert_eq!(setup(&format!("sfen {short}")).to_sfen(), START_SFEN);
+
+        let hands = setup("4k4/9/9/9/9/9/9/9/4K4 w 2Pr3p 12");
+        assert_eq!(hands.hand(Color::Black, PieceType::Pawn), 2);
+        assert_eq!(hands.hand(Color::White, PieceType::Rook), 1);
+        assert_eq!(hands.hand(Color::White, PieceType::Pawn), 3);
+        assert_eq!(hands.side_to_move(), Color::White);
+        assert_eq!(hands.to_sfen(), "4k4/9/9/9/9/9/9/9/4K4 w 2Pr3p 12");
+    }
+
+    #[test]
+    fn from_sfen_errors() {
+        crate::core::init();
+        assert!(matches!(
+            BoardSetup::from_sfen("  "),
+            Err(SfenLoadError::Empty)
+        ));
+        assert!(matches!(
+            BoardSetup::from_sfen("sfen 4k4/9/9/9/9/9/9/9/4X4 b - 1"),
+            Err(SfenLoadError::Invalid { .. })
+        ));
+        assert!(matches!(
+            BoardSetup::from_sfen(&format!("{START_SFEN} moves 7g7f")),
+            Err(SfenLoadError::UnexpectedMoves)
+        ));
+    }
+
+    #[test]
+    fn validate_accepts_the_start_and_a_mate_problem() {
+        assert_eq!(setup(START_SFEN).validate(), Ok(()));
+        // The attacker of a mate problem has no king.
+        assert_eq!(setup("4k4/9/4P4/9/9/9/9/9/9 b G 1").validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_errors() {
+        let error = |sfen: &str| setup(sfen).validate().unwrap_err();
+        assert_eq!(
+            error("9/9/9/9/9/9/9/9/4K4 b - 1"),
+            SetupError::KingCount {
+                color: Color::White,
+                count: 0,
+            }
+        );
+        assert_eq!(
+            error("4k4/9/9/9/9/9/9/9/3KRK3 b 2R 1"),
+            SetupError::KingCount {
+                color: Color::Black,
+                count: 2,
+            }
+        );
+        assert_eq!(
+            error("4k4/9/9/9/9/9/9/9/4K4 b 3R 1"),
+            SetupError::TooMany {
+                piece_type: PieceType::Rook,
+                count: 3,
+                max: 2,
+            }
+        );
+        assert!(matches!(
+            error("P3k4/9/9/9/9/9/9/9/4K4 b - 1"),
+            SetupError::DeadPiece { .. }
+        ));
+        assert!(matches!(
+            error("4k4/9/9/9/9/9/9/n8/4K4 b - 1"),
+            SetupError::DeadPiece { .. }
+        ));
+        assert_eq!(
+            error("4k4/9/9/9/9/P8/P8/9/4K4 b - 1"),
+            SetupError::DoublePawn {
+                color: Color::Black,
+                file: 8,
+            }
+        );
+        assert_eq!(
+            error("4k4/9/9/9/4R4/9/9/9/4K4 b - 1"),
+            SetupError::OpponentInCheck
+        );
+    }
+
+    #[test]
+    fn remaining_pieces_go_to_hand() {
+        let mut board = setup("4k4/9/4P4/9/9/9/9/9/9 b G 1");
+        board.give_remaining_pieces(Color::White);
+        assert_eq!(board.hand(Color::White, PieceType::Pawn), 17);
+        assert_eq!(board.hand(Color::White, PieceType::Gold), 3);
+        assert_eq!(board.hand(Color::White, PieceType::Rook), 2);
+        assert_eq!(board.validate(), Ok(()));
+    }
+}
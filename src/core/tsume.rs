@@ -0,0 +1,585 @@
+//! Tsume (mate problem) solver.
+//!
+//! The side to move attacks and may only play checks; the defender may play
+//! any legal move. Mate is proven with df-pn, a depth-first proof-number
+//! search, and the proven tree is then measured to find the shortest mate
+//! against the longest defence. Returning to a position already on the line
+//! never helps the attacker, so the search treats it as a failed line. Such a
+//! failure holds only for that line: a result that relies on one is cached
+//! only while the position it returned to is still on the line searched.
+
+use std::collections::HashMap;
+
+use shogi::{Color, Move, Position};
+
+use super::game::SfenLoadError;
+use super::movegen::Board;
+use super::sfen::BoardSetup;
+
+/// Proof or disproof number of a settled position.
+const INF: u32 = u32::MAX / 4;
+
+const UNKNOWN: Numbers = Numbers {
+    proof: 1,
+    disproof: 1,
+};
+const PROVEN: Numbers = Numbers {
+    proof: 0,
+    disproof: INF,
+};
+const DISPROVEN: Numbers = Numbers {
+    proof: INF,
+    disproof: 0,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct TsumeOptions {
+    /// Positions expanded before the solver gives up, once for proving mate
+    /// and once more for measuring it.
+    pub max_nodes: u64,
+    /// Hands the defender every piece not on the board or in a hand, as
+    /// problems are conventionally set.
+    pub remaining_to_defender: bool,
+}
+
+impl Default for TsumeOptions {
+    fn default() -> Self {
+        Self {
+            max_nodes: 200_000,
+            remaining_to_defender: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TsumeResult {
+    Mate(TsumeSolution),
+    /// Every sequence of checks can be escaped.
+    NoMate,
+    /// The node limit ran out first.
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TsumeSolution {
+    /// Position the solution starts from, after any remaining pieces were
+    /// handed to the defender.
+    pub start_sfen: String,
+    /// The attacker's moves and the longest defence, ending in mate. Among
+    /// equally long defences, moves on the board are preferred over drops.
+    pub moves: Vec<Move>,
+    /// Other attacker moves that mate within the moves left (余詰). An
+    /// alternative mating move at the very end is tolerated and not listed.
+    pub alternatives: Vec<Alternative>,
+    /// Positions expanded.
+    pub nodes: u64,
+}
+
+/// An attacker move that could replace `moves[ply]` and still mate in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alternative {
+    pub ply: usize,
+    pub mv: Move,
+}
+
+impl TsumeSolution {
+    /// Plies to mate, e.g. 7 for a seven-move problem.
+    pub fn length(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.alternatives.is_empty()
+    }
+}
+
+/// Solves the problem at `pos`, with its side to move as the attacker.
+pub fn solve(pos: &Position, options: &TsumeOptions) -> TsumeResult {
+    let attacker = pos.side_to_move();
+    let mut setup = BoardSetup::from_position(pos);
+    if options.remaining_to_defender {
+        setup.give_remaining_pieces(attacker.flip());
+    }
+    setup.set_move_number(1);
+    let start_sfen = setup.to_sfen();
+    let root = Board::from_setup(&setup);
+
+    let mut solver = Solver::new(attacker, options.max_nodes);
+    solver.mid(&root, true, INF, INF);
+    let numbers = solver.numbers(&root);
+    if numbers.disproof == 0 {
+        return TsumeResult::NoMate;
+    }
+    if numbers.proof != 0 {
+        return TsumeResult::Unknown;
+    }
+    solver.max_nodes = solver.nodes + options.max_nodes;
+    match solver.solution(&root) {
+        Some((moves, alternatives)) => TsumeResult::Mate(TsumeSolution {
+            start_sfen,
+            moves,
+            alternatives,
+            nodes: solver.nodes,
+        }),
+        None => TsumeResult::Unknown,
+    }
+}
+
+/// [`solve`] for a position given as SFEN.
+pub fn solve_sfen(sfen: &str, options: &TsumeOptions) -> Result<TsumeResult, SfenLoadError> {
+    let setup = BoardSetup::from_sfen(sfen)?;
+    let mut pos = Position::new();
+    let sfen = setup.to_sfen();
+    if let Err(reason) = pos.set_sfen(&sfen) {
+        return Err(SfenLoadError::Invalid { sfen, reason });
+    }
+    Ok(solve(&pos, options))
+}
+
+/// df-pn proof and disproof numbers: roughly how many positions must still be
+/// searched to prove mate, or to prove there is none.
+#[derive(Clone, Copy, Debug)]
+struct Numbers {
+    proof: u32,
+    disproof: u32,
+}
+
+/// Cached numbers of a position.
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    numbers: Numbers,
+    /// For a disproof that relied on returning to a position on the line,
+    /// the deepest such position. The entry is void once it leaves the line.
+    loop_to: Option<PathMark>,
+}
+
+/// A position's place on the line being searched. Stamps are never reused,
+/// so a mark names one visit to the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct PathMark {
+    depth: usize,
+    stamp: u64,
+}
+
+/// Plies known to be enough, and known not to be enough, to mate.
+#[derive(Clone, Copy, Debug, Default)]
+struct Bounds {
+    mate: Option<usize>,
+    no_mate: Option<usize>,
+}
+
+struct Solver {
+    attacker: Color,
+    numbers: HashMap<Board, Entry>,
+    /// Results of [`Solver::mates_within`]; only those that hold whatever
+    /// the line leading to the position.
+    bounds: HashMap<Board, Bounds>,
+    /// Positions on the line being searched.
+    path: HashMap<Board, PathMark>,
+    /// Stamps of the positions on the line, by depth.
+    stamps: Vec<u64>,
+    next_stamp: u64,
+    nodes: u64,
+    max_nodes: u64,
+}
+
+impl Solver {
+    fn new(attacker: Color, max_nodes: u64) -> Self {
+        Self {
+            attacker,
+            numbers: HashMap::new(),
+            bounds: HashMap::new(),
+            path: HashMap::new(),
+            stamps: Vec::new(),
+            next_stamp: 0,
+            nodes: 0,
+            max_nodes,
+        }
+    }
+
+    fn out_of_nodes(&self) -> bool {
+        self.nodes >= self.max_nodes
+    }
+
+    /// Legal moves from `key` with the positions they lead to; only checks
+    /// when the attacker is to move.
+    fn children(&mut self, key: &Board) -> Vec<(Move, Board)> {
+        self.nodes += 1;
+        let attacking = key.side_to_move() == self.attacker;
+        let defender = self.attacker.flip();
+        key.legal_moves()
+            .all()
+            .iter()
+            .filter_map(|&mv| {
+                let mut child = key.clone();
+                child.play(mv);
+                (!attacking || child.in_check(defender)).then_some((mv, child))
+            })
+            .collect()
+    }
+
+    /// Puts `key` at the end of the line being searched.
+    fn enter(&mut self, key: &Board) -> PathMark {
+        let mark = PathMark {
+            depth: self.stamps.len(),
+            stamp: self.next_stamp,
+        };
+        self.next_stamp += 1;
+        self.stamps.push(mark.stamp);
+        self.path.insert(key.clone(), mark);
+        mark
+    }
+
+    fn leave(&mut self, key: &Board) {
+        self.path.remove(key);
+        self.stamps.pop();
+    }
+
+    fn on_path(&self, mark: PathMark) -> bool {
+        self.stamps.get(mark.depth) == Some(&mark.stamp)
+    }
+
+    fn numbers(&self, key: &Board) -> Numbers {
+        self.lookup(key).0
+    }
+
+    /// Numbers of `key` on the current line, with the position on the line a
+    /// disproof relies on.
+    fn lookup(&self, key: &Board) -> (Numbers, Option<PathMark>) {
+        if let Some(&mark) = self.path.get(key) {
+            return (DISPROVEN, Some(mark));
+        }
+        match self.numbers.get(key) {
+            Some(entry) if entry.loop_to.is_none_or(|mark| self.on_path(mark)) => {
+                (entry.numbers, entry.loop_to)
+            }
+            _ => (UNKNOWN, None),
+        }
+    }
+
+    /// Searches `key` until its numbers reach either threshold.
+    fn mid(&mut self, key: &Board, attacker_to_move: bool, max_proof: u32, max_disproof: u32) {
+        let children = self.children(key);
+        if children.is_empty() {
+            let numbers = if attacker_to_move { DISPROVEN } else { PROVEN };
+            let entry = Entry {
+                numbers,
+                loop_to: None,
+            };
+            self.numbers.insert(key.clone(), entry);
+            return;
+        }
+        let mark = self.enter(key);
+        loop {
+            let (numbers, best, second, loop_to) = self.combine(&children, attacker_to_move);
+            if numbers.proof >= max_proof || numbers.disproof >= max_disproof || self.out_of_nodes()
+            {
+                // Returning to `key` itself is a failure on any line.
+                let loop_to = loop_to.filter(|loop_to| loop_to.depth < mark.depth);
+                let entry = Entry { numbers, loop_to };
+                self.numbers.insert(key.clone(), entry);
+                break;
+            }
+            let child = self.numbers(&children[best].1);
+            let (proof, disproof) = if attacker_to_move {
+                (
+                    max_proof.min(second.saturating_add(1)).min(INF),
+                    (max_disproof - numbers.disproof + child.disproof).min(INF),
+                )
+            } else {
+                (
+                    (max_proof - numbers.proof + child.proof).min(INF),
+                    max_disproof.min(second.saturating_add(1)).min(INF),
+                )
+            };
+            self.mid(&children[best].1, !attacker_to_move, proof, disproof);
+        }
+        self.leave(key);
+    }
+
+    /// Numbers of a position from its children's, with the child to search
+    /// next, the runner-up value that bounds how long it is searched, and for
+    /// a disproof the position on the line it relies on.
+    fn combine(
+        &self,
+        children: &[(Move, Board)],
+        attacker_to_move: bool,
+    ) -> (Numbers, usize, u32, Option<PathMark>) {
+        let mut best = 0;
+        let mut best_value = INF;
+        let mut second = INF;
+        let mut sum = 0;
+        // Every disproven child when the attacker moves, the least dependent
+        // one when the defender does.
+        let mut loop_to = None;
+        let mut escape: Option<Option<PathMark>> = None;
+        for (index, (_, child)) in children.iter().enumerate() {
+            let (numbers, child_loop_to) = self.lookup(child);
+            if numbers.disproof == 0 {
+                loop_to = loop_to.max(child_loop_to);
+                escape = Some(escape.map_or(child_loop_to, |escape| escape.min(child_loop_to)));
+            }
+            // The attacker needs one proven child, the defender one disproven.
+            let (select, add) = if attacker_to_move {
+                (numbers.proof, numbers.disproof)
+            } else {
+                (numbers.disproof, numbers.proof)
+            };
+            if select < best_value {
+                second = best_value;
+                best_value = select;
+                best = index;
+            } else if select < second {
+                second = select;
+            }
+            sum = (sum + add).min(INF);
+        }
+        let numbers = if attacker_to_move {
+            Numbers {
+                proof: best_value,
+                disproof: sum,
+            }
+        } else {
+            Numbers {
+                proof: sum,
+                disproof: best_value,
+            }
+        };
+        let loop_to = match numbers.disproof {
+            0 if attacker_to_move => loop_to,
+            0 => escape.flatten(),
+            _ => None,
+        };
+        (numbers, best, second, loop_to)
+    }
+
+    /// Whether the attacker mates from `key` within `plies`.
+    fn mates_within(&mut self, key: &Board, attacker_to_move: bool, plies: usize) -> bool {
+        self.search_within(key, attacker_to_move, plies).0
+    }
+
+    /// [`Solver::mates_within`], with the position on the line a "no"
+    /// relies on.
+    fn search_within(
+        &mut self,
+        key: &Board,
+        attacker_to_move: bool,
+        plies: usize,
+    ) -> (bool, Option<PathMark>) {
+        if let Some(bounds) = self.bounds.get(key) {
+            if bounds.mate.is_some_and(|mate| mate <= plies) {
+                return (true, None);
+            }
+            if bounds.no_mate.is_some_and(|no_mate| plies <= no_mate) {
+                return (false, None);
+            }
+        }
+        if let Some(&mark) = self.path.get(key) {
+            return (false, Some(mark));
+        }
+        if self.out_of_nodes() || (attacker_to_move && plies == 0) {
+            return (false, None);
+        }
+
+        let mut children = self.children(key);
+        // Likely mates first for the attacker, likely escapes for the defender.
+        children.sort_by_key(|(_, child)| {
+            let numbers = self.numbers(child);
+            if attacker_to_move {
+                numbers.proof
+            } else {
+                numbers.disproof
+            }
+        });
+        let mark = self.enter(key);
+        // The attacker fails only if every check fails, the defender escapes
+        // with any one move.
+        let mut loop_to = None;
+        let mates = if attacker_to_move {
+            let mut mates = false;
+            for (_, child) in &children {
+                let (child_mates, child_loop_to) = self.search_within(child, false, plies - 1);
+                if child_mates {
+                    mates = true;
+                    break;
+                }
+                loop_to = loop_to.max(child_loop_to);
+            }
+            mates
+        } else if children.is_empty() {
+            true
+        } else if plies == 0 {
+            false
+        } else {
+            let mut mates = true;
+            for (_, child) in &children {
+                let (child_mates, child_loop_to) = self.search_within(child, true, plies - 1);
+                if !child_mates {
+                    mates = false;
+                    loop_to = child_loop_to;
+                    break;
+                }
+            }
+            mates
+        };
+        self.leave(key);
+
+        let loop_to = loop_to.filter(|loop_to| !mates && loop_to.depth < mark.depth);
+        if !mates && (loop_to.is_some() || self.out_of_nodes()) {
+            return (false, loop_to);
+        }
+        let bounds = self.bounds.entry(key.clone()).or_default();
+        if mates {
+            bounds.mate = Some(bounds.mate.map_or(plies, |mate| mate.min(plies)));
+        } else {
+            bounds.no_mate = Some(bounds.no_mate.map_or(plies, |no_mate| no_mate.max(plies)));
+        }
+        (mates, None)
+    }
+
+    /// Plies to mate from the attacker's position `key` against the best
+    /// defence.
+    fn mate_length(&mut self, key: &Board) -> Option<usize> {
+        let mut plies = 1;
+        while !self.out_of_nodes() {
+            if self.mates_within(key, true, plies) {
+                return Some(plies);
+            }
+            plies += 2;
+        }
+        None
+    }
+
+    /// The shortest mate against the longest defence, and the attacker's
+    /// alternatives along it.
+    fn solution(&mut self, root: &Board) -> Option<(Vec<Move>, Vec<Alternative>)> {
+        let mut plies = self.mate_length(root)?;
+        let mut key = root.clone();
+        let mut moves = Vec::new();
+        let mut alternatives = Vec::new();
+        let mut attacker_to_move = true;
+        while plies > 0 {
+            let mut children = self.children(&key);
+            let next = if attacker_to_move {
+                let mating: Vec<(Move, Board)> = children
+                    .into_iter()
+                    .filter(|(_, child)| self.mates_within(child, false, plies - 1))
+                    .collect();
+                let mut mating = mating.into_iter();
+                let first = mating.next()?;
+                if plies > 1 {
+                    alternatives.extend(mating.map(|(mv, _)| Alternative {
+                        ply: moves.len(),
+                        mv,
+                    }));
+                }
+                first
+            } else {
+                // Every defence mates within `plies - 1`; the longest is one
+                // the attacker cannot finish two plies sooner.
+                children.sort_by_key(|(mv, _)| matches!(mv, Move::Drop { .. }));
+                children
+                    .into_iter()
+                    .find(|(_, child)| plies < 3 || !self.mates_within(child, true, plies - 3))?
+            };
+            self.enter(&key);
+            moves.push(next.0);
+            key = next.1;
+            plies -= 1;
+            attacker_to_move = !attacker_to_move;
+        }
+        self.path.clear();
+        self.stamps.clear();
+        if self.out_of_nodes() {
+            return None;
+        }
+        Some((moves, alternatives))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usi::move_to_usi;
+
+    fn solve(sfen: &str, remaining_to_defender: bool) -> TsumeResult {
+        crate::core::init();
+        let options = TsumeOptions {
+            remaining_to_defender,
+            ..TsumeOptions::default()
+        };
+        solve_sfen(sfen, &options).expect("valid test SFEN")
+    }
+
+    fn mate(sfen: &str) -> TsumeSolution {
+        match solve(sfen, true) {
+            TsumeResult::Mate(solution) => solution,
+            other => panic!("{sfen}: expected mate, got {other:?}"),
+        }
+    }
+
+    fn usi(moves: &[Move]) -> Vec<String> {
+        moves.iter().map(|&mv| move_to_usi(mv)).collect()
+    }
+
+    #[test]
+    fn one_move_mate() {
+        let solution = mate("4k4/9/4P4/9/9/9/9/9/9 b G 1");
+        assert_eq!(usi(&solution.moves), ["G*5b"]);
+        assert!(solution.is_unique());
+    }
+
+    #[test]
+    fn three_move_mate() {
+        // The king runs to the corner rather than to 2b, where the gold
+        // mates it just the same.
+        let solution = mate("7kl/9/6P2/9/9/9/9/9/9 b GS 1");
+        assert_eq!(usi(&solution.moves), ["S*3b", "2a1b", "G*2c"]);
+        assert!(solution.is_unique());
+    }
+
+    #[test]
+    fn five_move_mate() {
+        // Dropping an interposing piece on 2c only loses it to the bishop.
+        let solution = mate("8k/7P1/9/7P1/9/9/9/9/9 b BG 1");
+        assert_eq!(
+            usi(&solution.moves),
+            ["G*2a", "1a1b", "B*3d", "1b1c", "3d2c+"]
+        );
+        assert!(solution.is_unique());
+    }
+
+    #[test]
+    fn alternative_mates_are_listed() {
+        // Symmetric: the silver mates just as well from either side.
+        let solution = mate("6k2/9/5P1P1/9/9/9/9/9/9 b GS 1");
+        assert_eq!(
+            usi(&solution.moves),
+            ["S*2b", "3a3b", "G*3c", "3b4a", "3c4b"]
+        );
+        let alternatives: Vec<_> = solution
+            .alternatives
+            .iter()
+            .map(|alt| (alt.ply, move_to_usi(alt.mv)))
+            .collect();
+        assert_eq!(alternatives, [(0, "S*4b".to_string())]);
+        assert!(!solution.is_unique());
+    }
+
+    #[test]
+    fn no_mate() {
+        assert_eq!(
+            solve("4k4/9/9/9/9/9/9/9/9 b G 1", true),
+            TsumeResult::NoMate
+        );
+    }
+
+    #[test]
+    fn defender_escapes_by_repetition() {
+        // The rook can check forever, so every line ends in a repetition.
+        assert_eq!(
+            solve("8k/9/8P/9/9/9/9/9/R8 b - 1", false),
+            TsumeResult::NoMate
+        );
+    }
+}
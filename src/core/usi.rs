@@ -0,0 +1,293 @@
+use std::fmt;
+
+use shogi::{Color, Move, PieceType, Square};
+
+use super::game::{GameState, START_SFEN, position_key};
+use super::notation::japanese_move;
+
+#[derive(Debug)]
+pub enum UsiError {
+    Empty,
+    Syntax(String),
+    InvalidPosition(String),
+    IllegalMove { ply: usize, text: String },
+}
+
+impl fmt::Display for UsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty USI position"),
+            Self::Syntax(message) => write!(f, "{message}"),
+            Self::InvalidPosition(message) => write!(f, "invalid start position: {message}"),
+            Self::IllegalMove { ply, text } => write!(f, "illegal move {ply} `{text}`"),
+        }
+    }
+}
+
+impl std::error::Error for UsiError {}
+
+/// Builds `position startpos moves ...` (or `position sfen ...`) for the
+/// moves leading to the displayed ply of `game`.
+pub fn position_command(game: &GameState) -> String {
+    position_command_at(game, game.current_ply())
+}
+
+/// [`position_command`] for the first `ply` moves of the displayed line.
+pub fn position_command_at(game: &GameState, ply: usize) -> String {
+    let start = game.start_sfen();
+    let mut out = if position_key(start) == position_key(START_SFEN) {
+        "position startpos".to_string()
+    } else {
+        format!("position sfen {start}")
+    };
+    let moves = &game.history()[..ply.min(game.history().len())];
+    if !moves.is_empty() {
+        out.push_str(" moves");
+        for entry in moves {
+            out.push(' ');
+            out.push_str(&move_to_usi(entry.mv));
+        }
+    }
+    out
+}
+
+/// Reads a `position` line into a game positioned after its last move.
+///
+/// The leading `position` keyword is optional, so `startpos moves 7g7f` and
+/// `sfen ... moves ...` are accepted too.
+pub fn parse_position(text: &str) -> Result<GameState, UsiError> {
+    let mut tokens = text.split_whitespace().peekable();
+    if tokens.peek() == Some(&"position") {
+        tokens.next();
+    }
+    let sfen = match tokens.next() {
+        None => return Err(UsiError::Empty),
+        Some("startpos") => {
+            match tokens.next() {
+                None | Some("moves") => {}
+                Some(other) => {
+                    return Err(UsiError::Syntax(format!(
+                        "expected `moves`, found `{other}`"
+                    )));
+                }
+            }
+            START_SFEN.to_string()
+        }
+        // `take_while` also consumes the `moves` keyword.
+        Some("sfen") => tokens
+            .by_ref()
+            .take_while(|token| *token != "moves")
+            .collect::<Vec<_>>()
+            .join(" "),
+        Some(other) => {
+            return Err(UsiError::Syntax(format!(
+                "expected `startpos` or `sfen`, found `{other}`"
+            )));
+        }
+    };
+
+    let mut game =
+        GameState::from_sfen(&sfen).map_err(|err| UsiError::InvalidPosition(err.to_string()))?;
+    for (idx, token) in tokens.enumerate() {
+        let illegal = || UsiError::IllegalMove {
+            ply: idx + 1,
+            text: token.to_string(),
+        };
+        let mv = parse_usi_move(token).ok_or_else(illegal)?;
+        game.push_move(mv).map_err(|_| illegal())?;
+    }
+    Ok(game)
+}
+
+/// Japanese rendering of a principal variation played from `sfen`, e.g.
+/// `▲７六歩(77)`, `△３四歩(33)`. `last_to` is the destination of the move
+/// before `sfen`, so a recapture there reads `同`. Stops at the first move
+/// that does not parse or is not legal.
+pub fn japanese_pv(sfen: &str, last_to: Option<Square>, pv: &[String]) -> Vec<String> {
+    let Ok(mut game) = GameState::from_sfen(sfen) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    let mut prev_to = last_to;
+    for text in pv {
+        let mover = game.side_to_move();
+        let Some(mv) = parse_usi_move(text) else {
+            break;
+        };
+        if game.push_move(mv).is_err() {
+            break;
+        }
+        let Some(entry) = game.history().last() else {
+            break;
+        };
+        let mark = match mover {
+            Color::Black => '▲',
+            Color::White => '△',
+        };
+        out.push(format!("{mark}{}", japanese_move(entry, prev_to)));
+        prev_to = Some(entry.to());
+    }
+    out
+}
+
+/// USI spelling of `mv`, e.g. `7g7f`, `8h2b+`, `P*5e`.
+pub fn move_to_usi(mv: Move) -> String {
+    match mv {
+        Move::Normal { from, to, promote } => {
+            let suffix = if promote { "+" } else { "" };
+            format!("{}{}{suffix}", square_to_usi(from), square_to_usi(to))
+        }
+        Move::Drop { to, piece_type } => {
+            format!("{}*{}", drop_letter(piece_type), square_to_usi(to))
+        }
+    }
+}
+
+/// Parses a single USI move such as `7g7f`, `2b3c+` or `P*5e`.
+pub fn parse_usi_move(text: &str) -> Option<Move> {
+    let bytes = text.as_bytes();
+    if bytes.len() == 4 && bytes[1] == b'*' {
+        let piece_type = parse_drop_letter(bytes[0] as char)?;
+        let to = parse_usi_square(&text[2..4])?;
+        return Some(Move::Drop { to, piece_type });
+    }
+
+    let promote = match bytes.len() {
+        4 => false,
+        5 if bytes[4] == b'+' => true,
+        _ => return None,
+    };
+    let from = parse_usi_square(text.get(0..2)?)?;
+    let to = parse_usi_square(text.get(2..4)?)?;
+    Some(Move::Normal { from, to, promote })
+}
+
+pub fn square_to_usi(sq: Square) -> String {
+    format!("{}{}", sq.file() + 1, (b'a' + sq.rank()) as char)
+}
+
+pub fn parse_usi_square(text: &str) -> Option<Square> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].checked_sub(b'1')?;
+    let rank = bytes[1].checked_sub(b'a')?;
+    Square::new(file, rank)
+}
+
+fn drop_letter(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Gold => 'G',
+        PieceType::Silver => 'S',
+        PieceType::Knight => 'N',
+        PieceType::Lance => 'L',
+        _ => 'P',
+    }
+}
+
+fn parse_drop_letter(letter: char) -> Option<PieceType> {
+    match letter {
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'G' => Some(PieceType::Gold),
+        'S' => Some(PieceType::Silver),
+        'N' => Some(PieceType::Knight),
+        'L' => Some(PieceType::Lance),
+        'P' => Some(PieceType::Pawn),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> GameState {
+        crate::core::init();
+        parse_position(text).expect("valid test position")
+    }
+
+    #[test]
+    fn position_with_promotion_and_drop() {
+        let line = "position startpos moves 7g7f 3c3d 8h2b+ 3a2b B*4e";
+        let game = parse(line);
+        let history = game.history();
+        assert_eq!(history.len(), 5);
+        assert!(matches!(history[2].mv, Move::Normal { promote: true, .. }));
+        assert_eq!(
+            history[2].captured.map(|piece| piece.piece_type),
+            Some(PieceType::Bishop)
+        );
+        assert_eq!(
+            history[4].mv,
+            Move::Drop {
+                to: parse_usi_square("4e").unwrap(),
+                piece_type: PieceType::Bishop,
+            }
+        );
+        assert_eq!(position_command(&game), line);
+        assert_eq!(
+            position_command_at(&game, 1),
+            "position startpos moves 7g7f"
+        );
+    }
+
+    #[test]
+    fn sfen_position_without_keyword() {
+        let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 b G 1";
+        let game = parse(&format!("sfen {sfen} moves G*5b"));
+        assert_eq!(game.start_sfen(), sfen);
+        assert_eq!(
+            position_command(&game),
+            format!("position sfen {sfen} moves G*5b")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        crate::core::init();
+        assert!(matches!(parse_position(""), Err(UsiError::Empty)));
+        assert!(matches!(
+            parse_position("position start"),
+            Err(UsiError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse_position("position startpos 7g7f"),
+            Err(UsiError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse_position("position sfen 4k4/9/9/9/9/9/9/9/4X4 b - 1"),
+            Err(UsiError::InvalidPosition(_))
+        ));
+        match parse_position("position startpos moves 7g7f 3c3d 7f7d") {
+            Err(UsiError::IllegalMove { ply, text }) => {
+                assert_eq!((ply, text.as_str()), (3, "7f7d"));
+            }
+            _ => panic!("7f7d moves a pawn two squares"),
+        }
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        for text in ["7g7f", "8h2b+", "P*5e", "R*1a", "1i1a"] {
+            let mv = parse_usi_move(text).expect(text);
+            assert_eq!(move_to_usi(mv), text);
+        }
+        for text in ["", "7g", "7g7f=", "X*5e", "0a1b", "7g7j"] {
+            assert_eq!(parse_usi_move(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn japanese_pv_marks_recaptures() {
+        crate::core::init();
+        let pv = ["7g7f", "3c3d", "8h2b+", "3a2b", "9z9z"].map(String::from);
+        assert_eq!(
+            japanese_pv(START_SFEN, None, &pv),
+            ["▲７六歩(77)", "△３四歩(33)", "▲２二角成(88)", "△同　銀(31)"]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Branching game record.
+//!
+//! Every position may continue with several moves. The first continuation is
+//! the main line; the others are variations in the order they were added or
+//! promoted. A line ends at a node without continuations or at one that
+//! carries a result.
+
+use shogi::Move;
+
+use super::game::{GameResult, MoveEntry};
+
+pub type NodeId = usize;
+
+/// The start position, which has no move of its own.
+pub const ROOT: NodeId = 0;
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    entry: Option<MoveEntry>,
+    parent: Option<NodeId>,
+    /// Moves played from this position, main continuation first.
+    children: Vec<NodeId>,
+    /// How the game ended after this move.
+    result: Option<GameResult>,
+}
+
+/// Moves of a record as a tree rooted at the start position.
+///
+/// Removed nodes stay in the arena unreachable, so ids are never reused.
+#[derive(Clone, Debug)]
+pub struct VariationTree {
+    nodes: Vec<Node>,
+}
+
+impl Default for VariationTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl VariationTree {
+    /// Move played to reach `id`; panics for [`ROOT`].
+    pub fn entry(&self, id: NodeId) -> &MoveEntry {
+        self.nodes[id]
+            .entry
+            .as_ref()
+            .expect("only the root has no move")
+    }
+
+    pub(crate) fn entry_mut(&mut self, id: NodeId) -> &mut MoveEntry {
+        self.nodes[id]
+            .entry
+            .as_mut()
+            .expect("only the root has no move")
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    /// Continuations of `id`, main line first.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id].children
+    }
+
+    pub fn result(&self, id: NodeId) -> Option<GameResult> {
+        self.nodes[id].result
+    }
+
+    pub(crate) fn set_result(&mut self, id: NodeId, result: Option<GameResult>) {
+        self.nodes[id].result = result;
+    }
+
+    /// Continuation of `id` that plays `mv`, if it was recorded.
+    pub fn find_child(&self, id: NodeId, mv: Move) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .find(|&child| self.entry(child).mv == mv)
+    }
+
+    /// Records `entry` as the last continuation of `parent`.
+    pub(crate) fn add_child(&mut self, parent: NodeId, entry: MoveEntry) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            entry: Some(entry),
+            parent: Some(parent),
+            children: Vec::new(),
+            result: None,
+        });
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    /// Nodes that follow `id` along main continuations, excluding `id`.
+    pub fn main_line(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut node = id;
+        while self.result(node).is_none() {
+            let Some(&next) = self.children(node).first() else {
+                break;
+            };
+            line.push(next);
+            node = next;
+        }
+        line
+    }
+
+    /// Position of `id` among its siblings; 0 is the main continuation.
+    pub fn sibling_index(&self, id: NodeId) -> usize {
+        self.parent(id)
+            .and_then(|parent| self.children(parent).iter().position(|&c| c == id))
+            .unwrap_or(0)
+    }
+
+    /// Makes `id` the main continuation of its parent.
+    pub(crate) fn promote(&mut self, id: NodeId) {
+        let Some(parent) = self.parent(id) else {
+            return;
+        };
+        let children = &mut self.nodes[parent].children;
+        if let Some(index) = children.iter().position(|&c| c == id) {
+            let id = children.remove(index);
+            children.insert(0, id);
+        }
+    }
+
+    /// Detaches `id` and everything after it from the tree.
+    pub(crate) fn remove(&mut self, id: NodeId) {
+        if let Some(parent) = self.parent(id) {
+            self.nodes[parent].children.retain(|&c| c != id);
+        }
+    }
+
+    /// Drops every continuation of `id`.
+    pub(crate) fn truncate(&mut self, id: NodeId) {
+        self.nodes[id].children.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shogi::{Color, Piece, PieceType};
+
+    use super::*;
+    use crate::core::game::EndReason;
+    use crate::core::usi::parse_usi_move;
+
+    fn entry(usi: &str) -> MoveEntry {
+        MoveEntry {
+            mv: parse_usi_move(usi).expect("valid test move"),
+            piece: Piece {
+                piece_type: PieceType::Pawn,
+                color: Color::Black,
+            },
+            captured: None,
+            comment: String::new(),
+            elapsed: None,
+        }
+    }
+
+    /// `7g7f 3c3d 2g2f` with `8c8d` and `4c4d` as alternatives to `3c3d`.
+    fn tree() -> (VariationTree, [NodeId; 5]) {
+        let mut tree = VariationTree::default();
+        let first = tree.add_child(ROOT, entry("7g7f"));
+        let main = tree.add_child(first, entry("3c3d"));
+        let last = tree.add_child(main, entry("2g2f"));
+        let second = tree.add_child(first, entry("8c8d"));
+        let third = tree.add_child(first, entry("4c4d"));
+        (tree, [first, main, last, second, third])
+    }
+
+    #[test]
+    fn main_line_follows_first_children() {
+        let (tree, [first, main, last, second, _]) = tree();
+        assert_eq!(tree.main_line(ROOT), [first, main, last]);
+        assert_eq!(tree.main_line(second), []);
+        assert_eq!(tree.parent(last), Some(main));
+        assert_eq!(tree.parent(ROOT), None);
+    }
+
+    #[test]
+    fn main_line_stops_at_a_result() {
+        let (mut tree, [first, main, ..]) = tree();
+        tree.set_result(
+            main,
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::Interrupted,
+            }),
+        );
+        assert_eq!(tree.main_line(ROOT), [first, main]);
+    }
+
+    #[test]
+    fn siblings_and_lookup() {
+        let (tree, [first, main, _, second, third]) = tree();
+        assert_eq!(tree.children(first), [main, second, third]);
+        assert_eq!(tree.sibling_index(main), 0);
+        assert_eq!(tree.sibling_index(third), 2);
+        assert_eq!(tree.sibling_index(ROOT), 0);
+        let mv = parse_usi_move("4c4d").unwrap();
+        assert_eq!(tree.find_child(first, mv), Some(third));
+        assert_eq!(tree.find_child(ROOT, mv), None);
+    }
+
+    #[test]
+    fn promote_moves_a_variation_first() {
+        let (mut tree, [first, main, _, second, third]) = tree();
+        tree.promote(third);
+        assert_eq!(tree.children(first), [third, main, second]);
+        assert_eq!(tree.main_line(ROOT), [first, third]);
+        tree.promote(ROOT);
+        assert_eq!(tree.children(ROOT), [first]);
+    }
+
+    #[test]
+    fn remove_and_truncate() {
+        let (mut tree, [first, main, _, second, third]) = tree();
+        tree.remove(main);
+        assert_eq!(tree.children(first), [second, third]);
+        assert_eq!(tree.main_line(ROOT), [first, second]);
+        tree.truncate(first);
+        assert_eq!(tree.main_line(ROOT), [first]);
+        // Ids are not reused.
+        assert!(tree.add_child(first, entry("3c3d")) > third);
+    }
+}
@@ -0,0 +1,161 @@
+//! Engine analysis of a whole record, written back as move comments.
+//!
+//! Every position of the displayed line is searched in turn, and the move
+//! leading to it gets a comment such as
+//!
+//! ```text
+//! 解析 評価値 +85 深さ 18 読み筋 △３四歩(33) ▲２六歩(27)
+//! 最善手 ▲２六歩(27)
+//! ```
+//!
+//! Scores are from Sente's point of view. The `最善手` line appears when the
+//! engine preferred another move to the one played.
+
+use std::time::{Duration, Instant};
+
+use shogi::Color;
+
+use super::bridge::{EngineConfig, EngineHandle};
+use super::info::SearchInfo;
+use super::protocol::{EngineCommand, EngineEvent, GoParams};
+use crate::core::game::GameState;
+use crate::core::usi;
+
+/// Time past the search limit before the engine is told to stop, and past
+/// the `stop` timeout before it is given up on.
+const SEARCH_MARGIN: Duration = Duration::from_secs(5);
+
+/// Search limit assumed for `go` limits that are not a time, such as depth.
+const UNTIMED_SEARCH: Duration = Duration::from_secs(300);
+
+/// Searches every position of `game`'s displayed line with the engine
+/// launched from `config`, limited by `go`, and appends the results to the
+/// move comments. `on_ply` is called after each position with its ply and the
+/// comment added. The displayed ply is restored afterwards.
+pub fn annotate_record(
+    game: &mut GameState,
+    config: EngineConfig,
+    go: &GoParams,
+    mut on_ply: impl FnMut(usize, &str),
+) -> Result<(), String> {
+    if go.infinite || go.ponder {
+        return Err("analysis needs a search limit".to_string());
+    }
+    let timeouts = config.timeouts;
+    let handle = EngineHandle::start(config);
+    wait_ready(&handle, timeouts.usiok + timeouts.readyok)?;
+    handle.send(EngineCommand::NewGame);
+
+    let shown = game.current_ply();
+    // The engine's choice in the previous position, in USI and Japanese.
+    let mut previous_best: Option<(String, String)> = None;
+    for ply in 0..=game.history().len() {
+        game.jump_to_ply(ply);
+        let missed = previous_best
+            .take()
+            .filter(|(best, _)| usi::move_to_usi(game.history()[ply - 1].mv) != *best)
+            .map(|(_, japanese)| format!("最善手 {japanese}"));
+        let mut lines = Vec::new();
+        if !game.legal_moves().is_empty() {
+            let info = search(&handle, game, go, timeouts.stop)?;
+            let sfen = game.to_sfen();
+            let last_to = ply.checked_sub(1).map(|idx| game.history()[idx].to());
+            let pv = usi::japanese_pv(&sfen, last_to, &info.pv);
+            let mut line = "解析".to_string();
+            if let Some(score) = info.score {
+                let score = match game.side_to_move() {
+                    Color::Black => score,
+                    Color::White => score.flip(),
+                };
+                line.push_str(&format!(" 評価値 {score}"));
+            }
+            if let Some(depth) = info.depth {
+                line.push_str(&format!(" 深さ {depth}"));
+            }
+            if !pv.is_empty() {
+                line.push_str(&format!(" 読み筋 {}", pv.join(" ")));
+            }
+            lines.push(line);
+            previous_best = info.pv.first().cloned().zip(pv.first().cloned());
+        }
+        lines.extend(missed);
+        if lines.is_empty() {
+            continue;
+        }
+
+        let note = lines.join("\n");
+        let comment = match game.comment(ply) {
+            "" => note.clone(),
+            existing => format!("{existing}\n{note}"),
+        };
+        game.set_comment(ply, comment);
+        on_ply(ply, &note);
+    }
+
+    game.jump_to_ply(shown);
+    let _ = game.take_pending_sound();
+    game.set_status("");
+    Ok(())
+}
+
+fn wait_ready(handle: &EngineHandle, timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match handle.recv_timeout(remaining) {
+            Some(EngineEvent::Ready(_)) => return Ok(()),
+            Some(EngineEvent::Crashed(message)) => return Err(message),
+            Some(_) => {}
+            None => return Err("engine did not become ready in time".to_string()),
+        }
+    }
+}
+
+/// Searches the displayed position and returns the best line's last info.
+///
+/// An engine still searching well past the limit of `go` is sent `stop`, and
+/// one that does not answer within `stop_timeout` of that is an error.
+fn search(
+    handle: &EngineHandle,
+    game: &GameState,
+    go: &GoParams,
+    stop_timeout: Duration,
+) -> Result<SearchInfo, String> {
+    handle.send(EngineCommand::Position(usi::position_command(game)));
+    handle.send(EngineCommand::Go(go.clone()));
+    let mut info = SearchInfo::default();
+    let mut deadline = Instant::now() + search_limit(go) + SEARCH_MARGIN;
+    let mut stopped = false;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match handle.recv_timeout(remaining) {
+            Some(EngineEvent::Info(line)) => {
+                let parsed = SearchInfo::parse(&line);
+                if parsed.multipv.unwrap_or(1) == 1 {
+                    info.merge(parsed);
+                }
+            }
+            Some(EngineEvent::BestMove { .. }) => return Ok(info),
+            Some(EngineEvent::StopTimedOut) => {
+                return Err("engine did not answer `stop` in time".to_string());
+            }
+            Some(EngineEvent::Crashed(message)) => return Err(message),
+            Some(EngineEvent::Exited) => return Err("engine exited".to_string()),
+            Some(_) => {}
+            None if stopped => return Err("engine did not stop searching".to_string()),
+            None => {
+                handle.send(EngineCommand::Stop);
+                stopped = true;
+                deadline = Instant::now() + stop_timeout + SEARCH_MARGIN;
+            }
+        }
+    }
+}
+
+/// How long `go` lets the engine search.
+fn search_limit(go: &GoParams) -> Duration {
+    go.movetime
+        .or(go.byoyomi)
+        .or(go.mate.flatten())
+        .map_or(UNTIMED_SEARCH, Duration::from_millis)
+}
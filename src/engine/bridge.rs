@@ -0,0 +1,362 @@
+//! A USI session running on its own thread, driven through channels.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::process::{EngineProcess, ProcessOutput};
+use super::protocol::{EngineCommand, EngineEvent, EngineId, EngineMessage, parse_engine_line};
+
+/// How to launch an engine and what to configure before `isready`.
+#[derive(Clone, Debug, Default)]
+pub struct EngineConfig {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+    /// Defaults to the executable's directory, so engines find their evaluation files.
+    pub working_dir: Option<PathBuf>,
+    /// Sent as `setoption` between `usiok` and the first `isready`.
+    pub options: Vec<(String, String)>,
+    pub timeouts: Timeouts,
+}
+
+impl EngineConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Limits for engine replies before the session gives up on them.
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub usiok: Duration,
+    /// Engines load evaluation files on the first `isready`, so this is generous.
+    pub readyok: Duration,
+    /// Time to produce `bestmove` after `stop`.
+    pub stop: Duration,
+    /// Time to exit after `quit` before the process is killed.
+    pub quit: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            usiok: Duration::from_secs(10),
+            readyok: Duration::from_secs(60),
+            stop: Duration::from_secs(5),
+            quit: Duration::from_secs(3),
+        }
+    }
+}
+
+/// The GUI side of an engine session.
+///
+/// Dropping the handle sends `quit` and waits for the session thread, which
+/// kills the engine if it does not exit in time.
+pub struct EngineHandle {
+    input: Sender<Input>,
+    events: Receiver<EngineEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EngineHandle {
+    /// Starts the session thread. Launch failures arrive as [`EngineEvent::Crashed`].
+    pub fn start(config: EngineConfig) -> Self {
+        let (input, input_rx) = mpsc::channel();
+        let (events_tx, events) = mpsc::channel();
+        let output = input.clone();
+        let thread = thread::Builder::new()
+            .name("usi-session".to_string())
+            .spawn(move || Session::run(config, input_rx, output, events_tx))
+            .ok();
+        Self {
+            input,
+            events,
+            thread,
+        }
+    }
+
+    /// Runs only the handshake and returns what the engine reported about
+    /// itself. Blocks until the engine is ready, fails or times out.
+    pub fn probe(config: EngineConfig) -> Result<EngineId, String> {
+        let deadline = Instant::now() + config.timeouts.usiok + config.timeouts.readyok;
+        let handle = Self::start(config);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match handle.recv_timeout(remaining) {
+                Some(EngineEvent::Ready(id)) => return Ok(id),
+                Some(EngineEvent::Crashed(message)) => return Err(message),
+                Some(_) => {}
+                None => return Err("engine did not become ready in time".to_string()),
+            }
+        }
+    }
+
+    pub fn send(&self, command: EngineCommand) {
+        let _ = self.input.send(Input::Command(command));
+    }
+
+    pub fn try_recv(&self) -> Option<EngineEvent> {
+        self.events.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<EngineEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        let _ = self.input.send(Input::Command(EngineCommand::Quit));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+enum Input {
+    Command(EngineCommand),
+    Output(ProcessOutput),
+}
+
+enum SessionError {
+    Timeout(&'static str),
+    Crashed,
+    QuitRequested,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(reply) => write!(f, "engine did not answer with `{reply}` in time"),
+            Self::Crashed => write!(f, "engine exited unexpectedly"),
+            Self::QuitRequested => write!(f, "engine session closed"),
+        }
+    }
+}
+
+struct Session {
+    process: EngineProcess,
+    input: Receiver<Input>,
+    events: Sender<EngineEvent>,
+    timeouts: Timeouts,
+    /// Commands that arrived while waiting for a reply.
+    deferred: Vec<EngineCommand>,
+    thinking: bool,
+    /// Searches whose `stop` timed out; their late `bestmove`s are dropped.
+    abandoned: usize,
+}
+
+impl Session {
+    fn run(
+        config: EngineConfig,
+        input: Receiver<Input>,
+        output: Sender<Input>,
+        events: Sender<EngineEvent>,
+    ) {
+        let process = match EngineProcess::spawn(
+            &config.path,
+            &config.args,
+            config.working_dir.as_deref(),
+            move |line| {
+                let _ = output.send(Input::Output(line));
+            },
+        ) {
+            Ok(process) => process,
+            Err(err) => {
+                let _ = events.send(EngineEvent::Crashed(format!(
+                    "failed to start {}: {err}",
+                    config.path.display()
+                )));
+                return;
+            }
+        };
+
+        let mut session = Self {
+            process,
+            input,
+            events,
+            timeouts: config.timeouts,
+            deferred: Vec::new(),
+            thinking: false,
+            abandoned: 0,
+        };
+        let result = session
+            .handshake(&config.options)
+            .and_then(|()| session.serve());
+        match result {
+            Ok(()) | Err(SessionError::QuitRequested) => session.quit(),
+            Err(err) => session.fail(err),
+        }
+    }
+
+    fn handshake(&mut self, options: &[(String, String)]) -> Result<(), SessionError> {
+        self.write("usi")?;
+        let mut id = EngineId::default();
+        self.wait_for("usiok", self.timeouts.usiok, |message| match message {
+            EngineMessage::IdName(name) => {
+                id.name = name.clone();
+                false
+            }
+            EngineMessage::IdAuthor(author) => {
+                id.author = author.clone();
+                false
+            }
+            EngineMessage::Option(option) => {
+                id.options.push(option.clone());
+                false
+            }
+            EngineMessage::UsiOk => true,
+            _ => false,
+        })?;
+
+        for (name, value) in options {
+            self.write(format!("setoption name {name} value {value}"))?;
+        }
+        self.is_ready()?;
+        self.emit(EngineEvent::Ready(id));
+        Ok(())
+    }
+
+    fn serve(&mut self) -> Result<(), SessionError> {
+        loop {
+            let next = if self.deferred.is_empty() {
+                match self.input.recv() {
+                    Ok(input) => input,
+                    Err(_) => return Ok(()),
+                }
+            } else {
+                Input::Command(self.deferred.remove(0))
+            };
+            match next {
+                Input::Command(EngineCommand::Quit) => return Ok(()),
+                Input::Command(command) => self.execute(command)?,
+                Input::Output(ProcessOutput::Line(line)) => self.forward(parse_engine_line(&line)),
+                Input::Output(ProcessOutput::Closed) => return Err(SessionError::Crashed),
+            }
+        }
+    }
+
+    fn execute(&mut self, command: EngineCommand) -> Result<(), SessionError> {
+        match command {
+            EngineCommand::NewGame => {
+                self.is_ready()?;
+                self.write("usinewgame")
+            }
+            EngineCommand::Go(params) => {
+                self.thinking = true;
+                self.write(params.to_usi())
+            }
+            EngineCommand::Stop => {
+                if !self.thinking {
+                    return Ok(());
+                }
+                self.write("stop")?;
+                loop {
+                    let result = self.wait_for("bestmove", self.timeouts.stop, |message| {
+                        matches!(message, EngineMessage::BestMove { .. })
+                    });
+                    return match result {
+                        Ok(_) if self.abandoned > 0 => {
+                            self.abandoned -= 1;
+                            continue;
+                        }
+                        Ok(message) => {
+                            self.forward(message);
+                            Ok(())
+                        }
+                        Err(SessionError::Timeout(_)) => {
+                            self.thinking = false;
+                            self.abandoned += 1;
+                            self.emit(EngineEvent::StopTimedOut);
+                            Ok(())
+                        }
+                        Err(err) => Err(err),
+                    };
+                }
+            }
+            other => match other.to_usi() {
+                Some(line) => self.write(line),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn is_ready(&mut self) -> Result<(), SessionError> {
+        self.write("isready")?;
+        self.wait_for("readyok", self.timeouts.readyok, |message| {
+            *message == EngineMessage::ReadyOk
+        })
+        .map(|_| ())
+    }
+
+    /// Reads engine output until `done` accepts a message and returns it.
+    /// Declined messages are forwarded as usual; commands are deferred until
+    /// the reply arrives.
+    fn wait_for(
+        &mut self,
+        reply: &'static str,
+        timeout: Duration,
+        mut done: impl FnMut(&EngineMessage) -> bool,
+    ) -> Result<EngineMessage, SessionError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.input.recv_timeout(remaining) {
+                Ok(Input::Output(ProcessOutput::Line(line))) => {
+                    let message = parse_engine_line(&line);
+                    if done(&message) {
+                        return Ok(message);
+                    }
+                    self.forward(message);
+                }
+                Ok(Input::Output(ProcessOutput::Closed)) => return Err(SessionError::Crashed),
+                Ok(Input::Command(EngineCommand::Quit)) => return Err(SessionError::QuitRequested),
+                Ok(Input::Command(command)) => self.deferred.push(command),
+                Err(RecvTimeoutError::Timeout) => return Err(SessionError::Timeout(reply)),
+                Err(RecvTimeoutError::Disconnected) => return Err(SessionError::QuitRequested),
+            }
+        }
+    }
+
+    fn forward(&mut self, message: EngineMessage) {
+        match message {
+            EngineMessage::Info(line) => self.emit(EngineEvent::Info(line)),
+            EngineMessage::BestMove { .. } if self.abandoned > 0 => self.abandoned -= 1,
+            EngineMessage::BestMove { mv, ponder } => {
+                self.thinking = false;
+                self.emit(EngineEvent::BestMove { mv, ponder });
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, line: impl Into<String>) -> Result<(), SessionError> {
+        self.process.send(line).map_err(|_| SessionError::Crashed)
+    }
+
+    fn emit(&self, event: EngineEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn quit(&mut self) {
+        let _ = self.process.send("quit");
+        if self.process.wait_timeout(self.timeouts.quit).is_none() {
+            self.process.kill();
+        }
+        self.emit(EngineEvent::Exited);
+    }
+
+    fn fail(&mut self, err: SessionError) {
+        let message = match (&err, self.process.wait_timeout(Duration::from_millis(200))) {
+            (SessionError::Crashed, Some(status)) => format!("{err} ({status})"),
+            _ => err.to_string(),
+        };
+        self.process.kill();
+        self.emit(EngineEvent::Crashed(message));
+    }
+}
@@ -0,0 +1,145 @@
+//! Parsing of USI `info` lines into search statistics.
+
+use std::fmt;
+
+/// Evaluation reported with `score`, from the side to move's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    /// Centipawns.
+    Cp(i32),
+    /// Mate in this many plies; negative when the side to move is mated.
+    Mate(i32),
+    /// `mate +` or `mate -` without a distance; `true` when the side to move mates.
+    MateUnknown(bool),
+}
+
+impl Score {
+    /// The same score seen from the other side.
+    pub fn flip(self) -> Self {
+        match self {
+            Self::Cp(cp) => Self::Cp(-cp),
+            Self::Mate(plies) => Self::Mate(-plies),
+            Self::MateUnknown(winning) => Self::MateUnknown(!winning),
+        }
+    }
+
+    /// A single number for ordering scores; mates rank beyond any centipawn value.
+    pub fn sort_key(self) -> i64 {
+        const MATE: i64 = 1_000_000;
+        match self {
+            Self::Cp(cp) => i64::from(cp),
+            Self::Mate(plies) if plies >= 0 => MATE - i64::from(plies),
+            Self::Mate(plies) => -MATE - i64::from(plies),
+            Self::MateUnknown(true) => MATE,
+            Self::MateUnknown(false) => -MATE,
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cp(cp) => write!(f, "{cp:+}"),
+            Self::Mate(plies) if *plies >= 0 => write!(f, "+M{plies}"),
+            Self::Mate(plies) => write!(f, "-M{}", -plies),
+            Self::MateUnknown(true) => write!(f, "+M"),
+            Self::MateUnknown(false) => write!(f, "-M"),
+        }
+    }
+}
+
+/// `lowerbound` / `upperbound` after a score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreBound {
+    Lower,
+    Upper,
+}
+
+/// Fields of one or more `info` lines. Engines send partial lines (e.g. only
+/// `nodes` and `nps`), so absent fields stay `None` and [`SearchInfo::merge`]
+/// combines them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    /// Milliseconds searched.
+    pub time: Option<u64>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    /// Hash table usage in per mille.
+    pub hashfull: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub bound: Option<ScoreBound>,
+    pub currmove: Option<String>,
+    /// Principal variation in USI notation.
+    pub pv: Vec<String>,
+    pub string: Option<String>,
+}
+
+impl SearchInfo {
+    /// Parses an `info ...` line; unknown keys are skipped.
+    pub fn parse(line: &str) -> Self {
+        let mut info = Self::default();
+        let mut tokens = line.split_whitespace().peekable();
+        if tokens.peek() == Some(&"info") {
+            tokens.next();
+        }
+
+        while let Some(key) = tokens.next() {
+            match key {
+                "depth" => info.depth = tokens.next().and_then(|v| v.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|v| v.parse().ok()),
+                "time" => info.time = tokens.next().and_then(|v| v.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|v| v.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|v| v.parse().ok()),
+                "hashfull" => info.hashfull = tokens.next().and_then(|v| v.parse().ok()),
+                "multipv" => info.multipv = tokens.next().and_then(|v| v.parse().ok()),
+                "currmove" => info.currmove = tokens.next().map(str::to_string),
+                "score" => {
+                    info.score = match (tokens.next(), tokens.next()) {
+                        (Some("cp"), Some(value)) => value.parse().ok().map(Score::Cp),
+                        (Some("mate"), Some("+")) => Some(Score::MateUnknown(true)),
+                        (Some("mate"), Some("-")) => Some(Score::MateUnknown(false)),
+                        (Some("mate"), Some(value)) => value.parse().ok().map(Score::Mate),
+                        _ => None,
+                    };
+                }
+                "lowerbound" => info.bound = Some(ScoreBound::Lower),
+                "upperbound" => info.bound = Some(ScoreBound::Upper),
+                // Both run to the end of the line.
+                "pv" => info.pv = tokens.by_ref().map(str::to_string).collect(),
+                "string" => info.string = Some(tokens.by_ref().collect::<Vec<_>>().join(" ")),
+                _ => {}
+            }
+        }
+        info
+    }
+
+    /// Overwrites fields present in `newer`. A new score without a bound
+    /// clears the previous bound.
+    pub fn merge(&mut self, newer: Self) {
+        update(&mut self.depth, newer.depth);
+        update(&mut self.seldepth, newer.seldepth);
+        update(&mut self.time, newer.time);
+        update(&mut self.nodes, newer.nodes);
+        update(&mut self.nps, newer.nps);
+        update(&mut self.hashfull, newer.hashfull);
+        update(&mut self.multipv, newer.multipv);
+        update(&mut self.currmove, newer.currmove);
+        update(&mut self.string, newer.string);
+        if newer.score.is_some() {
+            self.score = newer.score;
+            self.bound = newer.bound;
+        }
+        if !newer.pv.is_empty() {
+            self.pv = newer.pv;
+        }
+    }
+}
+
+fn update<T>(field: &mut Option<T>, newer: Option<T>) {
+    if newer.is_some() {
+        *field = newer;
+    }
+}
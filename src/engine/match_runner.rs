@@ -0,0 +1,653 @@
+//! Engine-vs-engine matches played on a background thread.
+//!
+//! [`MatchHandle::start`] launches both engines, plays the games one after
+//! another and reports progress as [`MatchEvent`]s. Results are tallied from
+//! the point of view of the first engine.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use shogi::Color;
+
+use super::bridge::{EngineHandle, Timeouts};
+use super::info::{Score, SearchInfo};
+use super::protocol::{EngineCommand, EngineEvent, GameOutcome, GoParams};
+use super::registry::EngineEntry;
+use crate::core::clock::{Clock, TimeControl};
+use crate::core::game::{EndReason, GameResult, GameState, ImpasseRule, color_name};
+use crate::core::record::{self, RecordError, RecordFormat};
+use crate::core::usi;
+
+/// Grace period past the time limit, covering process and pipe latency.
+const TIME_MARGIN_MS: u64 = 1_000;
+/// How often the runner checks for a stop request while waiting on an engine.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When a game is decided without being played out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A side loses once both engines put it this many centipawns behind...
+    pub resign_cp: Option<i32>,
+    /// ...for this many consecutive moves of each engine.
+    pub resign_moves: u32,
+    /// Games reaching this many plies are drawn.
+    pub max_plies: Option<usize>,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            resign_cp: None,
+            resign_moves: 4,
+            max_plies: Some(256),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchSettings {
+    pub engines: [EngineEntry; 2],
+    pub games: u32,
+    pub time: TimeControl,
+    /// Swap colors every game, so each opening is played once with each color.
+    pub alternate_colors: bool,
+    /// `position` lines to start from, used in turn; empty means the initial position.
+    pub openings: Vec<String>,
+    pub adjudication: Adjudication,
+    /// Directory each finished game is saved to.
+    pub save_dir: Option<PathBuf>,
+    pub format: RecordFormat,
+}
+
+impl MatchSettings {
+    /// Engine index playing Black in game `index`.
+    pub fn black_engine(&self, index: u32) -> usize {
+        if self.alternate_colors {
+            (index % 2) as usize
+        } else {
+            0
+        }
+    }
+
+    fn opening(&self, index: u32) -> &str {
+        if self.openings.is_empty() {
+            return "startpos";
+        }
+        let round = if self.alternate_colors {
+            index / 2
+        } else {
+            index
+        };
+        &self.openings[round as usize % self.openings.len()]
+    }
+}
+
+/// Wins, losses and draws of the first engine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchTally {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchTally {
+    pub fn games(self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn record(&mut self, outcome: GameOutcome) {
+        match outcome {
+            GameOutcome::Win => self.wins += 1,
+            GameOutcome::Lose => self.losses += 1,
+            GameOutcome::Draw => self.draws += 1,
+        }
+    }
+
+    /// Points per game, counting draws as half a point.
+    pub fn score(self) -> Option<f64> {
+        let games = self.games();
+        (games > 0).then(|| (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(games))
+    }
+
+    /// Elo difference of the first engine over the second and its 95%
+    /// error margin. `None` until the score is strictly between 0 and 1.
+    pub fn elo(self) -> Option<(f64, f64)> {
+        let score = self.score().filter(|score| *score > 0.0 && *score < 1.0)?;
+        let games = f64::from(self.games());
+        let deviation = |points: f64| (points - score).powi(2);
+        let variance = (f64::from(self.wins) * deviation(1.0)
+            + f64::from(self.losses) * deviation(0.0)
+            + f64::from(self.draws) * deviation(0.5))
+            / games;
+        let error = 1.96 * (variance / games).sqrt();
+        let bounded = |points: f64| points.clamp(0.001, 0.999);
+        let margin =
+            (elo_difference(bounded(score + error)) - elo_difference(bounded(score - error))) / 2.0;
+        Some((elo_difference(score), margin))
+    }
+}
+
+impl fmt::Display for MatchTally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} -{} ={}", self.wins, self.losses, self.draws)?;
+        if let Some((elo, margin)) = self.elo() {
+            write!(f, " (Elo {elo:+.0} ± {margin:.0})")?;
+        }
+        Ok(())
+    }
+}
+
+fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Progress reported by a running match.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchEvent {
+    /// Both engines finished their handshake.
+    Ready {
+        names: [String; 2],
+    },
+    GameStarted {
+        index: u32,
+        /// Engine index playing Black.
+        black: usize,
+        /// Start position as a USI `position` line.
+        position: String,
+    },
+    Moved {
+        mv: String,
+        elapsed_ms: u64,
+        /// Last score of the mover, from its own point of view.
+        score: Option<Score>,
+    },
+    GameFinished {
+        index: u32,
+        result: GameResult,
+        saved: Option<PathBuf>,
+        tally: MatchTally,
+    },
+    Error(String),
+    /// The runner is done; `stopped` when it ended before the last game.
+    Finished {
+        tally: MatchTally,
+        stopped: bool,
+    },
+}
+
+/// The GUI side of a running match.
+///
+/// Dropping the handle stops the match; the runner then shuts both engines
+/// down on its own thread.
+pub struct MatchHandle {
+    events: Receiver<MatchEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MatchHandle {
+    pub fn start(settings: MatchSettings) -> Self {
+        let (events_tx, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let runner = Runner {
+            settings,
+            events: events_tx,
+            stop: Arc::clone(&stop),
+            tally: MatchTally::default(),
+        };
+        let _ = thread::Builder::new()
+            .name("engine-match".to_string())
+            .spawn(move || runner.run());
+        Self { events, stop }
+    }
+
+    pub fn try_recv(&self) -> Option<MatchEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Abandons the game in progress; the runner reports `Finished` once the
+    /// engines are shut down.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for MatchHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Debug)]
+pub enum OpeningsError {
+    Io(io::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for OpeningsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Invalid { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for OpeningsError {}
+
+/// Reads start positions, one per line: an SFEN, or a USI `position` line
+/// such as `startpos moves 7g7f 3c3d`. Blank lines and `#` comments are skipped.
+pub fn load_openings(path: &Path) -> Result<Vec<String>, OpeningsError> {
+    let bytes = fs::read(path).map_err(OpeningsError::Io)?;
+    parse_openings(&record::decode_text(&bytes))
+}
+
+pub fn parse_openings(text: &str) -> Result<Vec<String>, OpeningsError> {
+    let mut openings = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let position = if line.starts_with("position")
+            || line.starts_with("startpos")
+            || line.starts_with("sfen")
+        {
+            line.to_string()
+        } else {
+            format!("sfen {line}")
+        };
+        if let Err(err) = usi::parse_position(&position) {
+            return Err(OpeningsError::Invalid {
+                line: idx + 1,
+                message: err.to_string(),
+            });
+        }
+        openings.push(position);
+    }
+    Ok(openings)
+}
+
+enum RunError {
+    Stopped,
+    Failed(String),
+}
+
+struct Player {
+    handle: EngineHandle,
+    name: String,
+}
+
+struct Reply {
+    mv: String,
+    score: Option<Score>,
+}
+
+struct Runner {
+    settings: MatchSettings,
+    events: Sender<MatchEvent>,
+    stop: Arc<AtomicBool>,
+    tally: MatchTally,
+}
+
+impl Runner {
+    fn run(mut self) {
+        let stopped = match self.play() {
+            Ok(()) => false,
+            Err(RunError::Stopped) => true,
+            Err(RunError::Failed(message)) => {
+                self.emit(MatchEvent::Error(message));
+                true
+            }
+        };
+        self.emit(MatchEvent::Finished {
+            tally: self.tally,
+            stopped,
+        });
+    }
+
+    fn play(&mut self) -> Result<(), RunError> {
+        let handles = self
+            .settings
+            .engines
+            .each_ref()
+            .map(|entry| EngineHandle::start(entry.config()));
+        let mut players = Vec::with_capacity(2);
+        for (entry, handle) in self.settings.engines.clone().into_iter().zip(handles) {
+            let id_name = self.wait_ready(&handle, &entry)?;
+            let name = if entry.name.is_empty() {
+                id_name
+            } else {
+                entry.name
+            };
+            players.push(Player { handle, name });
+        }
+        self.emit(MatchEvent::Ready {
+            names: [players[0].name.clone(), players[1].name.clone()],
+        });
+
+        for index in 0..self.settings.games {
+            self.play_game(index, &players)?;
+        }
+        Ok(())
+    }
+
+    fn play_game(&mut self, index: u32, players: &[Player]) -> Result<(), RunError> {
+        let black = self.settings.black_engine(index);
+        let engine_for = |color: Color| match color {
+            Color::Black => black,
+            Color::White => 1 - black,
+        };
+        let position = self.settings.opening(index).to_string();
+        let mut game = usi::parse_position(&position)
+            .map_err(|err| RunError::Failed(format!("opening `{position}`: {err}")))?;
+        let info = game.info_mut();
+        info.black = Some(players[black].name.clone());
+        info.white = Some(players[1 - black].name.clone());
+        info.event = Some(format!("Engine match, game {}", index + 1));
+        info.time_control = Some(self.settings.time.header());
+
+        for player in players {
+            player.handle.send(EngineCommand::NewGame);
+        }
+        self.emit(MatchEvent::GameStarted {
+            index,
+            black,
+            position,
+        });
+
+        let mut clock = Clock::new(self.settings.time).with_grace(TIME_MARGIN_MS);
+        clock.start(game.side_to_move(), Instant::now());
+        let mut adjudicator = Adjudicator::new(self.settings.adjudication);
+        while game.result().is_none() {
+            let color = game.side_to_move();
+            let player = &players[engine_for(color)];
+            player
+                .handle
+                .send(EngineCommand::Position(usi::position_command(&game)));
+            player
+                .handle
+                .send(EngineCommand::Go(GoParams::for_clock(&clock)));
+
+            let allowed = clock.allowance_ms(color) + TIME_MARGIN_MS;
+            let reply =
+                self.wait_bestmove(player, Instant::now() + Duration::from_millis(allowed))?;
+            let elapsed = reply.as_ref().and_then(|_| clock.press(Instant::now()));
+            let (Some(Reply { mv, score }), Some(elapsed_ms)) = (reply, elapsed) else {
+                game.forfeit(color, EndReason::Timeout);
+                continue;
+            };
+
+            match mv.as_str() {
+                "resign" => game.resign(color),
+                "win" => game.declare_impasse(ImpasseRule::TwentySevenPoint),
+                _ => {
+                    let played =
+                        usi::parse_usi_move(&mv).is_some_and(|parsed| game.play_move(parsed));
+                    if !played {
+                        game.forfeit(color, EndReason::IllegalMove);
+                        continue;
+                    }
+                    let ply = game.current_ply();
+                    game.set_elapsed(ply, u32::try_from(elapsed_ms / 1_000).ok());
+                    self.emit(MatchEvent::Moved {
+                        mv,
+                        elapsed_ms,
+                        score,
+                    });
+                    if game.result().is_none()
+                        && let Some((result, note)) = adjudicator.after_move(color, score, ply)
+                    {
+                        game.set_comment(ply, note);
+                        game.set_result(Some(result));
+                    }
+                }
+            }
+        }
+
+        let result = game.result().expect("loop ends with a result");
+        for (idx, player) in players.iter().enumerate() {
+            let color = if idx == black {
+                Color::Black
+            } else {
+                Color::White
+            };
+            player
+                .handle
+                .send(EngineCommand::GameOver(outcome_for(result, color)));
+        }
+        let first_color = if black == 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+        self.tally.record(outcome_for(result, first_color));
+        let saved = self.save(index, &game);
+        self.emit(MatchEvent::GameFinished {
+            index,
+            result,
+            saved,
+            tally: self.tally,
+        });
+        Ok(())
+    }
+
+    fn save(&self, index: u32, game: &GameState) -> Option<PathBuf> {
+        let dir = self.settings.save_dir.as_ref()?;
+        let info = game.info();
+        let name = |player: &Option<String>| slug(player.as_deref().unwrap_or("engine"));
+        let path = dir.join(format!(
+            "{:03}_{}_vs_{}.{}",
+            index + 1,
+            name(&info.black),
+            name(&info.white),
+            self.settings.format.extension()
+        ));
+        let written = fs::create_dir_all(dir)
+            .map_err(RecordError::from)
+            .and_then(|()| record::write_record(game, &path));
+        match written {
+            Ok(()) => Some(path),
+            Err(err) => {
+                self.emit(MatchEvent::Error(format!(
+                    "could not save {}: {err}",
+                    path.display()
+                )));
+                None
+            }
+        }
+    }
+
+    /// Waits for the engine's handshake and returns the name it reported.
+    fn wait_ready(&self, handle: &EngineHandle, entry: &EngineEntry) -> Result<String, RunError> {
+        loop {
+            self.check_stop()?;
+            match handle.recv_timeout(POLL_INTERVAL) {
+                Some(EngineEvent::Ready(id)) => return Ok(id.name),
+                Some(EngineEvent::Crashed(message)) => {
+                    return Err(RunError::Failed(format!(
+                        "{}: {message}",
+                        entry.path.display()
+                    )));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits for `bestmove` until `deadline`. A late engine is stopped and
+    /// its reply discarded, giving `None`.
+    fn wait_bestmove(&self, player: &Player, deadline: Instant) -> Result<Option<Reply>, RunError> {
+        let mut info = SearchInfo::default();
+        loop {
+            self.check_stop()?;
+            let now = Instant::now();
+            if now >= deadline {
+                player.handle.send(EngineCommand::Stop);
+                // Drain the reply so it is not taken for the next move. The
+                // bridge sends it, or `StopTimedOut`, within its stop timeout.
+                let drain_until = now + Timeouts::default().stop * 2;
+                while Instant::now() < drain_until {
+                    if let Some(EngineEvent::BestMove { .. } | EngineEvent::StopTimedOut) =
+                        player.handle.recv_timeout(POLL_INTERVAL)
+                    {
+                        break;
+                    }
+                }
+                return Ok(None);
+            }
+            match player
+                .handle
+                .recv_timeout(POLL_INTERVAL.min(deadline - now))
+            {
+                Some(EngineEvent::Info(line)) => {
+                    let parsed = SearchInfo::parse(&line);
+                    if parsed.multipv.unwrap_or(1) == 1 {
+                        info.merge(parsed);
+                    }
+                }
+                Some(EngineEvent::BestMove { mv, .. }) => {
+                    return Ok(Some(Reply {
+                        mv,
+                        score: info.score,
+                    }));
+                }
+                Some(EngineEvent::Crashed(message)) => {
+                    return Err(RunError::Failed(format!("{}: {message}", player.name)));
+                }
+                Some(EngineEvent::Exited) => {
+                    return Err(RunError::Failed(format!("{} exited", player.name)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_stop(&self) -> Result<(), RunError> {
+        if self.stop.load(Ordering::Relaxed) {
+            Err(RunError::Stopped)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn emit(&self, event: MatchEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Tracks scores for [`Adjudication`].
+struct Adjudicator {
+    rule: Adjudication,
+    /// Latest score of each engine from Black's point of view, by [`color_index`].
+    scores: [Option<Score>; 2],
+    /// Side both engines consider winning, and for how many plies in a row.
+    streak: Option<(Color, u32)>,
+}
+
+impl Adjudicator {
+    fn new(rule: Adjudication) -> Self {
+        Self {
+            rule,
+            scores: [None; 2],
+            streak: None,
+        }
+    }
+
+    /// Records the mover's score and returns the adjudicated result, with a
+    /// comment for the record, once the game should end.
+    fn after_move(
+        &mut self,
+        mover: Color,
+        score: Option<Score>,
+        ply: usize,
+    ) -> Option<(GameResult, String)> {
+        self.scores[color_index(mover)] = match mover {
+            Color::Black => score,
+            Color::White => score.map(Score::flip),
+        };
+
+        if let Some(threshold) = self.rule.resign_cp {
+            let threshold = i64::from(threshold);
+            let favored = match self.scores {
+                [Some(black), Some(white)]
+                    if black.sort_key() >= threshold && white.sort_key() >= threshold =>
+                {
+                    Some(Color::Black)
+                }
+                [Some(black), Some(white)]
+                    if black.sort_key() <= -threshold && white.sort_key() <= -threshold =>
+                {
+                    Some(Color::White)
+                }
+                _ => None,
+            };
+            self.streak = match (favored, self.streak) {
+                (Some(side), Some((previous, plies))) if side == previous => {
+                    Some((side, plies + 1))
+                }
+                (Some(side), _) => Some((side, 1)),
+                (None, _) => None,
+            };
+            if let Some((winner, plies)) = self.streak
+                && plies >= self.rule.resign_moves * 2
+            {
+                let result = GameResult {
+                    winner: Some(winner),
+                    reason: EndReason::Resignation,
+                };
+                let note = format!(
+                    "Adjudicated: both engines scored {} ahead by {threshold} or more for {} moves",
+                    color_name(winner),
+                    self.rule.resign_moves
+                );
+                return Some((result, note));
+            }
+        }
+
+        match self.rule.max_plies {
+            Some(limit) if ply >= limit => Some((
+                GameResult {
+                    winner: None,
+                    reason: EndReason::Impasse,
+                },
+                format!("Adjudicated: draw at the {limit}-ply limit"),
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::Black => 0,
+        Color::White => 1,
+    }
+}
+
+fn outcome_for(result: GameResult, color: Color) -> GameOutcome {
+    match result.winner {
+        None => GameOutcome::Draw,
+        Some(winner) if winner == color => GameOutcome::Win,
+        Some(_) => GameOutcome::Lose,
+    }
+}
+
+/// `name` reduced to characters safe in file names.
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,17 @@
+//! USI engine integration: child processes, the protocol and a threaded session.
+//!
+//! Everything here is plain `std`, so it works without any UI feature.
+
+pub mod annotate;
+pub mod bridge;
+pub mod info;
+pub mod match_runner;
+pub mod process;
+pub mod protocol;
+pub mod registry;
+
+pub use bridge::{EngineConfig, EngineHandle};
+pub use info::{Score, SearchInfo};
+pub use match_runner::{MatchEvent, MatchHandle, MatchSettings, MatchTally};
+pub use protocol::{EngineCommand, EngineEvent, GoParams};
+pub use registry::{EngineEntry, EngineRegistry};
@@ -0,0 +1,114 @@
+//! A USI engine child process with one thread per pipe.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What the reader thread sees on the engine's stdout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProcessOutput {
+    Line(String),
+    /// stdout reached EOF or failed; the engine is gone or about to be.
+    Closed,
+}
+
+pub struct EngineProcess {
+    child: Child,
+    writer: Sender<String>,
+}
+
+impl EngineProcess {
+    /// Starts `path` in `working_dir`, or else in its own directory so engines
+    /// find their evaluation files, and feeds every stdout line to `on_output`.
+    pub fn spawn(
+        path: &Path,
+        args: &[String],
+        working_dir: Option<&Path>,
+        on_output: impl Fn(ProcessOutput) + Send + 'static,
+    ) -> io::Result<Self> {
+        let mut command = Command::new(path);
+        command
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        let dir = working_dir.or_else(|| path.parent());
+        if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+            command.current_dir(dir);
+        }
+        let mut child = command.spawn()?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        thread::Builder::new()
+            .name("usi-reader".to_string())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    match line {
+                        Ok(line) => on_output(ProcessOutput::Line(line)),
+                        Err(_) => break,
+                    }
+                }
+                on_output(ProcessOutput::Closed);
+            })?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let (writer, lines) = mpsc::channel::<String>();
+        thread::Builder::new()
+            .name("usi-writer".to_string())
+            .spawn(move || write_lines(stdin, lines))?;
+
+        Ok(Self { child, writer })
+    }
+
+    /// Queues `line` for the writer thread; fails once the pipe is broken.
+    pub fn send(&self, line: impl Into<String>) -> io::Result<()> {
+        self.writer
+            .send(line.into())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "engine stdin is closed"))
+    }
+
+    pub fn try_wait(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
+    }
+
+    /// Waits up to `timeout` for the engine to exit by itself.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait() {
+                return Some(status);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        if self.try_wait().is_none() {
+            self.kill();
+        }
+    }
+}
+
+fn write_lines(mut stdin: ChildStdin, lines: mpsc::Receiver<String>) {
+    for line in lines {
+        if writeln!(stdin, "{line}")
+            .and_then(|()| stdin.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! USI message types exchanged with engine processes.
+
+use shogi::Color;
+
+use crate::core::clock::Clock;
+
+/// Requests the GUI sends to an engine session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineCommand {
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    /// `isready` followed by `usinewgame`.
+    NewGame,
+    /// A full `position ...` line, see `core::usi::position_command`.
+    Position(String),
+    Go(GoParams),
+    Stop,
+    PonderHit,
+    GameOver(GameOutcome),
+    Quit,
+}
+
+impl EngineCommand {
+    /// The line written to the engine, or `None` for commands that expand
+    /// into several lines.
+    pub fn to_usi(&self) -> Option<String> {
+        match self {
+            Self::SetOption { name, value } => Some(match value {
+                Some(value) => format!("setoption name {name} value {value}"),
+                None => format!("setoption name {name}"),
+            }),
+            Self::NewGame => None,
+            Self::Position(line) => Some(line.clone()),
+            Self::Go(params) => Some(params.to_usi()),
+            Self::Stop => Some("stop".to_string()),
+            Self::PonderHit => Some("ponderhit".to_string()),
+            Self::GameOver(outcome) => Some(format!("gameover {}", outcome.as_usi())),
+            Self::Quit => Some("quit".to_string()),
+        }
+    }
+}
+
+/// Outcome reported with `gameover`, from the engine's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    Win,
+    Lose,
+    Draw,
+}
+
+impl GameOutcome {
+    fn as_usi(self) -> &'static str {
+        match self {
+            Self::Win => "win",
+            Self::Lose => "lose",
+            Self::Draw => "draw",
+        }
+    }
+}
+
+/// Search limits for `go`. Times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub btime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub byoyomi: Option<u64>,
+    pub binc: Option<u64>,
+    pub winc: Option<u64>,
+    pub movetime: Option<u64>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    /// `go mate`; `Some(None)` searches without a time limit.
+    pub mate: Option<Option<u64>>,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+impl GoParams {
+    pub fn infinite() -> Self {
+        Self {
+            infinite: true,
+            ..Self::default()
+        }
+    }
+
+    /// Time limits from `clock` at the start of the side to move's turn.
+    pub fn for_clock(clock: &Clock) -> Self {
+        let control = clock.control();
+        let byoyomi = control.byoyomi_ms();
+        let increment = control.increment_ms();
+        Self {
+            btime: Some(clock.main_left_ms(Color::Black)),
+            wtime: Some(clock.main_left_ms(Color::White)),
+            byoyomi: (byoyomi > 0).then_some(byoyomi),
+            binc: (increment > 0).then_some(increment),
+            winc: (increment > 0).then_some(increment),
+            ..Self::default()
+        }
+    }
+
+    pub fn to_usi(&self) -> String {
+        if let Some(limit) = self.mate {
+            return match limit {
+                Some(ms) => format!("go mate {ms}"),
+                None => "go mate infinite".to_string(),
+            };
+        }
+
+        let mut out = String::from("go");
+        if self.ponder {
+            out.push_str(" ponder");
+        }
+        let fields = [
+            ("btime", self.btime),
+            ("wtime", self.wtime),
+            ("byoyomi", self.byoyomi),
+            ("binc", self.binc),
+            ("winc", self.winc),
+            ("movetime", self.movetime),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                out.push_str(&format!(" {name} {value}"));
+            }
+        }
+        if self.infinite {
+            out.push_str(" infinite");
+        }
+        out
+    }
+}
+
+/// Notifications an engine session sends back to the GUI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// The `usi`/`isready` handshake finished.
+    Ready(EngineId),
+    /// A raw `info ...` line.
+    Info(String),
+    BestMove {
+        mv: String,
+        ponder: Option<String>,
+    },
+    /// `stop` went unanswered in time. The stopped search ends without a
+    /// `bestmove`, so this stands in for its reply; a `bestmove` the engine
+    /// sends for it later is dropped.
+    StopTimedOut,
+    /// The engine could not be started or exited without being asked to.
+    Crashed(String),
+    /// The engine quit after `quit`.
+    Exited,
+}
+
+/// What the engine reported about itself during the handshake.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineId {
+    pub name: String,
+    pub author: String,
+    pub options: Vec<UsiOption>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionKind {
+    Check,
+    Spin,
+    Combo,
+    Button,
+    String,
+    Filename,
+}
+
+/// An `option name ... type ...` declaration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsiOption {
+    pub name: String,
+    pub kind: OptionKind,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub vars: Vec<String>,
+}
+
+/// A line read from an engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineMessage {
+    IdName(String),
+    IdAuthor(String),
+    Option(UsiOption),
+    UsiOk,
+    ReadyOk,
+    BestMove { mv: String, ponder: Option<String> },
+    Info(String),
+    Other(String),
+}
+
+pub fn parse_engine_line(line: &str) -> EngineMessage {
+    let line = line.trim();
+    let mut tokens = line.split_whitespace();
+    match tokens.next() {
+        Some("usiok") => EngineMessage::UsiOk,
+        Some("readyok") => EngineMessage::ReadyOk,
+        Some("info") => EngineMessage::Info(line.to_string()),
+        Some("id") => {
+            let key = tokens.next();
+            let value = tokens.collect::<Vec<_>>().join(" ");
+            match key {
+                Some("name") => EngineMessage::IdName(value),
+                Some("author") => EngineMessage::IdAuthor(value),
+                _ => EngineMessage::Other(line.to_string()),
+            }
+        }
+        Some("bestmove") => match tokens.next() {
+            Some(mv) => {
+                let ponder = match (tokens.next(), tokens.next()) {
+                    (Some("ponder"), Some(ponder)) => Some(ponder.to_string()),
+                    _ => None,
+                };
+                EngineMessage::BestMove {
+                    mv: mv.to_string(),
+                    ponder,
+                }
+            }
+            None => EngineMessage::Other(line.to_string()),
+        },
+        Some("option") => match parse_option(line) {
+            Some(option) => EngineMessage::Option(option),
+            None => EngineMessage::Other(line.to_string()),
+        },
+        _ => EngineMessage::Other(line.to_string()),
+    }
+}
+
+/// Parses `option name <id> type <t> [default <x>] [min <x>] [max <x>] [var <x>]*`.
+/// Names and string defaults may contain spaces.
+fn parse_option(line: &str) -> Option<UsiOption> {
+    const KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+    let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+    for token in line.split_whitespace().skip(1) {
+        if KEYWORDS.contains(&token) {
+            fields.push((token, Vec::new()));
+        } else if let Some((_, values)) = fields.last_mut() {
+            values.push(token);
+        } else {
+            return None;
+        }
+    }
+
+    let value = |key: &str| {
+        fields
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, values)| values.join(" "))
+    };
+    let kind = match value("type")?.as_str() {
+        "check" => OptionKind::Check,
+        "spin" => OptionKind::Spin,
+        "combo" => OptionKind::Combo,
+        "button" => OptionKind::Button,
+        "string" => OptionKind::String,
+        "filename" => OptionKind::Filename,
+        _ => return None,
+    };
+    Some(UsiOption {
+        name: value("name").filter(|name| !name.is_empty())?,
+        kind,
+        default: value("default"),
+        min: value("min").and_then(|v| v.parse().ok()),
+        max: value("max").and_then(|v| v.parse().ok()),
+        vars: fields
+            .iter()
+            .filter(|(name, _)| *name == "var")
+            .map(|(_, values)| values.join(" "))
+            .collect(),
+    })
+}
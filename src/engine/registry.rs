@@ -0,0 +1,191 @@
+//! Engines the user has registered, with their saved option values.
+//!
+//! Stored as `engines.ini` in the settings directory:
+//!
+//! ```text
+//! [engine]
+//! name = YaneuraOu
+//! path = /opt/yaneuraou/YaneuraOu
+//! dir = /opt/yaneuraou
+//! option Threads = 4
+//! option USI_Hash = 1024
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::bridge::EngineConfig;
+use crate::config;
+
+const REGISTRY_FILE: &str = "engines.ini";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineEntry {
+    pub name: String,
+    pub path: PathBuf,
+    /// Directory the engine runs in; defaults to the executable's directory.
+    pub working_dir: Option<PathBuf>,
+    /// Option values sent as `setoption` before the first `isready`.
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineEntry {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> EngineConfig {
+        EngineConfig {
+            working_dir: self.working_dir.clone(),
+            options: self.options.clone(),
+            ..EngineConfig::new(&self.path)
+        }
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn remove_option(&mut self, name: &str) {
+        self.options.retain(|(key, _)| key != name);
+    }
+
+    /// Sets `name`, replacing an earlier value but keeping its position.
+    pub fn set_option(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.options.iter_mut().find(|(key, _)| key == name) {
+            Some((_, slot)) => *slot = value,
+            None => self.options.push((name.to_string(), value)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RegistryError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineRegistry {
+    pub engines: Vec<EngineEntry>,
+}
+
+impl EngineRegistry {
+    /// Reads the registry from the settings directory. A missing or
+    /// unreadable file gives an empty registry.
+    pub fn load() -> Self {
+        let Some(path) = config::config_file(REGISTRY_FILE) else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).unwrap_or_else(|err| {
+                eprintln!("ignoring {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config::config_file(REGISTRY_FILE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no settings directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.write())
+    }
+
+    pub fn find_by_path(&self, path: &Path) -> Option<&EngineEntry> {
+        self.engines.iter().find(|entry| entry.path == path)
+    }
+
+    /// Launch settings for `path`, using the registered entry when there is one.
+    pub fn config_for(&self, path: &Path) -> EngineConfig {
+        self.find_by_path(path)
+            .map_or_else(|| EngineConfig::new(path), EngineEntry::config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RegistryError> {
+        let mut registry = Self::default();
+        // Line of each `[engine]` header, for reporting incomplete entries.
+        let mut headers = Vec::new();
+        for (idx, raw) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "[engine]" {
+                registry.engines.push(EngineEntry::default());
+                headers.push(line_no);
+                continue;
+            }
+            let error = |message: &str| RegistryError {
+                line: line_no,
+                message: message.to_string(),
+            };
+            let Some(entry) = registry.engines.last_mut() else {
+                return Err(error("expected `[engine]`"));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected `key = value`"));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "name" => entry.name = value.to_string(),
+                "path" => entry.path = PathBuf::from(value),
+                "dir" => entry.working_dir = Some(PathBuf::from(value)),
+                _ => match key.strip_prefix("option ") {
+                    Some(name) => entry.set_option(name.trim(), value),
+                    None => return Err(error(&format!("unknown key `{key}`"))),
+                },
+            }
+        }
+
+        if let Some(idx) = registry
+            .engines
+            .iter()
+            .position(|entry| entry.path.as_os_str().is_empty())
+        {
+            return Err(RegistryError {
+                line: headers[idx],
+                message: "engine has no `path`".to_string(),
+            });
+        }
+        Ok(registry)
+    }
+
+    pub fn write(&self) -> String {
+        let mut out = String::from("# rshogi engine registry\n");
+        for entry in &self.engines {
+            out.push_str("\n[engine]\n");
+            out.push_str(&format!("name = {}\n", entry.name));
+            out.push_str(&format!("path = {}\n", entry.path.display()));
+            if let Some(dir) = &entry.working_dir {
+                out.push_str(&format!("dir = {}\n", dir.display()));
+            }
+            for (name, value) in &entry.options {
+                out.push_str(&format!("option {name} = {value}\n"));
+            }
+        }
+        out
+    }
+}
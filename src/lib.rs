@@ -0,0 +1,6 @@
+//! The parts of rshogi that work without a UI. The `rshogi` binary puts its
+//! front ends on top, and integration tests link against it directly.
+
+pub mod config;
+pub mod core;
+pub mod engine;
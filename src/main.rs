@@ -1,39 +1,48 @@
 #[cfg(feature = "ui-egui")]
 mod app;
-mod core;
+mod cli;
 #[cfg(feature = "ui-egui")]
 mod ui;
 #[cfg(feature = "ui-gpui")]
 mod ui_gpui;
 
-use shogi::bitboard::Factory as BBFactory;
+use std::process::ExitCode;
 
-fn main() {
-    BBFactory::init();
+#[cfg(feature = "ui-gpui")]
+use rshogi::config;
+use rshogi::{core, engine};
+
+fn main() -> ExitCode {
+    core::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        return code;
+    }
+
+    if cfg!(not(any(feature = "ui-egui", feature = "ui-gpui"))) {
+        eprintln!("no ui feature enabled. use --features ui-egui or --features ui-gpui");
+        eprintln!("run `rshogi help` for the command-line subcommands");
+        return ExitCode::FAILURE;
+    }
 
     #[cfg(all(feature = "ui-egui", not(feature = "ui-gpui")))]
     {
         run_egui();
-        return;
     }
 
     #[cfg(all(feature = "ui-gpui", not(feature = "ui-egui")))]
     {
         ui_gpui::run();
-        return;
     }
 
     #[cfg(all(feature = "ui-egui", feature = "ui-gpui"))]
     {
         eprintln!("both ui features are enabled; starting gpui by default");
         ui_gpui::run();
-        return;
     }
 
-    #[cfg(not(any(feature = "ui-egui", feature = "ui-gpui")))]
-    {
-        eprintln!("no ui feature enabled. use --features ui-egui or --features ui-gpui");
-    }
+    ExitCode::SUCCESS
 }
 
 #[cfg(feature = "ui-egui")]
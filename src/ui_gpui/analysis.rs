@@ -0,0 +1,411 @@
+use gpui::*;
+use gpui_component::{
+    ActiveTheme as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+use shogi::{Color, Move, Square};
+use std::path::PathBuf;
+
+use crate::core::usi;
+use crate::engine::{EngineCommand, GoParams, SearchInfo};
+
+use super::model::{BottomTab, DrawAnchor, EngineArrow, GpuiP1Shell};
+
+/// Score gap in centipawns at which candidate arrows reach their faintest.
+const ARROW_FADE_CP: i64 = 300;
+
+/// Infinite analysis of whatever position the board shows.
+pub(crate) struct Analysis {
+    engine_name: String,
+    ready: bool,
+    searching: bool,
+    /// Replies still owed for searches that were stopped: a `bestmove`, or a
+    /// stop timeout in its place.
+    stale_replies: usize,
+    /// `position` line of the current search.
+    position: Option<String>,
+    /// Analyzed position, for rendering the PV in Japanese.
+    sfen: String,
+    last_to: Option<Square>,
+    side_to_move: Color,
+    /// Candidate lines ordered by `multipv` rank.
+    pub(crate) lines: Vec<AnalysisLine>,
+}
+
+#[derive(Default)]
+pub(crate) struct AnalysisLine {
+    pub(crate) info: SearchInfo,
+    pub(crate) pv_japanese: Vec<String>,
+}
+
+/// Line counts offered in the analysis tab.
+const MULTIPV_CHOICES: [u32; 4] = [1, 2, 3, 5];
+
+impl Analysis {
+    fn new() -> Self {
+        Self {
+            engine_name: String::new(),
+            ready: false,
+            searching: false,
+            stale_replies: 0,
+            position: None,
+            sfen: String::new(),
+            last_to: None,
+            side_to_move: Color::Black,
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn is_analyzing(&self) -> bool {
+        self.analysis.is_some()
+    }
+
+    pub(crate) fn prompt_start_analysis(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Analyze with Engine".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update(cx, |shell, cx| {
+                shell.start_analysis(path, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    pub(crate) fn start_analysis(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.end_engine_game();
+        let mut config = self.engine_registry.config_for(&path);
+        config.options.retain(|(name, _)| name != "MultiPV");
+        config
+            .options
+            .push(("MultiPV".to_string(), self.analysis_multipv.to_string()));
+        self.start_engine(config, cx);
+        self.analysis = Some(Analysis::new());
+        self.bottom_tab = BottomTab::Analysis;
+    }
+
+    pub(crate) fn stop_analysis(&mut self) {
+        if self.analysis.take().is_some() {
+            self.stop_engine();
+            self.game.set_status("Analysis stopped");
+        }
+    }
+
+    pub(crate) fn on_analysis_ready(&mut self, engine_name: &str) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        analysis.ready = true;
+        analysis.engine_name = engine_name.to_string();
+        self.send_engine(EngineCommand::NewGame);
+        self.sync_analysis();
+    }
+
+    /// Restarts the search when the displayed position changed; returns
+    /// whether it did.
+    pub(crate) fn sync_analysis(&mut self) -> bool {
+        let Some(analysis) = &mut self.analysis else {
+            return false;
+        };
+        if !analysis.ready {
+            return false;
+        }
+        let position = usi::position_command(&self.game);
+        if analysis.position.as_deref() == Some(position.as_str()) {
+            return false;
+        }
+
+        let stop = std::mem::replace(&mut analysis.searching, true);
+        if stop {
+            analysis.stale_replies += 1;
+        }
+        analysis.position = Some(position.clone());
+        analysis.sfen = self.game.to_sfen();
+        analysis.last_to = self
+            .game
+            .current_ply()
+            .checked_sub(1)
+            .map(|idx| self.game.history()[idx].to());
+        analysis.side_to_move = self.game.side_to_move();
+        analysis.lines.clear();
+
+        if stop {
+            self.send_engine(EngineCommand::Stop);
+        }
+        self.send_engine(EngineCommand::Position(position));
+        self.send_engine(EngineCommand::Go(GoParams::infinite()));
+        true
+    }
+
+    pub(crate) fn on_analysis_info(&mut self, line: &str) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        if analysis.stale_replies > 0 {
+            return;
+        }
+        let info = SearchInfo::parse(line);
+        // Lines without `multipv` (e.g. periodic `nodes`/`nps`) belong to the best line.
+        let idx = info
+            .multipv
+            .map_or(0, |rank| rank.saturating_sub(1) as usize);
+        if idx >= self.analysis_multipv as usize {
+            return;
+        }
+        if analysis.lines.len() <= idx {
+            analysis.lines.resize_with(idx + 1, AnalysisLine::default);
+        }
+        let new_pv = !info.pv.is_empty();
+        let entry = &mut analysis.lines[idx];
+        entry.info.merge(info);
+        if new_pv {
+            entry.pv_japanese = usi::japanese_pv(&analysis.sfen, analysis.last_to, &entry.info.pv);
+        }
+    }
+
+    /// Changes the number of candidate lines and restarts the search.
+    pub(crate) fn set_analysis_multipv(&mut self, count: u32) {
+        if self.analysis_multipv == count {
+            return;
+        }
+        self.analysis_multipv = count;
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        if !analysis.ready {
+            return;
+        }
+        let stop = std::mem::take(&mut analysis.searching);
+        if stop {
+            analysis.stale_replies += 1;
+        }
+        // Forces `sync_analysis` to search again with the new option.
+        analysis.position = None;
+        if stop {
+            self.send_engine(EngineCommand::Stop);
+        }
+        self.send_engine(EngineCommand::SetOption {
+            name: "MultiPV".to_string(),
+            value: Some(count.to_string()),
+        });
+        self.sync_analysis();
+    }
+
+    /// First move of each candidate line, for the board overlay. Arrows fade
+    /// and thin out with the score gap to the best line.
+    pub(crate) fn engine_arrows(&self) -> Vec<EngineArrow> {
+        let Some(analysis) = &self.analysis else {
+            return Vec::new();
+        };
+        if analysis.position.as_deref() != Some(usi::position_command(&self.game).as_str()) {
+            return Vec::new();
+        }
+        let Some(best) = analysis.lines.first().and_then(|line| line.info.score) else {
+            return Vec::new();
+        };
+
+        let mut arrows = Vec::new();
+        for line in &analysis.lines {
+            let Some(mv) = line
+                .info
+                .pv
+                .first()
+                .and_then(|text| usi::parse_usi_move(text))
+            else {
+                continue;
+            };
+            let gap = line
+                .info
+                .score
+                .map_or(ARROW_FADE_CP, |score| best.sort_key() - score.sort_key())
+                .clamp(0, ARROW_FADE_CP);
+            let weight = 1.0 - gap as f32 / ARROW_FADE_CP as f32;
+            let (orig, dest) = match mv {
+                Move::Normal { from, to, .. } => (DrawAnchor::Board(from), DrawAnchor::Board(to)),
+                Move::Drop { to, piece_type } => (
+                    DrawAnchor::Hand {
+                        color: analysis.side_to_move,
+                        piece_type,
+                    },
+                    DrawAnchor::Board(to),
+                ),
+            };
+            arrows.push(EngineArrow {
+                orig,
+                dest,
+                alpha: 0.25 + 0.55 * weight,
+                scale: 0.45 + 0.55 * weight,
+            });
+        }
+        // Paint the best line last so it stays on top.
+        arrows.reverse();
+        arrows
+    }
+
+    /// A search ended, by `bestmove` or by a `stop` the engine never answered.
+    pub(crate) fn on_analysis_bestmove(&mut self) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        if analysis.stale_replies > 0 {
+            analysis.stale_replies -= 1;
+        } else {
+            analysis.searching = false;
+        }
+    }
+
+    /// Short engine state for the bottom panel.
+    pub(crate) fn engine_status_text(&self) -> String {
+        if let Some(analysis) = &self.analysis {
+            return match (analysis.ready, analysis.searching) {
+                (false, _) => "starting".to_string(),
+                (true, true) => format!("analyzing ({})", analysis.engine_name),
+                (true, false) => format!("analysis finished ({})", analysis.engine_name),
+            };
+        }
+        if self.engine_game.is_some() {
+            return if self.is_input_locked() {
+                "thinking".to_string()
+            } else {
+                "waiting".to_string()
+            };
+        }
+        if self.engine.is_some() {
+            "running".to_string()
+        } else {
+            "idle".to_string()
+        }
+    }
+
+    pub(crate) fn render_analysis_tab(&self, board: Entity<Self>, cx: &App) -> AnyElement {
+        let toggle_board = board.clone();
+        let toggle = if self.is_analyzing() {
+            Button::new("analysis-stop")
+                .small()
+                .ghost()
+                .label("Stop")
+                .on_click(move |_, _, cx| {
+                    toggle_board.update(cx, |board, cx| {
+                        board.stop_analysis();
+                        cx.notify();
+                    });
+                })
+        } else {
+            Button::new("analysis-start")
+                .small()
+                .primary()
+                .label("Analyze with Engine…")
+                .on_click(move |_, _, cx| {
+                    toggle_board.update(cx, |board, cx| board.prompt_start_analysis(cx));
+                })
+        };
+        let header = h_flex()
+            .items_center()
+            .gap_2()
+            .child(toggle)
+            .child(format!("engine: {}", self.engine_status_text()));
+
+        let Some(analysis) = &self.analysis else {
+            return v_flex()
+                .gap_2()
+                .child(header)
+                .child(
+                    div()
+                        .text_size(px(12.0))
+                        .text_color(cx.theme().muted_foreground)
+                        .child("Start an engine to analyze the displayed position."),
+                )
+                .into_any_element();
+        };
+
+        // USI scores are from the side to move; show them from Sente's side.
+        let score_text = |info: &SearchInfo| {
+            info.score.map_or_else(
+                || "-".to_string(),
+                |score| match analysis.side_to_move {
+                    Color::Black => score.to_string(),
+                    Color::White => score.flip().to_string(),
+                },
+            )
+        };
+        let best = analysis.lines.first().map(|line| &line.info);
+        let depth = match best.map(|info| (info.depth, info.seldepth)) {
+            Some((Some(depth), Some(seldepth))) => format!("{depth}/{seldepth}"),
+            Some((Some(depth), None)) => depth.to_string(),
+            _ => "-".to_string(),
+        };
+        let stats = format!(
+            "Depth {depth}  Nodes {}  NPS {}  Hash {}",
+            best.and_then(|info| info.nodes)
+                .map_or_else(|| "-".to_string(), format_count),
+            best.and_then(|info| info.nps)
+                .map_or_else(|| "-".to_string(), format_count),
+            best.and_then(|info| info.hashfull).map_or_else(
+                || "-".to_string(),
+                |permille| format!("{:.1}%", permille as f32 / 10.0)
+            ),
+        );
+
+        let mut multipv = h_flex().items_center().gap_1().child("Lines");
+        for count in MULTIPV_CHOICES {
+            let board = board.clone();
+            let button = Button::new(("analysis-multipv", count as usize))
+                .xsmall()
+                .label(count.to_string())
+                .on_click(move |_, _, cx| {
+                    board.update(cx, |board, cx| {
+                        board.set_analysis_multipv(count);
+                        cx.notify();
+                    });
+                });
+            multipv = multipv.child(if self.analysis_multipv == count {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let mut body = v_flex().gap_2().child(header.child(multipv)).child(stats);
+        for (rank, line) in analysis.lines.iter().enumerate() {
+            body = body.child(
+                v_flex()
+                    .child(div().text_size(px(12.0)).child(format!(
+                        "{}. {}  {}",
+                        rank + 1,
+                        score_text(&line.info),
+                        line.pv_japanese.join(" ")
+                    )))
+                    .child(
+                        div()
+                            .text_size(px(11.0))
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("USI: {}", line.info.pv.join(" "))),
+                    ),
+            );
+        }
+        body.into_any_element()
+    }
+}
+
+/// Compact count such as `950`, `12.3k` or `4.5M`.
+fn format_count(n: u64) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", n as f64 / 1e6),
+        _ => format!("{:.1}G", n as f64 / 1e9),
+    }
+}
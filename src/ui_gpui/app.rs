@@ -5,6 +5,7 @@ use std::path::PathBuf;
 
 use super::assets::CombinedAssets;
 use super::frame_root::FrameRoot;
+use super::keymap;
 use super::workspace::GpuiDockWorkspace;
 
 pub fn run() {
@@ -12,6 +13,7 @@ pub fn run() {
 
     app.run(|cx| {
         gpui_component::init(cx);
+        keymap::bind_keys(cx);
         if let Err(err) = ThemeRegistry::watch_dir(PathBuf::from("./themes"), cx, |_cx| {}) {
             eprintln!("Failed to watch themes directory: {err}");
         }
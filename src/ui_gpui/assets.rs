@@ -27,7 +27,7 @@ impl AssetSource for CombinedAssets {
         let fallback = gpui_component_assets::Assets;
         fallback
             .load(path)
-            .or_else(|_| Err(anyhow!("could not find asset at path \"{path}\"")))
+            .map_err(|_| anyhow!("could not find asset at path \"{path}\""))
     }
 
     fn list(&self, path: &str) -> Result<Vec<SharedString>> {
@@ -145,8 +145,8 @@ pub fn board_asset_path(board: BoardWallpaper) -> &'static str {
     }
 }
 
-pub fn piece_asset_path(piece: shogi::Piece, wallpaper: PieceWallpaper) -> String {
-    let prefix = if piece.color == shogi::Color::Black {
+pub fn piece_asset_path(piece: shogi::Piece, wallpaper: PieceWallpaper, flipped: bool) -> String {
+    let prefix = if (piece.color == shogi::Color::Black) != flipped {
         "0"
     } else {
         "1"
@@ -0,0 +1,410 @@
+use gpui::{prelude::FluentBuilder as _, *};
+use gpui_component::{
+    ActiveTheme as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+use shogi::{Color, Piece, PieceType, Square};
+
+use crate::core::game::{
+    GameState, START_SFEN, color_name, promoted_piece_type, unpromoted_piece_type,
+};
+use crate::core::notation::kanji_piece_name;
+use crate::core::sfen::{BoardSetup, HAND_PIECE_TYPES, PIECE_SET};
+
+use super::assets::piece_asset_path;
+use super::model::{BOARD_SIZE, GpuiP1Shell};
+
+const EDITOR_CELL_PX: f32 = 34.0;
+
+/// Pieces offered for placing, unpromoted first.
+const PALETTE: [PieceType; 14] = [
+    PieceType::King,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+    PieceType::ProRook,
+    PieceType::ProBishop,
+    PieceType::ProSilver,
+    PieceType::ProKnight,
+    PieceType::ProLance,
+    PieceType::ProPawn,
+];
+
+/// Open "Set Up Position" editor.
+pub(crate) struct BoardEditor {
+    pub(crate) setup: BoardSetup,
+    /// Piece a click on the board places; `None` erases.
+    pub(crate) brush: Option<Piece>,
+    /// Why the position was rejected.
+    pub(crate) error: Option<String>,
+}
+
+impl GpuiP1Shell {
+    /// Opens the editor on the displayed position.
+    pub(crate) fn open_board_editor(&mut self) {
+        self.drag = None;
+        self.game.clear_active_selection();
+        self.game.cancel_promotion();
+        self.board_editor = Some(BoardEditor {
+            setup: BoardSetup::from_position(self.game.position()),
+            brush: Some(Piece {
+                piece_type: PieceType::Pawn,
+                color: Color::Black,
+            }),
+            error: None,
+        });
+    }
+
+    /// Places the brush on `sq`; clicking a square that already holds the
+    /// brush piece empties it.
+    fn edit_square(&mut self, sq: Square) {
+        let Some(editor) = &mut self.board_editor else {
+            return;
+        };
+        let piece = if editor.setup.piece_at(sq) == editor.brush {
+            None
+        } else {
+            editor.brush
+        };
+        editor.setup.set_piece(sq, piece);
+        editor.error = None;
+    }
+
+    /// Promotes the piece on `sq`, or turns a promoted one back.
+    fn toggle_square_promotion(&mut self, sq: Square) {
+        let Some(editor) = &mut self.board_editor else {
+            return;
+        };
+        let Some(piece) = editor.setup.piece_at(sq) else {
+            return;
+        };
+        let base = unpromoted_piece_type(piece.piece_type);
+        let piece_type = if base == piece.piece_type {
+            promoted_piece_type(base)
+        } else {
+            base
+        };
+        editor.setup.set_piece(
+            sq,
+            Some(Piece {
+                piece_type,
+                ..piece
+            }),
+        );
+        editor.error = None;
+    }
+
+    fn adjust_hand(&mut self, color: Color, piece_type: PieceType, delta: i8) {
+        let Some(editor) = &mut self.board_editor else {
+            return;
+        };
+        let max = PIECE_SET
+            .iter()
+            .find(|(pt, _)| *pt == piece_type)
+            .map_or(0, |&(_, max)| max);
+        let count = editor.setup.hand(color, piece_type) as i8 + delta;
+        editor
+            .setup
+            .set_hand(color, piece_type, count.clamp(0, max as i8) as u8);
+        editor.error = None;
+    }
+
+    /// Starts a new game from the edited position once it passes validation.
+    fn commit_board_editor(&mut self) {
+        let Some(editor) = &mut self.board_editor else {
+            return;
+        };
+        editor.setup.set_move_number(1);
+        let game = editor
+            .setup
+            .validate()
+            .map_err(|err| err.to_string())
+            .and_then(|()| {
+                GameState::from_sfen(&editor.setup.to_sfen()).map_err(|err| err.to_string())
+            });
+        match game {
+            Ok(game) => {
+                self.board_editor = None;
+                self.replace_game(game);
+                self.game.set_status("Position set up");
+            }
+            Err(message) => editor.error = Some(message),
+        }
+    }
+
+    fn render_editor_piece(&self, piece: Option<Piece>) -> Option<AnyElement> {
+        piece.map(|piece| {
+            img(piece_asset_path(piece, self.piece_wallpaper, false))
+                .w_full()
+                .h_full()
+                .object_fit(ObjectFit::Contain)
+                .into_any_element()
+        })
+    }
+
+    pub(crate) fn render_board_editor_dialog(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(editor) = &self.board_editor else {
+            return div().into_any_element();
+        };
+
+        let mut grid = v_flex().border_1().border_color(cx.theme().border);
+        for rank in 0..BOARD_SIZE {
+            let mut row = h_flex();
+            for ui_col in 0..BOARD_SIZE {
+                let sq =
+                    Square::new(BOARD_SIZE - 1 - ui_col, rank).expect("valid board coordinate");
+                let index = (rank * BOARD_SIZE + ui_col) as usize;
+                row = row.child(
+                    div()
+                        .id(("editor-square", index))
+                        .w(px(EDITOR_CELL_PX))
+                        .h(px(EDITOR_CELL_PX))
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .bg(cx.theme().secondary)
+                        .children(self.render_editor_piece(editor.setup.piece_at(sq)))
+                        .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                            this.edit_square(sq);
+                            cx.notify();
+                        }))
+                        .on_mouse_down(
+                            MouseButton::Right,
+                            cx.listener(move |this, _: &MouseDownEvent, _, cx| {
+                                this.toggle_square_promotion(sq);
+                                cx.notify();
+                            }),
+                        ),
+                );
+            }
+            grid = grid.child(row);
+        }
+
+        let mut palette = v_flex().gap_1();
+        for color in [Color::Black, Color::White] {
+            let mut row = h_flex().flex_wrap().gap_0p5();
+            for (idx, piece_type) in PALETTE.into_iter().enumerate() {
+                let piece = Piece { piece_type, color };
+                let selected = editor.brush == Some(piece);
+                row = row.child(
+                    div()
+                        .id(("editor-palette", color as usize * PALETTE.len() + idx))
+                        .w(px(EDITOR_CELL_PX))
+                        .h(px(EDITOR_CELL_PX))
+                        .rounded_sm()
+                        .border_2()
+                        .border_color(if selected {
+                            cx.theme().primary
+                        } else {
+                            transparent_black()
+                        })
+                        .children(self.render_editor_piece(Some(piece)))
+                        .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                            if let Some(editor) = &mut this.board_editor {
+                                editor.brush = Some(piece);
+                            }
+                            cx.notify();
+                        })),
+                );
+            }
+            palette = palette.child(row);
+        }
+        let erase = Button::new("editor-erase")
+            .small()
+            .label("Erase")
+            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                if let Some(editor) = &mut this.board_editor {
+                    editor.brush = None;
+                }
+                cx.notify();
+            }));
+        palette = palette.child(if editor.brush.is_none() {
+            erase.primary()
+        } else {
+            erase.ghost()
+        });
+
+        let mut hands = v_flex().gap_1();
+        for color in [Color::Black, Color::White] {
+            let mut row = h_flex()
+                .flex_wrap()
+                .items_center()
+                .gap_1()
+                .child(div().w(px(48.0)).child(color_name(color)));
+            for (idx, piece_type) in HAND_PIECE_TYPES.into_iter().enumerate() {
+                let id = color as usize * HAND_PIECE_TYPES.len() + idx;
+                row = row.child(
+                    h_flex()
+                        .items_center()
+                        .child(
+                            Button::new(("editor-hand-less", id))
+                                .xsmall()
+                                .ghost()
+                                .label("−")
+                                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                                    this.adjust_hand(color, piece_type, -1);
+                                    cx.notify();
+                                })),
+                        )
+                        .child(format!(
+                            "{}{}",
+                            kanji_piece_name(piece_type),
+                            editor.setup.hand(color, piece_type)
+                        ))
+                        .child(
+                            Button::new(("editor-hand-more", id))
+                                .xsmall()
+                                .ghost()
+                                .label("+")
+                                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                                    this.adjust_hand(color, piece_type, 1);
+                                    cx.notify();
+                                })),
+                        ),
+                );
+            }
+            hands = hands.child(row);
+        }
+
+        let mut sides = h_flex().gap_2();
+        for color in [Color::Black, Color::White] {
+            let button = Button::new(("editor-side", color as usize))
+                .small()
+                .label(color_name(color))
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(editor) = &mut this.board_editor {
+                        editor.setup.set_side_to_move(color);
+                        editor.error = None;
+                    }
+                    cx.notify();
+                }));
+            sides = sides.child(if editor.setup.side_to_move() == color {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let card = v_flex()
+            .id("board-editor-card")
+            .w(px(760.0))
+            .gap_3()
+            .p_4()
+            .rounded_lg()
+            .border_1()
+            .border_color(cx.theme().border)
+            .bg(cx.theme().background)
+            .text_color(cx.theme().foreground)
+            .on_click(cx.listener(|_, _: &ClickEvent, _, cx| {
+                cx.stop_propagation();
+            }))
+            .child(div().text_size(px(15.0)).child("Set Up Position"))
+            .child(
+                div()
+                    .text_size(px(12.0))
+                    .text_color(cx.theme().muted_foreground)
+                    .child("Click to place the chosen piece, click it again to remove it, right-click to promote."),
+            )
+            .child(
+                h_flex()
+                    .gap_4()
+                    .items_start()
+                    .child(grid)
+                    .child(
+                        v_flex()
+                            .flex_1()
+                            .gap_2()
+                            .child("Pieces")
+                            .child(palette)
+                            .child("Hands")
+                            .child(hands)
+                            .child("To move")
+                            .child(sides),
+                    ),
+            )
+            .when_some(editor.error.clone(), |card, error| {
+                card.child(
+                    div()
+                        .text_size(px(12.0))
+                        .text_color(cx.theme().danger)
+                        .child(error),
+                )
+            })
+            .child(
+                h_flex()
+                    .justify_between()
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                Button::new("editor-start-position")
+                                    .ghost()
+                                    .label("Start Position")
+                                    .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                        if let Some(editor) = &mut this.board_editor
+                                            && let Ok(setup) = BoardSetup::from_sfen(START_SFEN)
+                                        {
+                                            editor.setup = setup;
+                                            editor.error = None;
+                                        }
+                                        cx.notify();
+                                    })),
+                            )
+                            .child(
+                                Button::new("editor-clear")
+                                    .ghost()
+                                    .label("Clear Board")
+                                    .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                        if let Some(editor) = &mut this.board_editor {
+                                            let side = editor.setup.side_to_move();
+                                            editor.setup = BoardSetup::empty();
+                                            editor.setup.set_side_to_move(side);
+                                            editor.error = None;
+                                        }
+                                        cx.notify();
+                                    })),
+                            ),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                Button::new("editor-cancel")
+                                    .ghost()
+                                    .label("Cancel")
+                                    .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                        this.board_editor = None;
+                                        cx.notify();
+                                    })),
+                            )
+                            .child(
+                                Button::new("editor-commit")
+                                    .primary()
+                                    .label("Set Up")
+                                    .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                        this.commit_board_editor();
+                                        this.play_pending_sound();
+                                        cx.notify();
+                                    })),
+                            ),
+                    ),
+            );
+
+        div()
+            .id("board-editor-overlay")
+            .absolute()
+            .left_0()
+            .top_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(hsla(0.0, 0.0, 0.0, 0.45))
+            .child(card)
+            .into_any_element()
+    }
+}
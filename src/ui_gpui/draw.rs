@@ -4,7 +4,7 @@ use std::f32::consts::TAU;
 
 use super::model::{
     BOARD_COORD_RIGHT_W, BOARD_SIZE, CELL_PX, DrawAnchor, DrawBrush, DrawCurrent, DrawShape,
-    GpuiP1Shell, HAND_COL_W, HAND_PIECES, SCENE_GAP_PX,
+    EngineArrow, GpuiP1Shell, HAND_COL_W, HAND_PIECES, SCENE_GAP_PX,
 };
 
 impl GpuiP1Shell {
@@ -49,17 +49,33 @@ impl GpuiP1Shell {
         self.draw_shapes.push(DrawShape { orig, dest, brush });
     }
 
-    pub(crate) fn square_from_ui(ui_row: u8, ui_col: u8) -> Square {
+    pub(crate) fn square_from_ui(ui_row: u8, ui_col: u8, flipped: bool) -> Square {
         // Match shogiground/egui sente view: files are rendered 9 -> 1 from left to right.
-        let file = BOARD_SIZE - 1 - ui_col;
-        let rank = ui_row;
+        // The gote view turns the board half a turn: 1 -> 9, rank 9 at the top.
+        let (file, rank) = if flipped {
+            (ui_col, BOARD_SIZE - 1 - ui_row)
+        } else {
+            (BOARD_SIZE - 1 - ui_col, ui_row)
+        };
         Square::new(file, rank).expect("valid board coordinate")
     }
 
-    pub(crate) fn ui_pos_from_square(sq: Square) -> (u8, u8) {
-        let row = sq.rank();
-        let col = BOARD_SIZE - 1 - sq.file();
-        (row, col)
+    pub(crate) fn ui_pos_from_square(sq: Square, flipped: bool) -> (u8, u8) {
+        if flipped {
+            (BOARD_SIZE - 1 - sq.rank(), sq.file())
+        } else {
+            (sq.rank(), BOARD_SIZE - 1 - sq.file())
+        }
+    }
+
+    /// Owners of the left and right hand columns: the player at the bottom
+    /// keeps their hand on the right.
+    pub(crate) fn hand_sides(flipped: bool) -> (Color, Color) {
+        if flipped {
+            (Color::Black, Color::White)
+        } else {
+            (Color::White, Color::Black)
+        }
     }
 
     pub(crate) fn scene_slot_h(board_px: f32) -> f32 {
@@ -78,10 +94,19 @@ impl GpuiP1Shell {
         SCENE_GAP_PX + BOARD_COORD_RIGHT_W
     }
 
-    pub(crate) fn anchor_to_scene_point(anchor: DrawAnchor, board_px: f32) -> Point<Pixels> {
+    /// Width of the board scene: both hands, the board and its coordinates.
+    pub(crate) fn scene_width(board_px: f32) -> f32 {
+        board_px + HAND_COL_W * 2.0 + SCENE_GAP_PX + Self::board_to_right_hand_gap()
+    }
+
+    pub(crate) fn anchor_to_scene_point(
+        anchor: DrawAnchor,
+        board_px: f32,
+        flipped: bool,
+    ) -> Point<Pixels> {
         match anchor {
             DrawAnchor::Board(sq) => {
-                let (ui_row, ui_col) = Self::ui_pos_from_square(sq);
+                let (ui_row, ui_col) = Self::ui_pos_from_square(sq, flipped);
                 point(
                     px(Self::board_left_x() + (ui_col as f32 + 0.5) * CELL_PX),
                     px((ui_row as f32 + 0.5) * CELL_PX),
@@ -93,7 +118,8 @@ impl GpuiP1Shell {
                     .iter()
                     .position(|&pt| pt == piece_type)
                     .unwrap_or(HAND_PIECES.len() - 1);
-                let x = if color == Color::White {
+                let (left_hand, _) = Self::hand_sides(flipped);
+                let x = if color == left_hand {
                     HAND_COL_W * 0.5
                 } else {
                     Self::right_hand_left_x(board_px) + HAND_COL_W * 0.5
@@ -108,13 +134,14 @@ impl GpuiP1Shell {
         let board_px = CELL_PX * BOARD_SIZE as f32;
         let x = local.x / px(1.0);
         let y = local.y / px(1.0);
+        let (left_hand, right_hand) = Self::hand_sides(self.flipped);
 
         if (0.0..board_px).contains(&y) {
             if (0.0..HAND_COL_W).contains(&x) {
                 let idx = ((y / Self::scene_slot_h(board_px)).floor() as usize)
                     .min(HAND_PIECES.len() - 1);
                 return Some(DrawAnchor::Hand {
-                    color: Color::White,
+                    color: left_hand,
                     piece_type: HAND_PIECES[idx],
                 });
             }
@@ -123,7 +150,11 @@ impl GpuiP1Shell {
             if (board_left..(board_left + board_px)).contains(&x) {
                 let ui_col = ((x - board_left) / CELL_PX).floor() as u8;
                 let ui_row = (y / CELL_PX).floor() as u8;
-                return Some(DrawAnchor::Board(Self::square_from_ui(ui_row, ui_col)));
+                return Some(DrawAnchor::Board(Self::square_from_ui(
+                    ui_row,
+                    ui_col,
+                    self.flipped,
+                )));
             }
 
             let right_left = Self::right_hand_left_x(board_px);
@@ -131,7 +162,7 @@ impl GpuiP1Shell {
                 let idx = ((y / Self::scene_slot_h(board_px)).floor() as usize)
                     .min(HAND_PIECES.len() - 1);
                 return Some(DrawAnchor::Hand {
-                    color: Color::Black,
+                    color: right_hand,
                     piece_type: HAND_PIECES[idx],
                 });
             }
@@ -154,11 +185,12 @@ impl GpuiP1Shell {
         window: &mut Window,
         shape: DrawShape,
         board_px: f32,
+        flipped: bool,
         current: bool,
         canvas_origin: Point<Pixels>,
     ) {
-        let orig_local = Self::anchor_to_scene_point(shape.orig, board_px);
-        let dest_local = Self::anchor_to_scene_point(shape.dest, board_px);
+        let orig_local = Self::anchor_to_scene_point(shape.orig, board_px, flipped);
+        let dest_local = Self::anchor_to_scene_point(shape.dest, board_px, flipped);
         let orig = point(
             orig_local.x + canvas_origin.x,
             orig_local.y + canvas_origin.y,
@@ -175,20 +207,42 @@ impl GpuiP1Shell {
         }
     }
 
+    pub(crate) fn paint_engine_arrow(
+        window: &mut Window,
+        arrow: EngineArrow,
+        board_px: f32,
+        flipped: bool,
+        canvas_origin: Point<Pixels>,
+    ) {
+        let orig_local = Self::anchor_to_scene_point(arrow.orig, board_px, flipped);
+        let dest_local = Self::anchor_to_scene_point(arrow.dest, board_px, flipped);
+        let orig = point(
+            orig_local.x + canvas_origin.x,
+            orig_local.y + canvas_origin.y,
+        );
+        let dest = point(
+            dest_local.x + canvas_origin.x,
+            dest_local.y + canvas_origin.y,
+        );
+        let color = hsla(0.58, 0.85, 0.42, arrow.alpha);
+        Self::paint_scaled_arrow(window, orig, dest, color, arrow.scale);
+    }
+
     pub(crate) fn paint_current_shape(
         window: &mut Window,
         current: DrawCurrent,
         board_px: f32,
+        flipped: bool,
         canvas_origin: Point<Pixels>,
     ) {
-        let orig_local = Self::anchor_to_scene_point(current.orig, board_px);
+        let orig_local = Self::anchor_to_scene_point(current.orig, board_px, flipped);
         let orig = point(
             orig_local.x + canvas_origin.x,
             orig_local.y + canvas_origin.y,
         );
         let color = Self::draw_brush_color(current.brush, 0.62);
         if let Some(dest_anchor) = current.dest {
-            let dest_local = Self::anchor_to_scene_point(dest_anchor, board_px);
+            let dest_local = Self::anchor_to_scene_point(dest_anchor, board_px, flipped);
             let dest = point(
                 dest_local.x + canvas_origin.x,
                 dest_local.y + canvas_origin.y,
@@ -235,6 +289,16 @@ impl GpuiP1Shell {
         start: Point<Pixels>,
         end: Point<Pixels>,
         color: Hsla,
+    ) {
+        Self::paint_scaled_arrow(window, start, end, color, 1.0);
+    }
+
+    fn paint_scaled_arrow(
+        window: &mut Window,
+        start: Point<Pixels>,
+        end: Point<Pixels>,
+        color: Hsla,
+        scale: f32,
     ) {
         let sx = start.x / px(1.0);
         let sy = start.y / px(1.0);
@@ -253,15 +317,15 @@ impl GpuiP1Shell {
         let line_end_x = ex - ux * margin;
         let line_end_y = ey - uy * margin;
 
-        let mut line = PathBuilder::stroke(px(CELL_PX * 0.10));
+        let mut line = PathBuilder::stroke(px(CELL_PX * 0.10 * scale));
         line.move_to(point(px(sx), px(sy)));
         line.line_to(point(px(line_end_x), px(line_end_y)));
         if let Ok(path) = line.build() {
             window.paint_path(path, color);
         }
 
-        let head_len = CELL_PX * 0.32;
-        let head_half_w = CELL_PX * 0.16;
+        let head_len = CELL_PX * 0.32 * scale.sqrt();
+        let head_half_w = CELL_PX * 0.16 * scale.sqrt();
         let base_x = line_end_x - ux * head_len;
         let base_y = line_end_y - uy * head_len;
         let perp_x = -uy;
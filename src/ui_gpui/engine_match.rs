@@ -0,0 +1,583 @@
+use gpui::*;
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex,
+    input::{Input, InputState},
+    v_flex,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::core::clock::TimeControl;
+use crate::core::record::RecordFormat;
+use crate::core::usi;
+use crate::engine::match_runner::{self, Adjudication};
+use crate::engine::{MatchEvent, MatchHandle, MatchSettings, MatchTally};
+
+use super::game_clock::CLOCK_PRESETS;
+use super::model::GpuiP1Shell;
+
+const MATCH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Open "Engine Match" dialog.
+pub(crate) struct MatchSetup {
+    /// Registry index of each engine.
+    engines: [Option<usize>; 2],
+    games: Entity<InputState>,
+    time: TimeControl,
+    alternate_colors: bool,
+    openings_path: Option<PathBuf>,
+    openings: Vec<String>,
+    /// Centipawns; empty disables score adjudication.
+    resign_cp: Entity<InputState>,
+    /// Plies; empty disables the move limit.
+    max_plies: Entity<InputState>,
+    save_dir: Option<PathBuf>,
+    format: RecordFormat,
+    error: Option<String>,
+}
+
+/// A running engine-vs-engine match, mirrored on the board.
+pub(crate) struct EngineMatch {
+    handle: MatchHandle,
+    names: [String; 2],
+    games: u32,
+    /// Game being played, counted from zero.
+    current: Option<u32>,
+    tally: MatchTally,
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn open_match_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let registered = self.engine_registry.engines.len();
+        let adjudication = Adjudication::default();
+        let max_plies = adjudication
+            .max_plies
+            .map_or_else(String::new, |plies| plies.to_string());
+        self.match_setup = Some(MatchSetup {
+            engines: [
+                (registered > 0).then_some(0),
+                (registered > 0).then_some(registered.min(2) - 1),
+            ],
+            games: cx.new(|cx| InputState::new(window, cx).default_value("10")),
+            time: CLOCK_PRESETS[0],
+            alternate_colors: true,
+            openings_path: None,
+            openings: Vec::new(),
+            resign_cp: cx.new(|cx| InputState::new(window, cx).default_value("")),
+            max_plies: cx.new(|cx| InputState::new(window, cx).default_value(max_plies)),
+            save_dir: None,
+            format: RecordFormat::Kif,
+            error: None,
+        });
+    }
+
+    pub(crate) fn is_match_running(&self) -> bool {
+        self.engine_match.is_some()
+    }
+
+    fn choose_match_openings(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Start Positions".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update(cx, |shell, cx| {
+                if let Some(setup) = &mut shell.match_setup {
+                    match match_runner::load_openings(&path) {
+                        Ok(openings) => {
+                            setup.openings = openings;
+                            setup.openings_path = Some(path);
+                            setup.error = None;
+                        }
+                        Err(err) => {
+                            setup.error = Some(format!("{}: {err}", path.display()));
+                        }
+                    }
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn choose_match_save_dir(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: Some("Save Games To".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let dir = paths.into_iter().next();
+            let _ = this.update(cx, |shell, cx| {
+                if let Some(setup) = &mut shell.match_setup {
+                    setup.save_dir = dir;
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Settings from the dialog, or a message naming the field to fix.
+    fn match_settings(&self, setup: &MatchSetup, cx: &App) -> Result<MatchSettings, String> {
+        let engine = |slot: Option<usize>| {
+            slot.and_then(|idx| self.engine_registry.engines.get(idx))
+                .cloned()
+                .ok_or_else(|| "Choose both engines".to_string())
+        };
+        let engines = [engine(setup.engines[0])?, engine(setup.engines[1])?];
+        let games = setup
+            .games
+            .read(cx)
+            .value()
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|games| *games > 0)
+            .ok_or_else(|| "Games must be a positive number".to_string())?;
+        let optional = |input: &Entity<InputState>, field: &str| {
+            let text = input.read(cx).value().trim().to_string();
+            if text.is_empty() {
+                return Ok(None);
+            }
+            text.parse::<u32>()
+                .map(Some)
+                .map_err(|_| format!("{field} must be a number or empty"))
+        };
+        let resign_cp = optional(&setup.resign_cp, "Resign score")?;
+        let max_plies = optional(&setup.max_plies, "Move limit")?;
+
+        Ok(MatchSettings {
+            engines,
+            games,
+            time: setup.time,
+            alternate_colors: setup.alternate_colors,
+            openings: setup.openings.clone(),
+            adjudication: Adjudication {
+                resign_cp: resign_cp.map(|cp| i32::try_from(cp).unwrap_or(i32::MAX)),
+                max_plies: max_plies.map(|plies| plies as usize),
+                ..Adjudication::default()
+            },
+            save_dir: setup.save_dir.clone(),
+            format: setup.format,
+        })
+    }
+
+    fn start_engine_match(&mut self, cx: &mut Context<Self>) {
+        let Some(setup) = &self.match_setup else {
+            return;
+        };
+        let settings = match self.match_settings(setup, cx) {
+            Ok(settings) => settings,
+            Err(message) => {
+                if let Some(setup) = &mut self.match_setup {
+                    setup.error = Some(message);
+                }
+                return;
+            }
+        };
+        self.match_setup = None;
+        self.stop_analysis();
+        self.end_engine_game();
+        self.stop_engine_match();
+
+        let names = settings.engines.each_ref().map(|entry| entry.name.clone());
+        let games = settings.games;
+        self.engine_match = Some(EngineMatch {
+            handle: MatchHandle::start(settings),
+            names,
+            games,
+            current: None,
+            tally: MatchTally::default(),
+        });
+        self.game.set_status("Starting engine match");
+        self.match_generation += 1;
+        let generation = self.match_generation;
+
+        cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor().timer(MATCH_POLL_INTERVAL).await;
+                let alive = this.update(cx, |shell, cx| {
+                    if shell.match_generation != generation {
+                        return false;
+                    }
+                    if shell.poll_engine_match() {
+                        cx.notify();
+                    }
+                    shell.engine_match.is_some()
+                });
+                if !matches!(alive, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Stops the running match; the board keeps the game in progress.
+    pub(crate) fn stop_engine_match(&mut self) {
+        if let Some(engine_match) = self.engine_match.take() {
+            engine_match.handle.stop();
+            self.game.set_status(format!(
+                "Match stopped after {} games: {}",
+                engine_match.tally.games(),
+                engine_match.tally
+            ));
+        }
+    }
+
+    fn poll_engine_match(&mut self) -> bool {
+        let mut changed = false;
+        while let Some(event) = self
+            .engine_match
+            .as_ref()
+            .and_then(|engine_match| engine_match.handle.try_recv())
+        {
+            self.handle_match_event(event);
+            changed = true;
+        }
+        changed
+    }
+
+    fn handle_match_event(&mut self, event: MatchEvent) {
+        let Some(engine_match) = &mut self.engine_match else {
+            return;
+        };
+        match event {
+            MatchEvent::Ready { names } => engine_match.names = names,
+            MatchEvent::GameStarted {
+                index,
+                black,
+                position,
+            } => {
+                engine_match.current = Some(index);
+                let black_name = engine_match.names[black].clone();
+                let white_name = engine_match.names[1 - black].clone();
+                let status = format!(
+                    "Match game {}/{}: {black_name} vs {white_name}",
+                    index + 1,
+                    engine_match.games
+                );
+                match usi::parse_position(&position) {
+                    Ok(mut game) => {
+                        let info = game.info_mut();
+                        info.black = Some(black_name);
+                        info.white = Some(white_name);
+                        self.set_game(game);
+                        self.game.set_status(status);
+                    }
+                    Err(err) => self
+                        .game
+                        .report_error(format!("Match start position: {err}")),
+                }
+            }
+            MatchEvent::Moved { mv, elapsed_ms, .. } => {
+                let end = self.game.history().len();
+                self.game.jump_to_ply(end);
+                if let Some(parsed) = usi::parse_usi_move(&mv)
+                    && self.game.play_move(parsed)
+                {
+                    let ply = self.game.current_ply();
+                    self.game
+                        .set_elapsed(ply, u32::try_from(elapsed_ms / 1_000).ok());
+                }
+            }
+            MatchEvent::GameFinished {
+                index,
+                result,
+                saved,
+                tally,
+            } => {
+                engine_match.tally = tally;
+                let end = self.game.history().len();
+                self.game.jump_to_ply(end);
+                self.game.set_result(Some(result));
+                let saved =
+                    saved.map_or_else(String::new, |path| format!(", saved {}", path.display()));
+                self.game
+                    .set_status(format!("Game {}: {result}{saved} — {tally}", index + 1));
+            }
+            MatchEvent::Error(message) => self.game.report_error(format!("Match: {message}")),
+            MatchEvent::Finished { tally, stopped } => {
+                let names = engine_match.names.clone();
+                self.engine_match = None;
+                let verb = if stopped { "stopped" } else { "finished" };
+                self.game.set_status(format!(
+                    "Match {verb}: {} vs {} {tally}",
+                    names[0], names[1]
+                ));
+            }
+        }
+        self.play_pending_sound();
+    }
+
+    /// Summary line for the console while a match runs.
+    pub(crate) fn match_status_text(&self) -> Option<String> {
+        let engine_match = self.engine_match.as_ref()?;
+        let progress = match engine_match.current {
+            Some(index) => format!("game {}/{}", index + 1, engine_match.games),
+            None => "starting engines".to_string(),
+        };
+        Some(format!(
+            "match: {} vs {} | {progress} | {}",
+            engine_match.names[0], engine_match.names[1], engine_match.tally
+        ))
+    }
+
+    pub(crate) fn render_match_dialog(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(setup) = &self.match_setup else {
+            return div().into_any_element();
+        };
+        let muted = cx.theme().muted_foreground;
+        let label = |text: &'static str| div().w(px(120.0)).text_size(px(12.0)).child(text);
+
+        let mut engine_rows = v_flex().gap_2();
+        for slot in 0..2 {
+            let mut choices = h_flex().gap_1().flex_wrap();
+            for (idx, entry) in self.engine_registry.engines.iter().enumerate() {
+                let button = Button::new(SharedString::from(format!("match-engine-{slot}-{idx}")))
+                    .xsmall()
+                    .label(entry.name.clone())
+                    .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                        if let Some(setup) = &mut this.match_setup {
+                            setup.engines[slot] = Some(idx);
+                        }
+                        cx.notify();
+                    }));
+                choices = choices.child(if setup.engines[slot] == Some(idx) {
+                    button.primary()
+                } else {
+                    button.ghost()
+                });
+            }
+            engine_rows = engine_rows.child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label(if slot == 0 { "Engine 1" } else { "Engine 2" }))
+                    .child(choices),
+            );
+        }
+        if self.engine_registry.engines.is_empty() {
+            engine_rows = engine_rows.child(
+                div()
+                    .text_size(px(12.0))
+                    .text_color(muted)
+                    .child("Register engines in Tools → Engine Settings first."),
+            );
+        }
+
+        let mut times = h_flex().gap_1().flex_wrap();
+        for (idx, time) in CLOCK_PRESETS.into_iter().enumerate() {
+            let button = Button::new(("match-time", idx))
+                .xsmall()
+                .label(time.label())
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.match_setup {
+                        setup.time = time;
+                    }
+                    cx.notify();
+                }));
+            times = times.child(if setup.time == time {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let alternate = Button::new("match-alternate")
+            .xsmall()
+            .label(if setup.alternate_colors { "On" } else { "Off" })
+            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                if let Some(setup) = &mut this.match_setup {
+                    setup.alternate_colors = !setup.alternate_colors;
+                }
+                cx.notify();
+            }));
+        let alternate = if setup.alternate_colors {
+            alternate.primary()
+        } else {
+            alternate.ghost()
+        };
+
+        let openings_label = match &setup.openings_path {
+            Some(path) => format!("{} ({} positions)", path.display(), setup.openings.len()),
+            None => "Initial position".to_string(),
+        };
+        let save_label = setup
+            .save_dir
+            .as_ref()
+            .map_or_else(|| "Not saved".to_string(), |dir| dir.display().to_string());
+        let mut formats = h_flex().gap_1();
+        for (idx, (format, name)) in [(RecordFormat::Kif, "KIF"), (RecordFormat::Csa, "CSA")]
+            .into_iter()
+            .enumerate()
+        {
+            let button = Button::new(("match-format", idx))
+                .xsmall()
+                .label(name)
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.match_setup {
+                        setup.format = format;
+                    }
+                    cx.notify();
+                }));
+            formats = formats.child(if setup.format == format {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+        let path_text = |text: String| {
+            div()
+                .flex_1()
+                .text_size(px(12.0))
+                .text_color(muted)
+                .overflow_hidden()
+                .child(text)
+        };
+
+        let card = v_flex()
+            .id("match-card")
+            .w(px(560.0))
+            .gap_3()
+            .p_4()
+            .rounded_lg()
+            .border_1()
+            .border_color(cx.theme().border)
+            .bg(cx.theme().background)
+            .text_color(cx.theme().foreground)
+            .on_click(cx.listener(|_, _: &ClickEvent, _, cx| {
+                cx.stop_propagation();
+            }))
+            .child(div().text_size(px(15.0)).child("Engine Match"))
+            .child(engine_rows)
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label("Games"))
+                    .child(div().w(px(90.0)).child(Input::new(&setup.games)))
+                    .child(label("Alternate colors"))
+                    .child(alternate),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label("Time"))
+                    .child(times),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label("Start positions"))
+                    .child(path_text(openings_label))
+                    .child(
+                        Button::new("match-openings-clear")
+                            .xsmall()
+                            .ghost()
+                            .label("Clear")
+                            .disabled(setup.openings_path.is_none())
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                if let Some(setup) = &mut this.match_setup {
+                                    setup.openings_path = None;
+                                    setup.openings.clear();
+                                }
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("match-openings")
+                            .xsmall()
+                            .label("Choose…")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.choose_match_openings(cx);
+                            })),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label("Resign at (cp)"))
+                    .child(div().w(px(90.0)).child(Input::new(&setup.resign_cp)))
+                    .child(label("Draw after plies"))
+                    .child(div().w(px(90.0)).child(Input::new(&setup.max_plies))),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(label("Save games to"))
+                    .child(path_text(save_label))
+                    .child(formats)
+                    .child(
+                        Button::new("match-save-dir")
+                            .xsmall()
+                            .label("Choose…")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.choose_match_save_dir(cx);
+                            })),
+                    ),
+            )
+            .children(setup.error.clone().map(|error| {
+                div()
+                    .text_size(px(12.0))
+                    .text_color(cx.theme().danger)
+                    .child(error)
+            }))
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("match-cancel")
+                            .ghost()
+                            .label("Cancel")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.match_setup = None;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("match-start")
+                            .primary()
+                            .label("Start")
+                            .disabled(self.engine_registry.engines.is_empty())
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.start_engine_match(cx);
+                                cx.notify();
+                            })),
+                    ),
+            );
+
+        div()
+            .id("match-overlay")
+            .absolute()
+            .left_0()
+            .top_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(hsla(0.0, 0.0, 0.0, 0.45))
+            .child(card)
+            .into_any_element()
+    }
+}
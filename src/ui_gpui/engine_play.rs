@@ -0,0 +1,525 @@
+use gpui::{prelude::FluentBuilder as _, *};
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+use shogi::Color;
+use std::path::PathBuf;
+
+use crate::core::clock::TimeControl;
+use crate::core::game::{EndReason, GameState, ImpasseRule, SoundCue, color_name};
+use crate::core::handicap::{self, HANDICAPS, Handicap};
+use crate::core::usi;
+use crate::engine::protocol::GameOutcome;
+use crate::engine::{EngineCommand, GoParams};
+
+use super::game_clock::CLOCK_PRESETS;
+use super::model::GpuiP1Shell;
+
+/// How long the engine may think about each move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EngineLimit {
+    /// Milliseconds per move, sent as `byoyomi`.
+    MoveTime(u64),
+    Depth(u32),
+}
+
+impl EngineLimit {
+    pub(crate) const PRESETS: [Self; 6] = [
+        Self::MoveTime(1_000),
+        Self::MoveTime(3_000),
+        Self::MoveTime(10_000),
+        Self::Depth(4),
+        Self::Depth(8),
+        Self::Depth(12),
+    ];
+
+    pub(crate) fn label(self) -> String {
+        match self {
+            Self::MoveTime(ms) => format!("{} s / move", ms / 1_000),
+            Self::Depth(depth) => format!("Depth {depth}"),
+        }
+    }
+
+    pub(crate) fn go_params(self) -> GoParams {
+        match self {
+            Self::MoveTime(ms) => GoParams {
+                btime: Some(0),
+                wtime: Some(0),
+                byoyomi: Some(ms),
+                ..GoParams::default()
+            },
+            Self::Depth(depth) => GoParams {
+                depth: Some(depth),
+                ..GoParams::default()
+            },
+        }
+    }
+}
+
+/// Choices made in the "New Game vs Engine" dialog.
+pub(crate) struct EngineGameSetup {
+    pub(crate) engine_path: Option<PathBuf>,
+    pub(crate) human: Color,
+    pub(crate) limit: EngineLimit,
+    /// With a clock, the engine searches on its remaining time instead of `limit`.
+    pub(crate) clock: Option<TimeControl>,
+    pub(crate) handicap: Handicap,
+}
+
+impl Default for EngineGameSetup {
+    fn default() -> Self {
+        Self {
+            engine_path: None,
+            human: Color::Black,
+            limit: EngineLimit::MoveTime(3_000),
+            clock: None,
+            handicap: handicap::EVEN,
+        }
+    }
+}
+
+/// A running human-vs-engine game.
+pub(crate) struct EngineGame {
+    pub(crate) human: Color,
+    pub(crate) limit: EngineLimit,
+    ready: bool,
+    /// Ply at which the outstanding search was requested.
+    search_ply: Option<usize>,
+    /// Replies still owed for searches that were abandoned: a `bestmove`,
+    /// or a stop timeout in its place.
+    stale_replies: usize,
+    /// `gameover` was sent, so the next search needs `usinewgame` first.
+    reported_result: bool,
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn open_engine_game_dialog(&mut self) {
+        self.engine_setup = Some(EngineGameSetup {
+            engine_path: self
+                .engine_registry
+                .engines
+                .first()
+                .map(|entry| entry.path.clone()),
+            ..EngineGameSetup::default()
+        });
+    }
+
+    /// Board input is ignored while the engine is to move or a match runs.
+    pub(crate) fn is_input_locked(&self) -> bool {
+        self.is_match_running()
+            || self.is_tsume_locked()
+            || self.engine_game.as_ref().is_some_and(|engine_game| {
+                self.game.side_to_move() != engine_game.human && !self.game.is_game_over()
+            })
+    }
+
+    /// Plays the cue for board input and lets the engine answer a completed move.
+    pub(crate) fn after_board_input(&mut self, ply_before: usize) {
+        self.play_pending_sound();
+        if self.game.current_ply() == ply_before + 1 {
+            self.press_clock();
+            self.on_move_played();
+        }
+    }
+
+    pub(crate) fn end_engine_game(&mut self) {
+        if self.engine_game.take().is_some() {
+            self.stop_engine();
+        }
+    }
+
+    fn choose_engine_path(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Choose Engine".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update(cx, |shell, cx| {
+                if let Some(setup) = &mut shell.engine_setup {
+                    setup.engine_path = Some(path);
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn start_engine_game(&mut self, cx: &mut Context<Self>) {
+        let Some(setup) = self.engine_setup.take() else {
+            return;
+        };
+        let Some(path) = setup.engine_path else {
+            return;
+        };
+        self.stop_analysis();
+        let engine_name = match self.engine_registry.find_by_path(&path) {
+            Some(entry) if !entry.name.is_empty() => entry.name.clone(),
+            _ => path.file_stem().map_or_else(
+                || "Engine".to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            ),
+        };
+
+        let mut game =
+            GameState::from_sfen(setup.handicap.sfen).unwrap_or_else(|_| GameState::new());
+        let info = game.info_mut();
+        let (human_slot, engine_slot) = match setup.human {
+            Color::Black => (&mut info.black, &mut info.white),
+            Color::White => (&mut info.white, &mut info.black),
+        };
+        *human_slot = Some("You".to_string());
+        *engine_slot = Some(engine_name);
+        self.replace_game(game);
+        // Sit the human at the bottom of the board.
+        self.flipped = setup.human == Color::White;
+        if let Some(control) = setup.clock {
+            self.set_clock(control, cx);
+        }
+
+        let config = self.engine_registry.config_for(&path);
+        self.start_engine(config, cx);
+        self.engine_game = Some(EngineGame {
+            human: setup.human,
+            limit: setup.limit,
+            ready: false,
+            search_ply: None,
+            stale_replies: 0,
+            reported_result: false,
+        });
+        self.game.set_status(format!(
+            "Playing {} against the engine",
+            color_name(setup.human)
+        ));
+    }
+
+    /// Called after the human's board input played a move.
+    fn on_move_played(&mut self) {
+        if self.tsume_trainer.is_some() {
+            self.on_tsume_move();
+            return;
+        }
+        if self.game.is_game_over() {
+            self.report_engine_result();
+        } else {
+            self.request_engine_move();
+        }
+    }
+
+    pub(crate) fn on_engine_ready(&mut self) {
+        let Some(engine_game) = &mut self.engine_game else {
+            return;
+        };
+        engine_game.ready = true;
+        self.send_engine(EngineCommand::NewGame);
+        self.resume_clock();
+        self.request_engine_move();
+    }
+
+    /// Starts a search when the engine is to move and none is pending.
+    pub(crate) fn request_engine_move(&mut self) {
+        let game_over = self.game.is_game_over();
+        let side_to_move = self.game.side_to_move();
+        let Some(engine_game) = &mut self.engine_game else {
+            return;
+        };
+        if !engine_game.ready
+            || engine_game.search_ply.is_some()
+            || side_to_move == engine_game.human
+            || game_over
+        {
+            return;
+        }
+        engine_game.search_ply = Some(self.game.current_ply());
+        let new_game = std::mem::take(&mut engine_game.reported_result);
+        let params = match &self.clock {
+            Some(clock) => GoParams::for_clock(clock),
+            None => engine_game.limit.go_params(),
+        };
+
+        if new_game {
+            self.send_engine(EngineCommand::NewGame);
+        }
+        self.send_engine(EngineCommand::Position(usi::position_command(&self.game)));
+        self.send_engine(EngineCommand::Go(params));
+        self.game.set_status("Engine is thinking");
+    }
+
+    /// Abandons the outstanding search, e.g. when moves are taken back.
+    pub(crate) fn cancel_engine_search(&mut self) {
+        let Some(engine_game) = &mut self.engine_game else {
+            return;
+        };
+        if engine_game.search_ply.take().is_some() {
+            engine_game.stale_replies += 1;
+            self.send_engine(EngineCommand::Stop);
+        }
+    }
+
+    /// The engine never answered `stop`; the cancelled search is over anyway.
+    pub(crate) fn on_engine_stop_timed_out(&mut self) {
+        if let Some(engine_game) = &mut self.engine_game {
+            engine_game.stale_replies = engine_game.stale_replies.saturating_sub(1);
+        }
+    }
+
+    pub(crate) fn on_engine_bestmove(&mut self, mv: &str) {
+        let Some(engine_game) = &mut self.engine_game else {
+            return;
+        };
+        if engine_game.stale_replies > 0 {
+            engine_game.stale_replies -= 1;
+            return;
+        }
+        if engine_game.search_ply.take() != Some(self.game.current_ply()) {
+            return;
+        }
+        let human = engine_game.human;
+        let engine_color = human.flip();
+
+        match mv {
+            "resign" => self.game.resign(engine_color),
+            "win" => self.game.declare_impasse(ImpasseRule::TwentySevenPoint),
+            _ => {
+                let played =
+                    usi::parse_usi_move(mv).is_some_and(|parsed| self.game.play_move(parsed));
+                if !played {
+                    self.game.forfeit(engine_color, EndReason::IllegalMove);
+                    self.game
+                        .report_error(format!("Engine played an illegal move `{mv}`"));
+                } else if !self.press_clock() {
+                    // The flag fell first; the loss is already reported.
+                    return;
+                }
+            }
+        }
+
+        if self.game.is_game_over() {
+            if let Some(result) = self.game.result() {
+                // Results are announced from the human's side of the board.
+                let _ = self.game.take_pending_sound();
+                self.sound.play(SoundCue::for_result(result, human));
+            }
+            self.report_engine_result();
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn report_engine_result(&mut self) {
+        let Some(result) = self.game.result() else {
+            return;
+        };
+        let Some(engine_game) = &mut self.engine_game else {
+            return;
+        };
+        if engine_game.reported_result {
+            return;
+        }
+        engine_game.reported_result = true;
+        let engine_color = engine_game.human.flip();
+        let outcome = match result.winner {
+            None => GameOutcome::Draw,
+            Some(winner) if winner == engine_color => GameOutcome::Win,
+            Some(_) => GameOutcome::Lose,
+        };
+        self.send_engine(EngineCommand::GameOver(outcome));
+    }
+
+    pub(crate) fn render_engine_game_dialog(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(setup) = &self.engine_setup else {
+            return div().into_any_element();
+        };
+
+        let engine_label = setup.engine_path.as_ref().map_or_else(
+            || "No engine selected".to_string(),
+            |path| path.display().to_string(),
+        );
+        let mut registered = h_flex().gap_2().flex_wrap();
+        for (idx, entry) in self.engine_registry.engines.iter().enumerate() {
+            let path = entry.path.clone();
+            let selected = setup.engine_path.as_ref() == Some(&path);
+            let button = Button::new(("engine-registered", idx))
+                .small()
+                .label(entry.name.clone())
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.engine_setup {
+                        setup.engine_path = Some(path.clone());
+                    }
+                    cx.notify();
+                }));
+            registered = registered.child(if selected {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+        let mut sides = h_flex().gap_2();
+        for color in [Color::Black, Color::White] {
+            let button = Button::new(("engine-side", color as usize))
+                .small()
+                .label(color_name(color))
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.engine_setup {
+                        setup.human = color;
+                    }
+                    cx.notify();
+                }));
+            sides = sides.child(if setup.human == color {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+        let mut limits = h_flex().gap_2().flex_wrap();
+        for (idx, limit) in EngineLimit::PRESETS.into_iter().enumerate() {
+            let button = Button::new(("engine-limit", idx))
+                .small()
+                .label(limit.label())
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.engine_setup {
+                        setup.limit = limit;
+                    }
+                    cx.notify();
+                }));
+            limits = limits.child(if setup.limit == limit {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let mut handicaps = h_flex().gap_2().flex_wrap();
+        for (idx, handicap) in HANDICAPS.into_iter().enumerate() {
+            let button = Button::new(("engine-handicap", idx))
+                .small()
+                .label(handicap.name)
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.engine_setup {
+                        setup.handicap = handicap;
+                    }
+                    cx.notify();
+                }));
+            handicaps = handicaps.child(if setup.handicap == handicap {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let mut clocks = h_flex().gap_2().flex_wrap();
+        let presets = std::iter::once(None).chain(CLOCK_PRESETS.into_iter().map(Some));
+        for (idx, control) in presets.enumerate() {
+            let label = control.map_or_else(|| "No clock".to_string(), TimeControl::label);
+            let button = Button::new(("engine-clock", idx))
+                .small()
+                .label(label)
+                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                    if let Some(setup) = &mut this.engine_setup {
+                        setup.clock = control;
+                    }
+                    cx.notify();
+                }));
+            clocks = clocks.child(if setup.clock == control {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        let card = v_flex()
+            .id("engine-game-card")
+            .w(px(420.0))
+            .gap_3()
+            .p_4()
+            .rounded_lg()
+            .border_1()
+            .border_color(cx.theme().border)
+            .bg(cx.theme().background)
+            .text_color(cx.theme().foreground)
+            .on_click(cx.listener(|_, _: &ClickEvent, _, cx| {
+                cx.stop_propagation();
+            }))
+            .child(div().text_size(px(15.0)).child("New Game vs Engine"))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .justify_between()
+                    .child(
+                        div()
+                            .text_size(px(12.0))
+                            .text_color(cx.theme().muted_foreground)
+                            .overflow_hidden()
+                            .child(engine_label),
+                    )
+                    .child(
+                        Button::new("engine-choose")
+                            .small()
+                            .label("Choose…")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.choose_engine_path(cx);
+                            })),
+                    ),
+            )
+            .child(registered)
+            .child("You play")
+            .child(sides)
+            .child("Handicap (Gote gives and moves first)")
+            .child(handicaps)
+            .child("Clock")
+            .child(clocks)
+            .when(setup.clock.is_none(), |card| {
+                card.child("Engine limit").child(limits)
+            })
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("engine-cancel")
+                            .ghost()
+                            .label("Cancel")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.engine_setup = None;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("engine-start")
+                            .primary()
+                            .label("Start")
+                            .disabled(setup.engine_path.is_none())
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.start_engine_game(cx);
+                                cx.notify();
+                            })),
+                    ),
+            );
+
+        div()
+            .id("engine-game-overlay")
+            .absolute()
+            .left_0()
+            .top_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(hsla(0.0, 0.0, 0.0, 0.45))
+            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                this.engine_setup = None;
+                cx.notify();
+            }))
+            .child(card)
+            .into_any_element()
+    }
+}
@@ -0,0 +1,631 @@
+use gpui::*;
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex,
+    input::{Input, InputState},
+    v_flex,
+};
+use std::path::PathBuf;
+
+use crate::engine::protocol::{EngineId, OptionKind, UsiOption};
+use crate::engine::{EngineConfig, EngineEntry, EngineHandle, EngineRegistry};
+
+use super::model::GpuiP1Shell;
+
+/// Open "Engine Settings" dialog, editing a copy of the registry.
+pub(crate) struct EngineSettings {
+    registry: EngineRegistry,
+    selected: Option<usize>,
+    name_input: Option<Entity<InputState>>,
+    /// Controls built from the selected engine's `option` declarations.
+    fields: Vec<OptionField>,
+    probe: ProbeState,
+    /// Bumped on every probe so late replies for another engine are dropped.
+    probe_generation: u64,
+}
+
+enum ProbeState {
+    Idle,
+    Running,
+    Done,
+    Failed(String),
+}
+
+struct OptionField {
+    option: UsiOption,
+    /// Current value for check and combo options.
+    value: String,
+    /// Text entry for spin, string and filename options.
+    input: Option<Entity<InputState>>,
+}
+
+impl OptionField {
+    fn value(&self, cx: &App) -> String {
+        match &self.input {
+            Some(input) => input.read(cx).value().trim().to_string(),
+            None => self.value.clone(),
+        }
+    }
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn open_engine_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.engine_settings = Some(EngineSettings {
+            registry: self.engine_registry.clone(),
+            selected: None,
+            name_input: None,
+            fields: Vec::new(),
+            probe: ProbeState::Idle,
+            probe_generation: 0,
+        });
+        if !self.engine_registry.engines.is_empty() {
+            self.select_registered_engine(0, window, cx);
+        }
+    }
+
+    fn select_registered_engine(
+        &mut self,
+        idx: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.commit_engine_fields(cx);
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        let Some(entry) = settings.registry.engines.get(idx) else {
+            return;
+        };
+        let name = entry.name.clone();
+        settings.selected = Some(idx);
+        settings.name_input = Some(cx.new(|cx| InputState::new(window, cx).default_value(name)));
+        settings.fields.clear();
+        self.probe_selected_engine(window, cx);
+    }
+
+    /// Starts the selected engine in the background to learn its options.
+    fn probe_selected_engine(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        let Some(entry) = settings
+            .selected
+            .and_then(|idx| settings.registry.engines.get(idx))
+        else {
+            return;
+        };
+        // Saved values are left out, so a bad value cannot stop the engine from starting.
+        let config = EngineConfig {
+            working_dir: entry.working_dir.clone(),
+            ..EngineConfig::new(&entry.path)
+        };
+        settings.probe = ProbeState::Running;
+        settings.probe_generation += 1;
+        let generation = settings.probe_generation;
+
+        cx.spawn_in(window, async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move { EngineHandle::probe(config) })
+                .await;
+            let _ = this.update_in(cx, |shell, window, cx| {
+                shell.on_engine_probed(generation, result, window, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn on_engine_probed(
+        &mut self,
+        generation: u64,
+        result: Result<EngineId, String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        if settings.probe_generation != generation {
+            return;
+        }
+        let id = match result {
+            Ok(id) => id,
+            Err(message) => {
+                settings.probe = ProbeState::Failed(message);
+                return;
+            }
+        };
+        let Some(entry) = settings
+            .selected
+            .and_then(|idx| settings.registry.engines.get_mut(idx))
+        else {
+            return;
+        };
+        if entry.name.is_empty() && !id.name.is_empty() {
+            entry.name = id.name.clone();
+            let name = id.name.clone();
+            settings.name_input =
+                Some(cx.new(|cx| InputState::new(window, cx).default_value(name)));
+        }
+
+        settings.fields = id
+            .options
+            .into_iter()
+            .map(|option| {
+                let value = entry
+                    .option(&option.name)
+                    .map(str::to_string)
+                    .or_else(|| option.default.clone())
+                    .unwrap_or_default();
+                let input = match option.kind {
+                    OptionKind::Spin | OptionKind::String | OptionKind::Filename => {
+                        let text = value.clone();
+                        Some(cx.new(|cx| InputState::new(window, cx).default_value(text)))
+                    }
+                    OptionKind::Check | OptionKind::Combo | OptionKind::Button => None,
+                };
+                OptionField {
+                    option,
+                    value,
+                    input,
+                }
+            })
+            .collect();
+        settings.probe = ProbeState::Done;
+    }
+
+    /// Copies the dialog's controls into the selected registry entry.
+    fn commit_engine_fields(&mut self, cx: &App) {
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        let Some(entry) = settings
+            .selected
+            .and_then(|idx| settings.registry.engines.get_mut(idx))
+        else {
+            return;
+        };
+        if let Some(input) = &settings.name_input {
+            let name = input.read(cx).value().trim().to_string();
+            if !name.is_empty() {
+                entry.name = name;
+            }
+        }
+        // Without a successful probe the saved values are kept as they are.
+        if !matches!(settings.probe, ProbeState::Done) {
+            return;
+        }
+        for field in &settings.fields {
+            let option = &field.option;
+            if option.kind == OptionKind::Button {
+                continue;
+            }
+            let mut value = field.value(cx);
+            if option.kind == OptionKind::Spin {
+                let Ok(number) = value.parse::<i64>() else {
+                    continue;
+                };
+                let number = number
+                    .max(option.min.unwrap_or(i64::MIN))
+                    .min(option.max.unwrap_or(i64::MAX));
+                value = number.to_string();
+            }
+            if option.default.as_deref() == Some(value.as_str()) {
+                entry.remove_option(&option.name);
+            } else {
+                entry.set_option(&option.name, value);
+            }
+        }
+    }
+
+    fn add_registered_engine(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Add Engine".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update_in(cx, |shell, window, cx| {
+                shell.commit_engine_fields(cx);
+                let Some(settings) = &mut shell.engine_settings else {
+                    return;
+                };
+                // The engine's `id name` replaces this once it has been probed.
+                settings.registry.engines.push(EngineEntry::new("", path));
+                let idx = settings.registry.engines.len() - 1;
+                settings.selected = None;
+                shell.select_registered_engine(idx, window, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn remove_registered_engine(&mut self) {
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        let Some(idx) = settings.selected.take() else {
+            return;
+        };
+        settings.registry.engines.remove(idx);
+        settings.name_input = None;
+        settings.fields.clear();
+        settings.probe = ProbeState::Idle;
+        settings.probe_generation += 1;
+    }
+
+    fn choose_engine_working_dir(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: Some("Working Directory".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let dir = paths.into_iter().next();
+            let _ = this.update_in(cx, |shell, window, cx| {
+                shell.set_engine_working_dir(dir, window, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn set_engine_working_dir(
+        &mut self,
+        dir: Option<PathBuf>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.commit_engine_fields(cx);
+        let Some(settings) = &mut self.engine_settings else {
+            return;
+        };
+        let Some(entry) = settings
+            .selected
+            .and_then(|idx| settings.registry.engines.get_mut(idx))
+        else {
+            return;
+        };
+        entry.working_dir = dir;
+        self.probe_selected_engine(window, cx);
+    }
+
+    fn save_engine_settings(&mut self, cx: &App) {
+        self.commit_engine_fields(cx);
+        let Some(settings) = self.engine_settings.take() else {
+            return;
+        };
+        self.engine_registry = settings.registry;
+        match self.engine_registry.save() {
+            Ok(()) => self.game.set_status("Engine settings saved"),
+            Err(err) => self
+                .game
+                .report_error(format!("Saving engine settings failed: {err}")),
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn render_engine_settings_dialog(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(settings) = &self.engine_settings else {
+            return div().into_any_element();
+        };
+
+        let mut engine_list = v_flex().w(px(170.0)).gap_1();
+        for (idx, entry) in settings.registry.engines.iter().enumerate() {
+            let label = if entry.name.is_empty() {
+                entry
+                    .path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+            } else {
+                entry.name.clone()
+            };
+            let button = Button::new(("engine-entry", idx))
+                .small()
+                .label(label)
+                .on_click(cx.listener(move |this, _: &ClickEvent, window, cx| {
+                    this.select_registered_engine(idx, window, cx);
+                    cx.notify();
+                }));
+            engine_list = engine_list.child(if settings.selected == Some(idx) {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+        engine_list = engine_list.child(
+            h_flex()
+                .gap_1()
+                .child(
+                    Button::new("engine-add")
+                        .small()
+                        .label("Add…")
+                        .on_click(cx.listener(|this, _: &ClickEvent, window, cx| {
+                            this.add_registered_engine(window, cx);
+                        })),
+                )
+                .child(
+                    Button::new("engine-remove")
+                        .small()
+                        .ghost()
+                        .label("Remove")
+                        .disabled(settings.selected.is_none())
+                        .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                            this.remove_registered_engine();
+                            cx.notify();
+                        })),
+                ),
+        );
+
+        let card = v_flex()
+            .id("engine-settings-card")
+            .w(px(640.0))
+            .gap_3()
+            .p_4()
+            .rounded_lg()
+            .border_1()
+            .border_color(cx.theme().border)
+            .bg(cx.theme().background)
+            .text_color(cx.theme().foreground)
+            .on_click(cx.listener(|_, _: &ClickEvent, _, cx| {
+                cx.stop_propagation();
+            }))
+            .child(div().text_size(px(15.0)).child("Engine Settings"))
+            .child(
+                h_flex()
+                    .items_start()
+                    .gap_3()
+                    .child(engine_list)
+                    .child(self.render_engine_details(settings, cx)),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("engine-settings-cancel")
+                            .ghost()
+                            .label("Cancel")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.engine_settings = None;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("engine-settings-save")
+                            .primary()
+                            .label("Save")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.save_engine_settings(cx);
+                                cx.notify();
+                            })),
+                    ),
+            );
+
+        div()
+            .id("engine-settings-overlay")
+            .absolute()
+            .left_0()
+            .top_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(hsla(0.0, 0.0, 0.0, 0.45))
+            .child(card)
+            .into_any_element()
+    }
+
+    fn render_engine_details(
+        &self,
+        settings: &EngineSettings,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let muted = cx.theme().muted_foreground;
+        let Some(entry) = settings
+            .selected
+            .and_then(|idx| settings.registry.engines.get(idx))
+        else {
+            return div()
+                .flex_1()
+                .text_size(px(12.0))
+                .text_color(muted)
+                .child("Add an engine to configure it.")
+                .into_any_element();
+        };
+
+        let working_dir = entry.working_dir.as_ref().map_or_else(
+            || "(executable's directory)".to_string(),
+            |dir| dir.display().to_string(),
+        );
+        let mut details = v_flex()
+            .flex_1()
+            .gap_2()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(div().w(px(110.0)).child("Name"))
+                    .children(settings.name_input.as_ref().map(Input::new)),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().w(px(110.0)).child("Path"))
+                    .child(
+                        div()
+                            .text_size(px(12.0))
+                            .text_color(muted)
+                            .child(entry.path.display().to_string()),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(div().w(px(110.0)).child("Working dir"))
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_size(px(12.0))
+                            .text_color(muted)
+                            .child(working_dir),
+                    )
+                    .child(
+                        Button::new("engine-dir-choose")
+                            .small()
+                            .label("Choose…")
+                            .on_click(cx.listener(|this, _: &ClickEvent, window, cx| {
+                                this.choose_engine_working_dir(window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("engine-dir-reset")
+                            .small()
+                            .ghost()
+                            .label("Default")
+                            .disabled(entry.working_dir.is_none())
+                            .on_click(cx.listener(|this, _: &ClickEvent, window, cx| {
+                                this.set_engine_working_dir(None, window, cx);
+                                cx.notify();
+                            })),
+                    ),
+            )
+            .child(div().h(px(1.0)).w_full().bg(cx.theme().border));
+
+        let mut options = v_flex()
+            .id("engine-options")
+            .gap_2()
+            .max_h(px(360.0))
+            .overflow_y_scroll();
+        match &settings.probe {
+            ProbeState::Idle => {}
+            ProbeState::Running => {
+                options = options.child(
+                    div()
+                        .text_size(px(12.0))
+                        .text_color(muted)
+                        .child("Asking the engine for its options…"),
+                );
+            }
+            ProbeState::Failed(message) => {
+                options = options.child(
+                    div()
+                        .text_size(px(12.0))
+                        .text_color(cx.theme().danger)
+                        .child(format!("Could not start the engine: {message}")),
+                );
+            }
+            ProbeState::Done => {
+                for (idx, field) in settings.fields.iter().enumerate() {
+                    options = options.child(
+                        h_flex()
+                            .gap_2()
+                            .items_center()
+                            .child(
+                                div()
+                                    .w(px(150.0))
+                                    .text_size(px(12.0))
+                                    .child(field.option.name.clone()),
+                            )
+                            .child(Self::render_option_control(idx, field, cx)),
+                    );
+                }
+            }
+        }
+        details = details.child(options);
+        details.into_any_element()
+    }
+
+    fn render_option_control(
+        idx: usize,
+        field: &OptionField,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let option = &field.option;
+        match option.kind {
+            OptionKind::Check => {
+                let on = field.value == "true";
+                let button = Button::new(("engine-option-check", idx))
+                    .small()
+                    .label(if on { "On" } else { "Off" })
+                    .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                        this.set_engine_option_value(idx, if on { "false" } else { "true" });
+                        cx.notify();
+                    }));
+                if on {
+                    button.primary().into_any_element()
+                } else {
+                    button.ghost().into_any_element()
+                }
+            }
+            OptionKind::Combo => {
+                let mut choices = h_flex().gap_1().flex_wrap();
+                for (var_idx, var) in option.vars.iter().enumerate() {
+                    let value = var.clone();
+                    let button =
+                        Button::new(SharedString::from(format!("engine-option-{idx}-{var_idx}")))
+                            .xsmall()
+                            .label(var.clone())
+                            .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                                this.set_engine_option_value(idx, &value);
+                                cx.notify();
+                            }));
+                    choices = choices.child(if field.value == *var {
+                        button.primary()
+                    } else {
+                        button.ghost()
+                    });
+                }
+                choices.into_any_element()
+            }
+            OptionKind::Spin | OptionKind::String | OptionKind::Filename => {
+                let range = match (option.min, option.max) {
+                    (Some(min), Some(max)) => format!("{min}–{max}"),
+                    _ => String::new(),
+                };
+                h_flex()
+                    .flex_1()
+                    .gap_2()
+                    .items_center()
+                    .children(field.input.as_ref().map(Input::new))
+                    .child(
+                        div()
+                            .text_size(px(11.0))
+                            .text_color(cx.theme().muted_foreground)
+                            .child(range),
+                    )
+                    .into_any_element()
+            }
+            OptionKind::Button => div()
+                .text_size(px(11.0))
+                .text_color(cx.theme().muted_foreground)
+                .child("(action, not saved)")
+                .into_any_element(),
+        }
+    }
+
+    fn set_engine_option_value(&mut self, idx: usize, value: &str) {
+        if let Some(field) = self
+            .engine_settings
+            .as_mut()
+            .and_then(|settings| settings.fields.get_mut(idx))
+        {
+            field.value = value.to_string();
+        }
+    }
+}
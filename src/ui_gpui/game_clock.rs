@@ -0,0 +1,221 @@
+use gpui::*;
+use gpui_component::{ActiveTheme as _, h_flex};
+use shogi::Color;
+use std::time::{Duration, Instant};
+
+use crate::core::clock::{Clock, TimeControl};
+use crate::core::game::{EndReason, SoundCue};
+
+use super::model::{BOARD_SIZE, CELL_PX, GpuiP1Shell, HAND_COL_W};
+
+const CLOCK_TICK_INTERVAL: Duration = Duration::from_millis(100);
+/// Remaining time below which the running clock is drawn as urgent.
+const LOW_TIME_MS: u64 = 10_000;
+
+/// Time controls offered in the Game menu and the engine dialogs.
+pub(crate) const CLOCK_PRESETS: [TimeControl; 7] = [
+    TimeControl::PerMove { ms: 1_000 },
+    TimeControl::PerMove { ms: 3_000 },
+    TimeControl::PerMove { ms: 10_000 },
+    TimeControl::Byoyomi {
+        main_ms: 600_000,
+        byoyomi_ms: 30_000,
+    },
+    TimeControl::Byoyomi {
+        main_ms: 0,
+        byoyomi_ms: 30_000,
+    },
+    TimeControl::Fischer {
+        main_ms: 300_000,
+        increment_ms: 5_000,
+    },
+    TimeControl::SuddenDeath { main_ms: 180_000 },
+];
+
+impl GpuiP1Shell {
+    /// Puts a stopped clock on the game; [`Self::resume_clock`] starts it.
+    pub(crate) fn set_clock(&mut self, control: TimeControl, cx: &mut Context<Self>) {
+        self.clock = Some(Clock::new(control));
+        self.game.info_mut().time_control = Some(control.header());
+        self.clock_generation += 1;
+        let generation = self.clock_generation;
+
+        cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor().timer(CLOCK_TICK_INTERVAL).await;
+                let alive = this.update(cx, |shell, cx| {
+                    if shell.clock_generation != generation {
+                        return false;
+                    }
+                    if shell.tick_clock() {
+                        cx.notify();
+                    }
+                    shell.clock.is_some()
+                });
+                if !matches!(alive, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn clear_clock(&mut self) {
+        self.clock = None;
+        self.clock_generation += 1;
+    }
+
+    /// Runs the clock of the side to move.
+    pub(crate) fn resume_clock(&mut self) {
+        if self.game.is_game_over() {
+            return;
+        }
+        let side_to_move = self.game.side_to_move();
+        if let Some(clock) = &mut self.clock {
+            clock.start(side_to_move, Instant::now());
+        }
+    }
+
+    /// Stops both clocks, e.g. while moves are taken back.
+    pub(crate) fn pause_clock(&mut self) {
+        if let Some(clock) = &mut self.clock
+            && clock.running().is_some()
+        {
+            clock.stop(Instant::now());
+            self.game.set_status("Clock paused");
+        }
+    }
+
+    pub(crate) fn can_resume_clock(&self) -> bool {
+        self.clock.as_ref().is_some_and(|clock| {
+            clock.running().is_none() && clock.flagged().is_none() && !self.game.is_game_over()
+        })
+    }
+
+    /// Ends the mover's turn after a move was played. A move made after the
+    /// flag fell is taken back and the mover loses on time; returns whether
+    /// the move stands.
+    pub(crate) fn press_clock(&mut self) -> bool {
+        let mover = self.game.side_to_move().flip();
+        let Some(clock) = &mut self.clock else {
+            return true;
+        };
+        if clock.running() != Some(mover) {
+            return true;
+        }
+        let now = Instant::now();
+        match clock.press(now) {
+            Some(elapsed_ms) => {
+                if self.game.is_game_over() {
+                    clock.stop(now);
+                }
+                let ply = self.game.current_ply();
+                self.game
+                    .set_elapsed(ply, u32::try_from(elapsed_ms / 1_000).ok());
+                true
+            }
+            None => {
+                self.game.undo();
+                self.on_flag_fallen(mover);
+                false
+            }
+        }
+    }
+
+    /// Checks for a fallen flag; returns whether the clock display changed.
+    fn tick_clock(&mut self) -> bool {
+        let game_over = self.game.is_game_over();
+        let Some(clock) = &mut self.clock else {
+            return false;
+        };
+        if clock.running().is_none() {
+            return false;
+        }
+        let now = Instant::now();
+        if game_over {
+            clock.stop(now);
+            return true;
+        }
+        if let Some(color) = clock.check_flag(now) {
+            self.on_flag_fallen(color);
+        }
+        true
+    }
+
+    fn on_flag_fallen(&mut self, color: Color) {
+        self.cancel_engine_search();
+        self.game.forfeit(color, EndReason::Timeout);
+        if let (Some(engine_game), Some(result)) = (&self.engine_game, self.game.result()) {
+            // Results are announced from the human's side of the board.
+            let _ = self.game.take_pending_sound();
+            self.sound
+                .play(SoundCue::for_result(result, engine_game.human));
+        }
+        self.report_engine_result();
+        self.play_pending_sound();
+    }
+
+    /// Both clocks, each under its side's hand panel.
+    pub(crate) fn render_clocks(&self, cx: &App) -> AnyElement {
+        let Some(clock) = &self.clock else {
+            return div().into_any_element();
+        };
+        let scene_w = Self::scene_width(CELL_PX * BOARD_SIZE as f32);
+        let (left_hand, right_hand) = Self::hand_sides(self.flipped);
+        h_flex()
+            .w(px(scene_w))
+            .items_center()
+            .justify_between()
+            .child(self.render_clock(clock, left_hand, cx))
+            .child(
+                div()
+                    .text_size(px(11.0))
+                    .text_color(cx.theme().muted_foreground)
+                    .child(clock.control().label()),
+            )
+            .child(self.render_clock(clock, right_hand, cx))
+            .into_any_element()
+    }
+
+    fn render_clock(&self, clock: &Clock, color: Color, cx: &App) -> Div {
+        let left = clock.time_left(color, Instant::now());
+        let running = clock.running() == Some(color);
+        let flagged = clock.flagged() == Some(color);
+        let mark = match color {
+            Color::Black => "☗",
+            Color::White => "☖",
+        };
+        let text = if flagged {
+            format!("{mark} time up")
+        } else if left.in_byoyomi() {
+            format!("{mark} byoyomi {left}")
+        } else {
+            format!("{mark} {left}")
+        };
+        let urgent = flagged || (running && left.total_ms() < LOW_TIME_MS);
+
+        div()
+            .w(px(HAND_COL_W))
+            .py_1()
+            .rounded_md()
+            .border_1()
+            .border_color(if running {
+                cx.theme().primary
+            } else {
+                cx.theme().border
+            })
+            .bg(if running {
+                cx.theme().secondary
+            } else {
+                cx.theme().background
+            })
+            .text_color(if urgent {
+                cx.theme().danger
+            } else {
+                cx.theme().foreground
+            })
+            .text_size(px(14.0))
+            .text_center()
+            .child(text)
+    }
+}
@@ -47,11 +47,24 @@ impl GpuiP1Shell {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        self.clear_drawings();
+        cx.notify();
+    }
+
+    pub(crate) fn clear_drawings(&mut self) {
         self.draw_current = None;
         if !self.draw_shapes.is_empty() {
             self.draw_shapes.clear();
         }
-        cx.notify();
+    }
+
+    /// Backs out of whatever the pointer was doing: a pending promotion
+    /// choice, a drag, a shape being drawn or a selected piece.
+    pub(crate) fn cancel_input(&mut self) {
+        self.drag = None;
+        self.draw_current = None;
+        self.game.cancel_promotion();
+        self.game.clear_active_selection();
     }
 
     pub(crate) fn on_square_click(
@@ -61,11 +74,12 @@ impl GpuiP1Shell {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if self.consume_suppressed_click() {
+        if self.consume_suppressed_click() || self.is_input_locked() {
             return;
         }
+        let ply = self.game.current_ply();
         self.game.on_square_clicked(sq);
-        self.play_pending_sound();
+        self.after_board_input(ply);
         cx.notify();
     }
 
@@ -76,7 +90,7 @@ impl GpuiP1Shell {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if self.consume_suppressed_click() {
+        if self.consume_suppressed_click() || self.is_input_locked() {
             return;
         }
         self.game.select_hand_piece(piece_type);
@@ -91,8 +105,9 @@ impl GpuiP1Shell {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        let ply = self.game.current_ply();
         self.game.choose_promotion(promote);
-        self.play_pending_sound();
+        self.after_board_input(ply);
         cx.notify();
     }
 
@@ -114,7 +129,10 @@ impl GpuiP1Shell {
         cx: &mut Context<Self>,
     ) {
         self.cancel_draw_if_any();
-        if event.button != MouseButton::Left || self.game.has_pending_promotion() {
+        if event.button != MouseButton::Left
+            || self.game.has_pending_promotion()
+            || self.is_input_locked()
+        {
             return;
         }
         let Some(piece) = self.game.piece_at(sq) else {
@@ -148,7 +166,7 @@ impl GpuiP1Shell {
         let brush = Self::draw_brush(event.modifiers);
         let board_px = CELL_PX * BOARD_SIZE as f32;
         let anchor = DrawAnchor::Board(sq);
-        let anchor_local = Self::anchor_to_scene_point(anchor, board_px);
+        let anchor_local = Self::anchor_to_scene_point(anchor, board_px, self.flipped);
         self.draw_current = Some(DrawCurrent {
             orig: anchor,
             dest: Some(anchor),
@@ -167,7 +185,10 @@ impl GpuiP1Shell {
         cx: &mut Context<Self>,
     ) {
         self.cancel_draw_if_any();
-        if event.button != MouseButton::Left || self.game.has_pending_promotion() {
+        if event.button != MouseButton::Left
+            || self.game.has_pending_promotion()
+            || self.is_input_locked()
+        {
             return;
         }
         if color != self.game.side_to_move() || self.game.hand_count(color, piece_type) == 0 {
@@ -199,7 +220,7 @@ impl GpuiP1Shell {
         let brush = Self::draw_brush(event.modifiers);
         let anchor = DrawAnchor::Hand { color, piece_type };
         let board_px = CELL_PX * BOARD_SIZE as f32;
-        let anchor_local = Self::anchor_to_scene_point(anchor, board_px);
+        let anchor_local = Self::anchor_to_scene_point(anchor, board_px, self.flipped);
         self.draw_current = Some(DrawCurrent {
             orig: anchor,
             dest: Some(anchor),
@@ -224,11 +245,12 @@ impl GpuiP1Shell {
             return;
         }
 
+        let ply = self.game.current_ply();
         match drag.source {
             DragSource::Board { from, .. } => self.game.perform_board_drag(from, sq),
             DragSource::Hand { piece_type, .. } => self.game.perform_hand_drag(piece_type, sq),
         }
-        self.play_pending_sound();
+        self.after_board_input(ply);
         self.suppress_next_click = true;
         cx.stop_propagation();
         cx.notify();
@@ -0,0 +1,161 @@
+//! Keyboard shortcuts.
+//!
+//! Defaults can be changed with `keymap.ini` in the settings directory, one
+//! binding per line:
+//!
+//! ```text
+//! # keystrokes = action
+//! j = StepForward
+//! k = StepBackward
+//! ctrl-b = none
+//! ```
+//!
+//! An action named in the file loses its default keys, and `none` unbinds a
+//! default key without binding it to anything else.
+
+use std::fmt;
+use std::fs;
+
+use gpui::{App, KeyBinding, Keystroke, actions};
+
+use crate::config;
+
+const KEYMAP_FILE: &str = "keymap.ini";
+
+/// Key context of the workspace; bindings apply anywhere inside it unless a
+/// focused text input claims the key first.
+pub(crate) const KEY_CONTEXT: &str = "RshogiWorkspace";
+
+actions!(
+    rshogi,
+    [
+        StepBackward,
+        StepForward,
+        GoToStart,
+        GoToEnd,
+        FlipBoard,
+        ToggleRightDock,
+        ToggleBottomDock,
+        ToggleAnalysis,
+        Cancel,
+        ClearDrawings,
+        FocusMoveInput,
+    ]
+);
+
+/// Letter shortcuts carry a modifier so they never fire while typing.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("left", "StepBackward"),
+    ("right", "StepForward"),
+    ("home", "GoToStart"),
+    ("end", "GoToEnd"),
+    ("ctrl-shift-f", "FlipBoard"),
+    ("ctrl-b", "ToggleRightDock"),
+    ("ctrl-j", "ToggleBottomDock"),
+    ("ctrl-shift-a", "ToggleAnalysis"),
+    ("escape", "Cancel"),
+    ("ctrl-shift-d", "ClearDrawings"),
+    ("ctrl-m", "FocusMoveInput"),
+];
+
+const UNBIND: &str = "none";
+
+#[derive(Debug)]
+pub(crate) struct KeymapError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Registers the default shortcuts merged with the user's `keymap.ini`. A
+/// file that fails to parse is ignored as a whole.
+pub(crate) fn bind_keys(cx: &mut App) {
+    let mut bindings: Vec<(String, String)> = DEFAULT_BINDINGS
+        .iter()
+        .map(|&(keys, action)| (keys.to_string(), action.to_string()))
+        .collect();
+    if let Some(path) = config::config_file(KEYMAP_FILE)
+        && let Ok(text) = fs::read_to_string(&path)
+    {
+        match parse(&text) {
+            Ok(overrides) => apply_overrides(&mut bindings, overrides),
+            Err(err) => eprintln!("ignoring {}: {err}", path.display()),
+        }
+    }
+    cx.bind_keys(
+        bindings
+            .iter()
+            .filter_map(|(keys, action)| key_binding(keys, action)),
+    );
+}
+
+fn apply_overrides(bindings: &mut Vec<(String, String)>, overrides: Vec<(String, String)>) {
+    for (keys, action) in &overrides {
+        bindings.retain(|(k, a)| k != keys && a != action);
+    }
+    for (keys, action) in overrides {
+        if action != UNBIND && !bindings.iter().any(|(k, _)| *k == keys) {
+            bindings.push((keys, action));
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<(String, String)>, KeymapError> {
+    let mut bindings = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| KeymapError {
+            line: index + 1,
+            message,
+        };
+        let (keys, action) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `keystrokes = action`".to_string()))?;
+        let (keys, action) = (keys.trim(), action.trim());
+        if keys.is_empty() {
+            return Err(error("missing keystrokes".to_string()));
+        }
+        if let Some(bad) = keys
+            .split_whitespace()
+            .find(|keystroke| Keystroke::parse(keystroke).is_err())
+        {
+            return Err(error(format!("invalid keystroke `{bad}`")));
+        }
+        if action != UNBIND && key_binding(keys, action).is_none() {
+            return Err(error(format!("unknown action `{action}`")));
+        }
+        bindings.push((
+            keys.split_whitespace().collect::<Vec<_>>().join(" "),
+            action.to_string(),
+        ));
+    }
+    Ok(bindings)
+}
+
+fn key_binding(keys: &str, action: &str) -> Option<KeyBinding> {
+    let context = Some(KEY_CONTEXT);
+    Some(match action {
+        "StepBackward" => KeyBinding::new(keys, StepBackward, context),
+        "StepForward" => KeyBinding::new(keys, StepForward, context),
+        "GoToStart" => KeyBinding::new(keys, GoToStart, context),
+        "GoToEnd" => KeyBinding::new(keys, GoToEnd, context),
+        "FlipBoard" => KeyBinding::new(keys, FlipBoard, context),
+        "ToggleRightDock" => KeyBinding::new(keys, ToggleRightDock, context),
+        "ToggleBottomDock" => KeyBinding::new(keys, ToggleBottomDock, context),
+        "ToggleAnalysis" => KeyBinding::new(keys, ToggleAnalysis, context),
+        "Cancel" => KeyBinding::new(keys, Cancel, context),
+        "ClearDrawings" => KeyBinding::new(keys, ClearDrawings, context),
+        "FocusMoveInput" => KeyBinding::new(keys, FocusMoveInput, context),
+        _ => return None,
+    })
+}
@@ -1,11 +1,22 @@
+mod analysis;
 mod app;
 mod assets;
+mod board_editor;
 mod draw;
+mod engine_match;
+mod engine_play;
+mod engine_settings;
 mod frame_root;
+mod game_clock;
 mod interaction;
+mod keymap;
 mod model;
+mod move_input;
+mod move_list;
 mod render;
 mod sound;
+mod tsume_solver;
+mod tsume_trainer;
 mod workspace;
 
 pub use app::run;
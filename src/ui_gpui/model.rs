@@ -1,10 +1,23 @@
-use crate::core::game::GameState;
+use crate::core::clock::Clock;
+use crate::core::game::{GameState, ImpasseRule};
+use crate::core::handicap::Handicap;
+use crate::core::notation::Notation;
+use crate::core::{record, usi};
+use crate::engine::{EngineCommand, EngineConfig, EngineEvent, EngineHandle, EngineRegistry};
 use gpui::*;
 use shogi::{Color, Piece, PieceType, Square};
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::Path, rc::Rc, time::Duration};
 
+use super::analysis::Analysis;
 use super::assets::{BoardWallpaper, PieceWallpaper};
+use super::board_editor::BoardEditor;
+use super::engine_match::{EngineMatch, MatchSetup};
+use super::engine_play::{EngineGame, EngineGameSetup};
+use super::engine_settings::EngineSettings;
+use super::move_input::MoveInput;
 use super::sound::SoundPlayer;
+use super::tsume_solver::TsumeSolverDialog;
+use super::tsume_trainer::TsumeTrainer;
 
 pub(crate) const BOARD_SIZE: u8 = 9;
 // Use integer pixel sizes to avoid sub-pixel misalignment between cells/highlights/grid lines.
@@ -19,6 +32,7 @@ pub(crate) const PROMO_PIECE_PX: f32 = 58.0;
 pub(crate) const DRAG_START_THRESHOLD_PX: f32 = 4.0;
 pub(crate) const SCENE_GAP_PX: f32 = 8.0;
 pub(crate) const BOARD_COORD_RIGHT_W: f32 = 16.0;
+const ENGINE_POLL_INTERVAL: Duration = Duration::from_millis(30);
 pub(crate) const HAND_PIECES: [PieceType; 7] = [
     PieceType::Rook,
     PieceType::Bishop,
@@ -40,6 +54,47 @@ pub(crate) struct GpuiP1Shell {
     pub(crate) sound: SoundPlayer,
     pub(crate) piece_wallpaper: PieceWallpaper,
     pub(crate) board_wallpaper: BoardWallpaper,
+    /// Board seen from Gote's side: White's camp at the bottom.
+    pub(crate) flipped: bool,
+    /// How the move list and piece names are written.
+    pub(crate) notation: Notation,
+    pub(crate) engine: Option<EngineHandle>,
+    /// Bumped whenever an engine starts so stale poll loops stop.
+    engine_generation: u64,
+    pub(crate) engine_registry: EngineRegistry,
+    pub(crate) engine_settings: Option<EngineSettings>,
+    /// Open "New Game vs Engine" dialog.
+    pub(crate) engine_setup: Option<EngineGameSetup>,
+    pub(crate) engine_game: Option<EngineGame>,
+    pub(crate) clock: Option<Clock>,
+    /// Bumped whenever a clock is set so stale tick loops stop.
+    pub(crate) clock_generation: u64,
+    /// Open "Engine Match" dialog.
+    pub(crate) match_setup: Option<MatchSetup>,
+    pub(crate) engine_match: Option<EngineMatch>,
+    /// Bumped whenever a match starts so stale poll loops stop.
+    pub(crate) match_generation: u64,
+    pub(crate) analysis: Option<Analysis>,
+    /// `MultiPV` used for analysis; kept across engine restarts.
+    pub(crate) analysis_multipv: u32,
+    pub(crate) bottom_tab: BottomTab,
+    pub(crate) move_input: Option<MoveInput>,
+    /// Open "Set Up Position" editor.
+    pub(crate) board_editor: Option<BoardEditor>,
+    /// Open "Solve Mate" dialog.
+    pub(crate) tsume_solver: Option<TsumeSolverDialog>,
+    /// Bumped whenever a solve starts or its dialog closes so stale results are dropped.
+    pub(crate) tsume_generation: u64,
+    pub(crate) tsume_trainer: Option<TsumeTrainer>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BottomTab {
+    Console,
+    Analysis,
+    Moves,
+    /// Shown while the tsume trainer is open.
+    Tsume,
 }
 
 #[derive(Clone, Copy)]
@@ -77,6 +132,16 @@ pub(crate) struct DrawShape {
     pub(crate) brush: DrawBrush,
 }
 
+/// Candidate move from engine analysis, drawn apart from user shapes.
+#[derive(Clone, Copy)]
+pub(crate) struct EngineArrow {
+    pub(crate) orig: DrawAnchor,
+    pub(crate) dest: DrawAnchor,
+    pub(crate) alpha: f32,
+    /// Stroke width relative to user arrows.
+    pub(crate) scale: f32,
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct DrawCurrent {
     pub(crate) orig: DrawAnchor,
@@ -98,6 +163,27 @@ impl GpuiP1Shell {
             sound: SoundPlayer::new(),
             piece_wallpaper: PieceWallpaper::RyokoKanji,
             board_wallpaper: BoardWallpaper::Oak,
+            flipped: false,
+            notation: Notation::Japanese,
+            engine: None,
+            engine_generation: 0,
+            engine_registry: EngineRegistry::load(),
+            engine_settings: None,
+            engine_setup: None,
+            engine_game: None,
+            clock: None,
+            clock_generation: 0,
+            match_setup: None,
+            engine_match: None,
+            match_generation: 0,
+            analysis: None,
+            analysis_multipv: 3,
+            bottom_tab: BottomTab::Console,
+            move_input: None,
+            board_editor: None,
+            tsume_solver: None,
+            tsume_generation: 0,
+            tsume_trainer: None,
         }
     }
 
@@ -126,6 +212,128 @@ impl GpuiP1Shell {
         }
     }
 
+    /// Takes back one move, or back to the human's turn in an engine game.
+    pub(crate) fn undo_move(&mut self) {
+        self.drag = None;
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.undo();
+        if let Some(engine_game) = &self.engine_game
+            && self.game.side_to_move() != engine_game.human
+        {
+            self.game.undo();
+        }
+        self.play_pending_sound();
+        self.request_engine_move();
+    }
+
+    pub(crate) fn redo_move(&mut self) {
+        self.drag = None;
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.redo();
+        self.play_pending_sound();
+        self.request_engine_move();
+    }
+
+    pub(crate) fn resign(&mut self) {
+        self.drag = None;
+        let color = match &self.engine_game {
+            Some(engine_game) => engine_game.human,
+            None => self.game.side_to_move(),
+        };
+        self.cancel_engine_search();
+        self.game.resign(color);
+        self.play_pending_sound();
+        self.report_engine_result();
+    }
+
+    pub(crate) fn agree_draw(&mut self) {
+        self.drag = None;
+        self.cancel_engine_search();
+        self.game.agree_draw();
+        self.play_pending_sound();
+        self.report_engine_result();
+        self.request_engine_move();
+    }
+
+    pub(crate) fn declare_impasse(&mut self, rule: ImpasseRule) {
+        self.drag = None;
+        self.cancel_engine_search();
+        self.game.declare_impasse(rule);
+        self.play_pending_sound();
+        self.report_engine_result();
+        self.request_engine_move();
+    }
+
+    /// Replaces the current game with a new record starting from `sfen`.
+    pub(crate) fn load_sfen(&mut self, sfen: &str) {
+        match GameState::from_sfen(sfen) {
+            Ok(game) => self.replace_game(game),
+            Err(err) => self
+                .game
+                .report_error(format!("Load position failed: {err}")),
+        }
+        self.play_pending_sound();
+    }
+
+    /// Starts an empty record from `handicap`'s position.
+    pub(crate) fn new_game(&mut self, handicap: Handicap) {
+        self.load_sfen(handicap.sfen);
+        if handicap.is_even() {
+            self.game.set_status("New game");
+        } else {
+            self.game
+                .set_status(format!("New game: {} handicap", handicap.name));
+        }
+    }
+
+    pub(crate) fn open_record(&mut self, path: &Path) {
+        match record::read_record(path) {
+            Ok(game) => {
+                self.replace_game(game);
+                self.game.set_status(format!("Opened {}", path.display()));
+            }
+            Err(err) => self.game.report_error(format!("Open failed: {err}")),
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn save_record(&mut self, path: &Path) {
+        match record::write_record(&self.game, path) {
+            Ok(()) => self.game.set_status(format!("Saved {}", path.display())),
+            Err(err) => self.game.report_error(format!("Save failed: {err}")),
+        }
+        self.play_pending_sound();
+    }
+
+    /// Replaces the current game with the record described by a USI `position` line.
+    pub(crate) fn load_usi_position(&mut self, text: &str) {
+        match usi::parse_position(text) {
+            Ok(game) => self.replace_game(game),
+            Err(err) => self
+                .game
+                .report_error(format!("Load USI position failed: {err}")),
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn replace_game(&mut self, game: GameState) {
+        self.end_engine_game();
+        self.stop_engine_match();
+        self.clear_clock();
+        self.close_tsume_trainer();
+        self.set_game(game);
+    }
+
+    /// Shows `game` without ending the engine game or match that produced it.
+    pub(crate) fn set_game(&mut self, game: GameState) {
+        self.game = game;
+        self.drag = None;
+        self.draw_current = None;
+        self.draw_shapes.clear();
+    }
+
     pub(crate) fn piece_wallpaper(&self) -> PieceWallpaper {
         self.piece_wallpaper
     }
@@ -141,4 +349,92 @@ impl GpuiP1Shell {
     pub(crate) fn set_board_wallpaper(&mut self, wallpaper: BoardWallpaper) {
         self.board_wallpaper = wallpaper;
     }
+
+    pub(crate) fn flip_board(&mut self) {
+        self.flipped = !self.flipped;
+        self.draw_current = None;
+    }
+}
+
+impl GpuiP1Shell {
+    /// Starts an engine session and polls its events on the UI thread.
+    pub(crate) fn start_engine(&mut self, config: EngineConfig, cx: &mut Context<Self>) {
+        self.stop_engine();
+        self.game
+            .set_status(format!("Starting engine {}", config.path.display()));
+        self.engine = Some(EngineHandle::start(config));
+        self.engine_generation += 1;
+        let generation = self.engine_generation;
+
+        cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor().timer(ENGINE_POLL_INTERVAL).await;
+                let alive = this.update(cx, |shell, cx| {
+                    if shell.engine_generation != generation {
+                        return false;
+                    }
+                    if shell.poll_engine() | shell.sync_analysis() {
+                        cx.notify();
+                    }
+                    shell.engine.is_some()
+                });
+                if !matches!(alive, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Asks the engine to quit without blocking the UI on its exit.
+    pub(crate) fn stop_engine(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            std::thread::spawn(move || drop(engine));
+        }
+    }
+
+    pub(crate) fn send_engine(&self, command: EngineCommand) {
+        if let Some(engine) = &self.engine {
+            engine.send(command);
+        }
+    }
+
+    /// Drains pending engine events; returns whether anything changed.
+    fn poll_engine(&mut self) -> bool {
+        let mut changed = false;
+        while let Some(event) = self.engine.as_ref().and_then(|engine| engine.try_recv()) {
+            self.handle_engine_event(event);
+            changed = true;
+        }
+        changed
+    }
+
+    fn handle_engine_event(&mut self, event: EngineEvent) {
+        match event {
+            EngineEvent::Ready(id) => {
+                self.game.set_status(format!("Engine ready: {}", id.name));
+                self.on_engine_ready();
+                self.on_analysis_ready(&id.name);
+            }
+            EngineEvent::StopTimedOut => {
+                self.game
+                    .report_error("Engine: engine did not answer `stop` in time");
+                self.on_engine_stop_timed_out();
+                self.on_analysis_bestmove();
+            }
+            EngineEvent::Crashed(message) => {
+                self.game.report_error(format!("Engine crashed: {message}"));
+                self.engine = None;
+                self.engine_game = None;
+                self.analysis = None;
+            }
+            EngineEvent::Exited => self.engine = None,
+            EngineEvent::BestMove { mv, .. } => {
+                self.on_engine_bestmove(&mv);
+                self.on_analysis_bestmove();
+            }
+            EngineEvent::Info(line) => self.on_analysis_info(&line),
+        }
+        self.play_pending_sound();
+    }
 }
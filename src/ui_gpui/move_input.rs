@@ -0,0 +1,168 @@
+use gpui::{prelude::FluentBuilder as _, *};
+use gpui_component::{
+    ActiveTheme as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex,
+    input::{Input, InputEvent, InputState},
+    v_flex,
+};
+use shogi::Move;
+
+use crate::core::move_input::{self, MoveMatch};
+
+use super::model::GpuiP1Shell;
+
+/// Completions listed under the box; more text narrows them down.
+const MAX_COMPLETIONS: usize = 8;
+
+/// Box for entering moves as text, e.g. `76fu`, `7g7f` or `P*5e`.
+pub(crate) struct MoveInput {
+    pub(crate) state: Entity<InputState>,
+    /// Moves the typed text could still become, with their move list text.
+    pub(crate) completions: Vec<(Move, String)>,
+    _subscription: Subscription,
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn init_move_input(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let state = cx
+            .new(|cx| InputState::new(window, cx).placeholder("Type a move: 76fu, 7g7f, P*5e, +"));
+        let subscription = cx.subscribe_in(&state, window, Self::on_move_input_event);
+        self.move_input = Some(MoveInput {
+            state,
+            completions: Vec::new(),
+            _subscription: subscription,
+        });
+    }
+
+    fn on_move_input_event(
+        &mut self,
+        state: &Entity<InputState>,
+        event: &InputEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let text = state.read(cx).value().to_string();
+        match event {
+            InputEvent::Change => self.update_completions(&text),
+            InputEvent::PressEnter { .. } => {
+                if self.submit_typed_move(&text) {
+                    state.update(cx, |state, cx| state.set_value("", window, cx));
+                    self.update_completions("");
+                }
+            }
+            _ => return,
+        }
+        cx.notify();
+    }
+
+    fn update_completions(&mut self, text: &str) {
+        let completions = if text.trim().is_empty() {
+            Vec::new()
+        } else {
+            move_input::match_moves(&mut self.game, text)
+                .candidates
+                .into_iter()
+                .take(MAX_COMPLETIONS)
+                .map(|mv| (mv, move_input::describe(&self.game, mv, self.notation)))
+                .collect()
+        };
+        if let Some(input) = &mut self.move_input {
+            input.completions = completions;
+        }
+    }
+
+    /// Plays the move spelled by `text`, or answers a pending promotion with
+    /// `+` or `=`. Returns whether the text was used up.
+    fn submit_typed_move(&mut self, text: &str) -> bool {
+        let text = text.trim();
+        if text.is_empty() || self.is_input_locked() {
+            return false;
+        }
+        let ply = self.game.current_ply();
+        if self.game.has_pending_promotion() {
+            match text {
+                "+" | "成" => self.game.choose_promotion(true),
+                "=" | "不成" => self.game.choose_promotion(false),
+                _ => {
+                    self.game.set_status("Type + to promote or = to decline");
+                    return false;
+                }
+            }
+            self.after_board_input(ply);
+            return true;
+        }
+        match move_input::match_moves(&mut self.game, text).resolve() {
+            MoveMatch::Move(mv) => {
+                self.game.play_move(mv);
+            }
+            MoveMatch::Promotion(mv) => self.game.enter_move(mv),
+            MoveMatch::Ambiguous => {
+                self.game.set_status(format!("`{text}` fits several moves"));
+                return false;
+            }
+            MoveMatch::None => {
+                self.game
+                    .report_error(format!("No legal move matches `{text}`"));
+                self.play_pending_sound();
+                return false;
+            }
+        }
+        self.drag = None;
+        self.after_board_input(ply);
+        true
+    }
+
+    /// Plays a listed completion, which already settles any promotion.
+    fn play_completion(&mut self, mv: Move, window: &mut Window, cx: &mut Context<Self>) {
+        if self.is_input_locked() || self.game.has_pending_promotion() {
+            return;
+        }
+        let ply = self.game.current_ply();
+        self.drag = None;
+        self.game.play_move(mv);
+        self.after_board_input(ply);
+        if let Some(input) = &mut self.move_input {
+            input.completions.clear();
+            input
+                .state
+                .update(cx, |state, cx| state.set_value("", window, cx));
+        }
+    }
+
+    pub(crate) fn render_move_input(&self, board: Entity<Self>, cx: &App) -> Option<AnyElement> {
+        let input = self.move_input.as_ref()?;
+        let mut completions = h_flex().flex_wrap().gap_1();
+        for (index, (mv, label)) in input.completions.iter().enumerate() {
+            let board = board.clone();
+            let mv = *mv;
+            completions = completions.child(
+                Button::new(("move-completion", index))
+                    .xsmall()
+                    .ghost()
+                    .label(label.clone())
+                    .on_click(move |_, window, cx| {
+                        board.update(cx, |board, cx| {
+                            board.play_completion(mv, window, cx);
+                            cx.notify();
+                        });
+                    }),
+            );
+        }
+        Some(
+            v_flex()
+                .gap_1()
+                .child(Input::new(&input.state).small())
+                .when(input.completions.is_empty(), |this| {
+                    this.child(
+                        div()
+                            .text_size(px(11.0))
+                            .text_color(cx.theme().muted_foreground)
+                            .child("USI, CSA, Hodges or Japanese; Enter plays"),
+                    )
+                })
+                .child(completions)
+                .into_any_element(),
+        )
+    }
+}
@@ -0,0 +1,197 @@
+use gpui::*;
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+
+use crate::core::notation::{self, Notation};
+
+use super::model::GpuiP1Shell;
+
+const MOVE_NUMBER_W: f32 = 36.0;
+
+impl GpuiP1Shell {
+    pub(crate) fn set_notation(&mut self, notation: Notation) {
+        self.notation = notation;
+    }
+
+    /// Shows the position after `ply` moves of the displayed line.
+    pub(crate) fn go_to_ply(&mut self, ply: usize) {
+        self.drag = None;
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.jump_to_ply(ply);
+        self.play_pending_sound();
+        self.request_engine_move();
+    }
+
+    /// Follows the `index`-th move recorded at `ply` instead of the displayed one.
+    pub(crate) fn select_variation(&mut self, ply: usize, index: usize) {
+        self.drag = None;
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.select_variation(ply, index);
+        self.play_pending_sound();
+        self.request_engine_move();
+    }
+
+    /// Deletes the displayed move and what follows it, if it is one of
+    /// several moves recorded at its ply.
+    pub(crate) fn delete_current_variation(&mut self) {
+        let ply = self.game.current_ply();
+        if !self.can_delete_variation() {
+            return;
+        }
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.delete_variation(ply);
+        self.play_pending_sound();
+        self.game
+            .set_status(format!("Deleted variation at move {ply}"));
+        self.request_engine_move();
+    }
+
+    pub(crate) fn can_delete_variation(&self) -> bool {
+        let ply = self.game.current_ply();
+        ply > 0 && self.game.variations(ply).len() > 1
+    }
+
+    pub(crate) fn render_moves_tab(&self, board: Entity<Self>, cx: &App) -> AnyElement {
+        let cursor = self.game.current_ply();
+        let on_main_line = self.game.is_main_line(self.game.history().len());
+
+        let promote_board = board.clone();
+        let delete_board = board.clone();
+        let header = h_flex()
+            .items_center()
+            .gap_2()
+            .child(
+                Button::new("moves-make-main-line")
+                    .small()
+                    .ghost()
+                    .label("Make Main Line")
+                    .disabled(on_main_line)
+                    .on_click(move |_, _, cx| {
+                        promote_board.update(cx, |board, cx| {
+                            board.game.promote_to_main_line();
+                            cx.notify();
+                        });
+                    }),
+            )
+            .child(
+                Button::new("moves-delete-variation")
+                    .small()
+                    .ghost()
+                    .label("Delete Variation")
+                    .disabled(!self.can_delete_variation())
+                    .on_click(move |_, _, cx| {
+                        delete_board.update(cx, |board, cx| {
+                            board.delete_current_variation();
+                            cx.notify();
+                        });
+                    }),
+            )
+            .child(
+                div()
+                    .text_size(px(11.0))
+                    .text_color(cx.theme().muted_foreground)
+                    .child(if on_main_line {
+                        "main line"
+                    } else {
+                        "variation"
+                    }),
+            );
+
+        let move_button = |id: ElementId, label: String, ply: usize| {
+            let board = board.clone();
+            let button = Button::new(id)
+                .xsmall()
+                .label(label)
+                .on_click(move |_, _, cx| {
+                    board.update(cx, |board, cx| {
+                        board.go_to_ply(ply);
+                        cx.notify();
+                    });
+                });
+            if cursor == ply {
+                button.primary()
+            } else {
+                button.ghost()
+            }
+        };
+
+        let mut list = v_flex()
+            .id("moves-list")
+            .flex_1()
+            .overflow_y_scroll()
+            .gap_0p5()
+            .child(
+                h_flex()
+                    .child(div().w(px(MOVE_NUMBER_W)))
+                    .child(move_button("move-start".into(), "Start".to_string(), 0)),
+            );
+        for (idx, moves) in notation::line_moves(&self.game, self.notation)
+            .into_iter()
+            .enumerate()
+        {
+            let ply = idx + 1;
+            let number = match self.notation {
+                Notation::Japanese => ply.to_string(),
+                Notation::Western => format!("{ply}."),
+            };
+            let mut row = h_flex()
+                .items_center()
+                .gap_1()
+                .child(
+                    div()
+                        .w(px(MOVE_NUMBER_W))
+                        .text_size(px(11.0))
+                        .text_color(cx.theme().muted_foreground)
+                        .child(number),
+                )
+                .child(move_button(
+                    ("move", ply).into(),
+                    moves.texts[moves.selected].clone(),
+                    ply,
+                ));
+            // Other moves recorded at this ply, shown inline.
+            for (index, text) in moves.texts.into_iter().enumerate() {
+                if index == moves.selected {
+                    continue;
+                }
+                let board = board.clone();
+                row = row.child(
+                    Button::new(SharedString::from(format!("move-variation-{ply}-{index}")))
+                        .xsmall()
+                        .ghost()
+                        .label(format!("({text})"))
+                        .on_click(move |_, _, cx| {
+                            board.update(cx, |board, cx| {
+                                board.select_variation(ply, index);
+                                cx.notify();
+                            });
+                        }),
+                );
+            }
+            list = list.child(row);
+        }
+        if let Some(result) = self.game.result() {
+            list = list.child(
+                div()
+                    .pl(px(MOVE_NUMBER_W))
+                    .text_size(px(12.0))
+                    .text_color(cx.theme().muted_foreground)
+                    .child(result.to_string()),
+            );
+        }
+
+        v_flex()
+            .size_full()
+            .gap_2()
+            .child(header)
+            .children(self.render_move_input(board.clone(), cx))
+            .child(list)
+            .into_any_element()
+    }
+}
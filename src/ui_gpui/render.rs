@@ -5,13 +5,13 @@ use gpui_component::{
 };
 use shogi::{Color, Piece, PieceType, Square};
 
-use crate::core::game::{piece_type_label, promoted_piece_type};
+use crate::core::game::promoted_piece_type;
 use crate::ui_gpui::assets::{board_asset_path, piece_asset_path};
 
 use super::model::{
-    BOARD_COORD_RIGHT_W, BOARD_SIZE, CELL_PX, DragSource, DragState, DrawCurrent, DrawShape,
-    GpuiP1Shell, HAND_COL_W, HAND_PIECE_PX, HAND_PIECES, PIECE_PX, PROMO_CARD_H_RATIO,
-    PROMO_CARD_RADIUS, PROMO_CARD_W_RATIO, PROMO_PIECE_PX, SCENE_GAP_PX,
+    BOARD_COORD_RIGHT_W, BOARD_SIZE, BottomTab, CELL_PX, DragSource, DragState, DrawCurrent,
+    DrawShape, EngineArrow, GpuiP1Shell, HAND_COL_W, HAND_PIECE_PX, HAND_PIECES, PIECE_PX,
+    PROMO_CARD_H_RATIO, PROMO_CARD_RADIUS, PROMO_CARD_W_RATIO, PROMO_PIECE_PX, SCENE_GAP_PX,
 };
 
 impl GpuiP1Shell {
@@ -105,22 +105,23 @@ impl GpuiP1Shell {
             piece_type: promoted_piece_type(piece.piece_type),
             color: piece.color,
         };
-        let (ui_row, ui_col) = Self::ui_pos_from_square(target_sq);
+        let (ui_row, ui_col) = Self::ui_pos_from_square(target_sq, self.flipped);
         let card_w = CELL_PX * PROMO_CARD_W_RATIO;
         let card_h = CELL_PX * PROMO_CARD_H_RATIO;
         let x = ui_col as f32 * CELL_PX + (CELL_PX - card_w) * 0.5;
-        let raw_y = if piece.color == Color::White {
+        // The card opens away from the mover's side of the screen.
+        let moves_down = (piece.color == Color::White) != self.flipped;
+        let raw_y = if moves_down {
             (ui_row as f32 - 1.0) * CELL_PX
         } else {
             ui_row as f32 * CELL_PX
         };
         let y = raw_y.clamp(0.0, (board_px - card_h).max(0.0));
-        let (top_piece, bottom_piece, top_promotes, bottom_promotes) =
-            if piece.color == Color::White {
-                (piece, promoted, false, true)
-            } else {
-                (promoted, piece, true, false)
-            };
+        let (top_piece, bottom_piece, top_promotes, bottom_promotes) = if moves_down {
+            (piece, promoted, false, true)
+        } else {
+            (promoted, piece, true, false)
+        };
 
         let top_choice = div()
             .id("promote-choice-top")
@@ -129,10 +130,14 @@ impl GpuiP1Shell {
             .items_center()
             .justify_center()
             .child(
-                img(piece_asset_path(top_piece, self.piece_wallpaper))
-                    .w(px(PROMO_PIECE_PX))
-                    .h(px(PROMO_PIECE_PX))
-                    .object_fit(ObjectFit::Contain),
+                img(piece_asset_path(
+                    top_piece,
+                    self.piece_wallpaper,
+                    self.flipped,
+                ))
+                .w(px(PROMO_PIECE_PX))
+                .h(px(PROMO_PIECE_PX))
+                .object_fit(ObjectFit::Contain),
             )
             .on_click(cx.listener(move |this, ev, window, cx| {
                 this.on_choose_promotion(top_promotes, ev, window, cx);
@@ -147,10 +152,14 @@ impl GpuiP1Shell {
             .border_t_1()
             .border_color(hsla(0.0, 0.0, 1.0, 0.1))
             .child(
-                img(piece_asset_path(bottom_piece, self.piece_wallpaper))
-                    .w(px(PROMO_PIECE_PX))
-                    .h(px(PROMO_PIECE_PX))
-                    .object_fit(ObjectFit::Contain),
+                img(piece_asset_path(
+                    bottom_piece,
+                    self.piece_wallpaper,
+                    self.flipped,
+                ))
+                .w(px(PROMO_PIECE_PX))
+                .h(px(PROMO_PIECE_PX))
+                .object_fit(ObjectFit::Contain),
             )
             .on_click(cx.listener(move |this, ev, window, cx| {
                 this.on_choose_promotion(bottom_promotes, ev, window, cx);
@@ -192,14 +201,18 @@ impl GpuiP1Shell {
     fn render_shapes_overlay(&self, board_px: f32, scene_w: f32) -> AnyElement {
         #[derive(Clone)]
         struct ShapesPrepaint {
+            engine_arrows: Vec<EngineArrow>,
             shapes: Vec<DrawShape>,
             current: Option<DrawCurrent>,
             board_px: f32,
+            flipped: bool,
         }
 
         let bounds_cell = self.draw_scene_bounds.clone();
+        let engine_arrows = self.engine_arrows();
         let shapes = self.draw_shapes.clone();
         let current = self.draw_current;
+        let flipped = self.flipped;
 
         div()
             .id("shapes-overlay")
@@ -213,17 +226,30 @@ impl GpuiP1Shell {
                     move |bounds, _, _| {
                         *bounds_cell.borrow_mut() = Some(bounds);
                         ShapesPrepaint {
+                            engine_arrows: engine_arrows.clone(),
                             shapes: shapes.clone(),
                             current,
                             board_px,
+                            flipped,
                         }
                     },
                     move |bounds, prepaint, window, _| {
+                        // Engine candidates sit underneath the user's annotations.
+                        for arrow in &prepaint.engine_arrows {
+                            GpuiP1Shell::paint_engine_arrow(
+                                window,
+                                *arrow,
+                                prepaint.board_px,
+                                prepaint.flipped,
+                                bounds.origin,
+                            );
+                        }
                         for shape in &prepaint.shapes {
                             GpuiP1Shell::paint_shape(
                                 window,
                                 *shape,
                                 prepaint.board_px,
+                                prepaint.flipped,
                                 false,
                                 bounds.origin,
                             );
@@ -233,6 +259,7 @@ impl GpuiP1Shell {
                                 window,
                                 current,
                                 prepaint.board_px,
+                                prepaint.flipped,
                                 bounds.origin,
                             );
                         }
@@ -271,7 +298,7 @@ impl GpuiP1Shell {
             .w(px(size))
             .h(px(size))
             .child(
-                img(piece_asset_path(piece, self.piece_wallpaper))
+                img(piece_asset_path(piece, self.piece_wallpaper, self.flipped))
                     .w_full()
                     .h_full()
                     .object_fit(ObjectFit::Contain)
@@ -324,7 +351,7 @@ impl GpuiP1Shell {
             .items_center()
             .justify_center()
             .child(
-                img(piece_asset_path(piece, self.piece_wallpaper))
+                img(piece_asset_path(piece, self.piece_wallpaper, self.flipped))
                     .w(px(HAND_PIECE_PX))
                     .h(px(HAND_PIECE_PX))
                     .object_fit(ObjectFit::Contain)
@@ -412,7 +439,11 @@ impl GpuiP1Shell {
                     .justify_center()
                     .text_size(px(11.0))
                     .text_color(hsla(0.0, 0.0, 0.76, 0.82))
-                    .child((BOARD_SIZE - file).to_string()),
+                    .child(if self.flipped {
+                        (file + 1).to_string()
+                    } else {
+                        (BOARD_SIZE - file).to_string()
+                    }),
             );
         }
         row
@@ -435,7 +466,11 @@ impl GpuiP1Shell {
                     .justify_center()
                     .text_size(px(11.0))
                     .text_color(hsla(0.0, 0.0, 0.76, 0.82))
-                    .child((ui_row + 1).to_string()),
+                    .child(if self.flipped {
+                        (BOARD_SIZE - ui_row).to_string()
+                    } else {
+                        (ui_row + 1).to_string()
+                    }),
             );
         }
         strip
@@ -459,7 +494,7 @@ impl GpuiP1Shell {
         for rank in 0..BOARD_SIZE {
             let mut row = h_flex().gap_0();
             for file in 0..BOARD_SIZE {
-                let sq = Self::square_from_ui(rank, file);
+                let sq = Self::square_from_ui(rank, file, self.flipped);
                 let piece = self.game.piece_at(sq);
                 let mut cell = div()
                     .id(("sq", (rank as usize) * BOARD_SIZE as usize + file as usize))
@@ -481,7 +516,7 @@ impl GpuiP1Shell {
                 );
                 if let Some(p) = piece.filter(|_| !dragging_from_sq) {
                     cell = cell.child(
-                        img(piece_asset_path(p, self.piece_wallpaper))
+                        img(piece_asset_path(p, self.piece_wallpaper, self.flipped))
                             .w(px(PIECE_PX))
                             .h(px(PIECE_PX))
                             .object_fit(ObjectFit::Contain),
@@ -515,7 +550,8 @@ impl GpuiP1Shell {
         }
 
         let board_px = CELL_PX * BOARD_SIZE as f32;
-        let scene_w = board_px + HAND_COL_W * 2.0 + SCENE_GAP_PX + Self::board_to_right_hand_gap();
+        let scene_w = Self::scene_width(board_px);
+        let (left_hand, right_hand) = Self::hand_sides(self.flipped);
         let board_panel = div()
             .relative()
             .w(px(board_px))
@@ -545,11 +581,11 @@ impl GpuiP1Shell {
                 h_flex()
                     .gap_0()
                     .items_start()
-                    .child(self.render_hand_panel(Color::White, board_px, cx))
+                    .child(self.render_hand_panel(left_hand, board_px, cx))
                     .child(div().w(px(SCENE_GAP_PX)).h(px(board_px)))
                     .child(board_panel)
                     .child(self.render_right_rank_coords(board_px))
-                    .child(self.render_hand_panel(Color::Black, board_px, cx)),
+                    .child(self.render_hand_panel(right_hand, board_px, cx)),
             )
             .child(self.render_top_file_coords(board_px))
             .child(self.render_shapes_overlay(board_px, scene_w))
@@ -563,7 +599,9 @@ impl GpuiP1Shell {
             format!("Status: {}", self.game.status())
         };
         let selected_hand_line = match self.game.selected_hand_piece() {
-            Some(piece_type) => format!("Selected hand: {}", piece_type_label(piece_type)),
+            Some(piece_type) => {
+                format!("Selected hand: {}", self.notation.piece_name(piece_type))
+            }
             None => "Selected hand: none".to_string(),
         };
 
@@ -621,10 +659,12 @@ impl GpuiP1Shell {
             )
             .child(
                 div().w_full().flex_1().child(
-                    h_flex()
+                    v_flex()
                         .w_full()
-                        .justify_center()
-                        .child(self.render_board_scene(cx)),
+                        .items_center()
+                        .gap_3()
+                        .child(self.render_board_scene(cx))
+                        .child(self.render_clocks(cx)),
                 ),
             )
             .into_any_element()
@@ -633,6 +673,11 @@ impl GpuiP1Shell {
     pub(crate) fn render_right_sidebar(&self, cx: &App) -> AnyElement {
         let turn = format!("Turn: {:?}", self.game.side_to_move());
         let ply = format!("Ply: {}", self.game.ply());
+        let history = format!(
+            "History: {}/{}",
+            self.game.current_ply(),
+            self.game.history().len()
+        );
         let status_line = if self.game.status().is_empty() {
             "Status: ready".to_string()
         } else {
@@ -661,6 +706,7 @@ impl GpuiP1Shell {
             )
             .child(turn)
             .child(ply)
+            .child(history)
             .child(status_line)
             .child(
                 div()
@@ -678,12 +724,40 @@ impl GpuiP1Shell {
             .into_any_element()
     }
 
-    pub(crate) fn render_bottom_panel(&self, cx: &App) -> AnyElement {
+    pub(crate) fn render_bottom_panel(&self, board: Entity<Self>, cx: &App) -> AnyElement {
         let status_line = if self.game.status().is_empty() {
             "ready".to_string()
         } else {
             self.game.status().to_string()
         };
+        let tab_button = |id: &'static str, label: &'static str, tab: BottomTab| {
+            let board = board.clone();
+            let button = Button::new(id).label(label).on_click(move |_, _, cx| {
+                board.update(cx, |board, cx| {
+                    board.bottom_tab = tab;
+                    cx.notify();
+                });
+            });
+            if self.bottom_tab == tab {
+                button.primary()
+            } else {
+                button.ghost()
+            }
+        };
+        let body = match self.bottom_tab {
+            BottomTab::Console => v_flex()
+                .gap_2()
+                .child(format!(
+                    "engine: {} | game status: {status_line}",
+                    self.engine_status_text()
+                ))
+                .children(self.match_status_text())
+                .child("workspace initialized: dock layout active")
+                .into_any_element(),
+            BottomTab::Analysis => self.render_analysis_tab(board.clone(), cx),
+            BottomTab::Moves => self.render_moves_tab(board.clone(), cx),
+            BottomTab::Tsume => self.render_tsume_tab(board.clone(), cx),
+        };
 
         v_flex()
             .size_full()
@@ -697,13 +771,25 @@ impl GpuiP1Shell {
                 h_flex()
                     .items_center()
                     .gap_2()
-                    .child(Button::new("bottom-tab-console").ghost().label("Console"))
-                    .child(Button::new("bottom-tab-analysis").ghost().label("Analysis"))
-                    .child(Button::new("bottom-tab-moves").ghost().label("Moves")),
+                    .child(tab_button(
+                        "bottom-tab-console",
+                        "Console",
+                        BottomTab::Console,
+                    ))
+                    .child(tab_button(
+                        "bottom-tab-analysis",
+                        "Analysis",
+                        BottomTab::Analysis,
+                    ))
+                    .child(tab_button("bottom-tab-moves", "Moves", BottomTab::Moves))
+                    .children(
+                        self.tsume_trainer
+                            .is_some()
+                            .then(|| tab_button("bottom-tab-tsume", "Tsume", BottomTab::Tsume)),
+                    ),
             )
             .child(div().h(px(1.0)).w_full().bg(cx.theme().border))
-            .child(format!("engine: idle | game status: {status_line}"))
-            .child("workspace initialized: dock layout active")
+            .child(body)
             .into_any_element()
     }
 }
@@ -726,10 +812,14 @@ impl Render for GpuiP1Shell {
         for color in [Color::Black, Color::White] {
             for piece_type in HAND_PIECES {
                 preload = preload.child(
-                    img(piece_asset_path(Piece { piece_type, color }, self.piece_wallpaper))
-                        .w(px(1.0))
-                        .h(px(1.0))
-                        .object_fit(ObjectFit::Contain),
+                    img(piece_asset_path(
+                        Piece { piece_type, color },
+                        self.piece_wallpaper,
+                        self.flipped,
+                    ))
+                    .w(px(1.0))
+                    .h(px(1.0))
+                    .object_fit(ObjectFit::Contain),
                 );
             }
         }
@@ -784,5 +874,10 @@ impl Render for GpuiP1Shell {
             .size_full()
             .child(content_layer)
             .child(self.render_drag_overlay())
+            .child(self.render_engine_game_dialog(cx))
+            .child(self.render_match_dialog(cx))
+            .child(self.render_engine_settings_dialog(cx))
+            .child(self.render_board_editor_dialog(cx))
+            .child(self.render_tsume_solver_dialog(cx))
     }
 }
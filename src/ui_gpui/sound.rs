@@ -37,6 +37,9 @@ fn sound_path(cue: SoundCue) -> PathBuf {
         SoundCue::Move => "move.ogg",
         SoundCue::Capture => "capture.ogg",
         SoundCue::Error => "error.ogg",
+        SoundCue::Victory => "victory.ogg",
+        SoundCue::Defeat => "defeat.ogg",
+        SoundCue::Draw => "draw.ogg",
     };
     PathBuf::from("assets")
         .join("sounds")
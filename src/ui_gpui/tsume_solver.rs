@@ -0,0 +1,205 @@
+use gpui::{prelude::FluentBuilder as _, *};
+use gpui_component::{
+    ActiveTheme as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+use shogi::Square;
+
+use crate::core::tsume::{self, TsumeOptions, TsumeResult, TsumeSolution};
+use crate::core::usi::{japanese_pv, move_to_usi};
+
+use super::model::GpuiP1Shell;
+
+/// Open "Solve Mate" dialog.
+pub(crate) struct TsumeSolverDialog {
+    /// Ply of the record the problem was taken from.
+    ply: usize,
+    last_to: Option<Square>,
+    /// `None` while the solver runs.
+    result: Option<Result<TsumeResult, String>>,
+}
+
+impl GpuiP1Shell {
+    /// Solves the displayed position on a background thread, with its side to
+    /// move as the attacker.
+    pub(crate) fn open_tsume_solver(&mut self, cx: &mut Context<Self>) {
+        self.tsume_generation += 1;
+        let generation = self.tsume_generation;
+        let sfen = self.game.to_sfen();
+        self.tsume_solver = Some(TsumeSolverDialog {
+            ply: self.game.current_ply(),
+            last_to: self.game.last_action_to(),
+            result: None,
+        });
+
+        cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    tsume::solve_sfen(&sfen, &TsumeOptions::default())
+                        .map_err(|err| err.to_string())
+                })
+                .await;
+            let _ = this.update(cx, |shell, cx| {
+                if shell.tsume_generation != generation {
+                    return;
+                }
+                if let Some(dialog) = &mut shell.tsume_solver {
+                    dialog.result = Some(result);
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn close_tsume_solver(&mut self) {
+        // The search cannot be interrupted; its result is dropped on arrival.
+        self.tsume_generation += 1;
+        self.tsume_solver = None;
+    }
+
+    /// Records the solution as moves from the problem position, then shows
+    /// that position again.
+    fn add_tsume_solution(&mut self, solution: &TsumeSolution, ply: usize) {
+        self.drag = None;
+        self.pause_clock();
+        self.cancel_engine_search();
+        self.game.jump_to_ply(ply);
+        let played = solution
+            .moves
+            .iter()
+            .take_while(|&&mv| self.game.push_move(mv).is_ok())
+            .count();
+        self.game.jump_to_ply(ply);
+        if played < solution.length() {
+            // Defender drops of pieces the record does not hand out.
+            self.game.set_status(format!(
+                "Added {played} of {} moves; the rest need pieces the defender does not hold",
+                solution.length()
+            ));
+        } else {
+            self.game.set_status("Solution added to the record");
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn render_tsume_solver_dialog(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(dialog) = &self.tsume_solver else {
+            return div().into_any_element();
+        };
+
+        let muted = cx.theme().muted_foreground;
+        let mut body = v_flex().gap_2().text_size(px(13.0));
+        let mut solution = None;
+        match &dialog.result {
+            None => body = body.child("Searching…"),
+            Some(Err(message)) => {
+                body = body.child(div().text_color(cx.theme().danger).child(message.clone()))
+            }
+            Some(Ok(TsumeResult::NoMate)) => {
+                body = body.child("No mate: every sequence of checks can be escaped.")
+            }
+            Some(Ok(TsumeResult::Unknown)) => {
+                body = body.child("No answer within the search limit.")
+            }
+            Some(Ok(TsumeResult::Mate(found))) => {
+                let usi: Vec<String> = found.moves.iter().map(|&mv| move_to_usi(mv)).collect();
+                let line = japanese_pv(&found.start_sfen, dialog.last_to, &usi);
+                body = body
+                    .child(format!(
+                        "Mate in {}{}",
+                        found.length(),
+                        if found.is_unique() {
+                            ""
+                        } else {
+                            " — alternative solutions (余詰)"
+                        }
+                    ))
+                    .child(div().child(line.join(" ")));
+                for alternative in &found.alternatives {
+                    let mut prefix = usi[..alternative.ply].to_vec();
+                    prefix.push(move_to_usi(alternative.mv));
+                    let text = japanese_pv(&found.start_sfen, dialog.last_to, &prefix)
+                        .pop()
+                        .unwrap_or_default();
+                    body = body.child(
+                        div()
+                            .text_color(cx.theme().danger)
+                            .child(format!("Move {}: {text} also mates", alternative.ply + 1)),
+                    );
+                }
+                body = body.child(
+                    div()
+                        .text_size(px(11.0))
+                        .text_color(muted)
+                        .child(format!("{} positions searched", found.nodes)),
+                );
+                solution = Some(found.clone());
+            }
+        }
+
+        let ply = dialog.ply;
+        let card = v_flex()
+            .id("tsume-solver-card")
+            .w(px(520.0))
+            .gap_3()
+            .p_4()
+            .rounded_lg()
+            .border_1()
+            .border_color(cx.theme().border)
+            .bg(cx.theme().background)
+            .text_color(cx.theme().foreground)
+            .on_click(cx.listener(|_, _: &ClickEvent, _, cx| {
+                cx.stop_propagation();
+            }))
+            .child(div().text_size(px(15.0)).child("Solve Mate"))
+            .child(
+                div()
+                    .text_size(px(12.0))
+                    .text_color(muted)
+                    .child("The side to move attacks with checks only; the defender holds every piece left over."),
+            )
+            .child(body)
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .when_some(solution, |row, solution| {
+                        row.child(
+                            Button::new("tsume-add-solution")
+                                .ghost()
+                                .label("Add to Record")
+                                .on_click(cx.listener(move |this, _: &ClickEvent, _, cx| {
+                                    this.add_tsume_solution(&solution, ply);
+                                    this.close_tsume_solver();
+                                    cx.notify();
+                                })),
+                        )
+                    })
+                    .child(
+                        Button::new("tsume-close")
+                            .primary()
+                            .label("Close")
+                            .on_click(cx.listener(|this, _: &ClickEvent, _, cx| {
+                                this.close_tsume_solver();
+                                cx.notify();
+                            })),
+                    ),
+            );
+
+        div()
+            .id("tsume-solver-overlay")
+            .absolute()
+            .left_0()
+            .top_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(hsla(0.0, 0.0, 0.0, 0.45))
+            .child(card)
+            .into_any_element()
+    }
+}
@@ -0,0 +1,376 @@
+use gpui::*;
+use gpui_component::{
+    ActiveTheme as _, Sizable as _,
+    button::{Button, ButtonVariants},
+    h_flex, v_flex,
+};
+use shogi::{Color, Move};
+use std::path::Path;
+
+use crate::core::game::{EndReason, GameState, color_name};
+use crate::core::problems::{self, Problem, ProblemStatus, TsumeProgress};
+use crate::core::tsume::{self, TsumeOptions, TsumeResult};
+
+use super::model::{BottomTab, GpuiP1Shell};
+
+/// Where the current attempt stands.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Attempt {
+    /// The set gave no solution and the solver is looking for one.
+    Solving,
+    /// Neither the set nor the solver has a solution to check against.
+    NoSolution,
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// Tsume trainer: the user plays the attacker and the defender answers
+/// from the solution.
+pub(crate) struct TsumeTrainer {
+    problems: Vec<Problem>,
+    index: usize,
+    progress: TsumeProgress,
+    attacker: Color,
+    solution: Vec<Move>,
+    attempt: Attempt,
+    /// Bumped whenever a problem starts so stale solver results are dropped.
+    generation: u64,
+}
+
+impl GpuiP1Shell {
+    pub(crate) fn prompt_open_tsume_set(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Open Tsume Problems".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update(cx, |shell, cx| {
+                shell.open_tsume_set(&path, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Loads a problem set and starts on its first problem not yet solved.
+    fn open_tsume_set(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let problems = match problems::load_problems(path) {
+            Ok(problems) => problems,
+            Err(err) => {
+                self.game
+                    .report_error(format!("Failed to load {}: {err}", path.display()));
+                self.play_pending_sound();
+                return;
+            }
+        };
+        let progress = TsumeProgress::load();
+        let index = problems
+            .iter()
+            .position(|problem| progress.record(&problem.sfen).status != ProblemStatus::Solved)
+            .unwrap_or(0);
+        self.tsume_trainer = Some(TsumeTrainer {
+            problems,
+            index,
+            progress,
+            attacker: Color::Black,
+            solution: Vec::new(),
+            attempt: Attempt::Solving,
+            generation: 0,
+        });
+        self.bottom_tab = BottomTab::Tsume;
+        self.start_tsume_problem(index, cx);
+    }
+
+    pub(crate) fn close_tsume_trainer(&mut self) {
+        if self.tsume_trainer.take().is_some() && self.bottom_tab == BottomTab::Tsume {
+            self.bottom_tab = BottomTab::Moves;
+        }
+    }
+
+    /// Shows a problem position like any new game, but keeps the trainer.
+    fn show_tsume_position(&mut self, game: GameState) {
+        let trainer = self.tsume_trainer.take();
+        self.replace_game(game);
+        self.tsume_trainer = trainer;
+    }
+
+    /// Sets up problem `index` afresh, solving it first when the set gave no
+    /// solution.
+    fn start_tsume_problem(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(trainer) = &mut self.tsume_trainer else {
+            return;
+        };
+        let Some(problem) = trainer.problems.get(index).cloned() else {
+            return;
+        };
+        let game = match GameState::from_sfen(&problem.sfen) {
+            Ok(game) => game,
+            Err(err) => {
+                trainer.attempt = Attempt::NoSolution;
+                self.game
+                    .report_error(format!("Problem {}: {err}", index + 1));
+                self.play_pending_sound();
+                return;
+            }
+        };
+        trainer.index = index;
+        trainer.generation += 1;
+        trainer.attacker = game.side_to_move();
+        trainer.solution = problem.solution.clone();
+        trainer.attempt = if trainer.solution.is_empty() {
+            Attempt::Solving
+        } else {
+            Attempt::Playing
+        };
+        let generation = trainer.generation;
+        let solving = trainer.attempt == Attempt::Solving;
+        self.show_tsume_position(game);
+        self.game.set_status(format!(
+            "{}: {} to mate",
+            problem.title,
+            color_name(self.game.side_to_move())
+        ));
+        if !solving {
+            return;
+        }
+
+        cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    tsume::solve_sfen(&problem.sfen, &TsumeOptions::default())
+                        .map_err(|err| err.to_string())
+                })
+                .await;
+            let _ = this.update(cx, |shell, cx| {
+                shell.on_tsume_problem_solved(generation, result);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn on_tsume_problem_solved(&mut self, generation: u64, result: Result<TsumeResult, String>) {
+        let Some(trainer) = &mut self.tsume_trainer else {
+            return;
+        };
+        if trainer.generation != generation {
+            return;
+        }
+        let solution = match result {
+            Ok(TsumeResult::Mate(solution)) => solution,
+            Ok(_) => {
+                trainer.attempt = Attempt::NoSolution;
+                self.game
+                    .set_status("The solver found no mate in this problem");
+                return;
+            }
+            Err(message) => {
+                trainer.attempt = Attempt::NoSolution;
+                self.game.report_error(message);
+                return;
+            }
+        };
+        let length = solution.length();
+        trainer.solution = solution.moves;
+        trainer.attempt = Attempt::Playing;
+        // The solver hands the defender the pieces left over.
+        if let Ok(game) = GameState::from_sfen(&solution.start_sfen) {
+            self.show_tsume_position(game);
+        }
+        self.game.set_status(format!(
+            "{} to mate in {}",
+            color_name(self.game.side_to_move()),
+            length
+        ));
+    }
+
+    /// Board input waits while the solver runs, once the attempt is over and
+    /// whenever the defender is to move.
+    pub(crate) fn is_tsume_locked(&self) -> bool {
+        self.tsume_trainer.as_ref().is_some_and(|trainer| {
+            trainer.attempt != Attempt::Playing || self.game.side_to_move() != trainer.attacker
+        })
+    }
+
+    /// Checks the attacker's move against the solution and answers with the
+    /// defender's reply.
+    pub(crate) fn on_tsume_move(&mut self) {
+        let Some(trainer) = &mut self.tsume_trainer else {
+            return;
+        };
+        if trainer.attempt != Attempt::Playing {
+            return;
+        }
+        let ply = self.game.current_ply();
+        let played = &self.game.history()[..ply];
+        let on_solution = played.len() <= trainer.solution.len()
+            && played
+                .iter()
+                .zip(&trainer.solution)
+                .all(|(entry, &mv)| entry.mv == mv);
+        // Another mating move at the very end still solves the problem.
+        let other_mate = ply == trainer.solution.len()
+            && played[..ply - 1]
+                .iter()
+                .zip(&trainer.solution)
+                .all(|(entry, &mv)| entry.mv == mv)
+            && self.game.result().is_some_and(|result| {
+                result.winner == Some(trainer.attacker) && result.reason == EndReason::Checkmate
+            });
+
+        let solved = if !on_solution && !other_mate {
+            false
+        } else if ply >= trainer.solution.len() {
+            true
+        } else {
+            let reply = trainer.solution[ply];
+            if !self.game.play_move(reply) {
+                trainer.attempt = Attempt::NoSolution;
+                self.game
+                    .report_error("The solution's next move is not legal here");
+                self.play_pending_sound();
+                return;
+            }
+            self.play_pending_sound();
+            return;
+        };
+
+        let sfen = trainer.problems[trainer.index].sfen.clone();
+        trainer.progress.record_attempt(&sfen, solved);
+        let saved = trainer.progress.save();
+        if solved {
+            trainer.attempt = Attempt::Solved;
+            self.game.set_status("Solved!");
+        } else {
+            trainer.attempt = Attempt::Failed;
+            self.game.report_error("Not the solution");
+        }
+        if let Err(err) = saved {
+            self.game
+                .report_error(format!("Saving tsume progress failed: {err}"));
+        }
+        self.play_pending_sound();
+    }
+
+    pub(crate) fn render_tsume_tab(&self, board: Entity<Self>, cx: &App) -> AnyElement {
+        let Some(trainer) = &self.tsume_trainer else {
+            return div().into_any_element();
+        };
+        let problem = &trainer.problems[trainer.index];
+        let solved = trainer
+            .problems
+            .iter()
+            .filter(|problem| {
+                trainer.progress.record(&problem.sfen).status == ProblemStatus::Solved
+            })
+            .count();
+        let record = trainer.progress.record(&problem.sfen);
+        let state = match trainer.attempt {
+            Attempt::Solving => "Solving…".to_string(),
+            Attempt::NoSolution => "No solution to check against".to_string(),
+            Attempt::Playing => format!(
+                "{} to mate in {}",
+                color_name(trainer.attacker),
+                trainer.solution.len()
+            ),
+            Attempt::Solved => "Solved".to_string(),
+            Attempt::Failed => "Not the solution".to_string(),
+        };
+
+        let action = |id: &'static str, label: &'static str, index: usize| {
+            let board = board.clone();
+            Button::new(id)
+                .small()
+                .ghost()
+                .label(label)
+                .on_click(move |_, _, cx| {
+                    board.update(cx, |board, cx| {
+                        board.start_tsume_problem(index, cx);
+                        cx.notify();
+                    });
+                })
+        };
+        let last = trainer.problems.len() - 1;
+        let close = {
+            let board = board.clone();
+            Button::new("tsume-close")
+                .small()
+                .ghost()
+                .label("Close Trainer")
+                .on_click(move |_, _, cx| {
+                    board.update(cx, |board, cx| {
+                        board.close_tsume_trainer();
+                        cx.notify();
+                    });
+                })
+        };
+
+        let mut list = h_flex().flex_wrap().gap_1();
+        for (index, problem) in trainer.problems.iter().enumerate() {
+            let mark = match trainer.progress.record(&problem.sfen).status {
+                ProblemStatus::Unsolved => "",
+                ProblemStatus::Failed => " ✗",
+                ProblemStatus::Solved => " ✓",
+            };
+            let board = board.clone();
+            let button = Button::new(("tsume-problem", index))
+                .xsmall()
+                .label(format!("{}{mark}", index + 1))
+                .on_click(move |_, _, cx| {
+                    board.update(cx, |board, cx| {
+                        board.start_tsume_problem(index, cx);
+                        cx.notify();
+                    });
+                });
+            list = list.child(if index == trainer.index {
+                button.primary()
+            } else {
+                button.ghost()
+            });
+        }
+
+        v_flex()
+            .gap_2()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(format!(
+                        "{} ({} of {})",
+                        problem.title,
+                        trainer.index + 1,
+                        trainer.problems.len()
+                    ))
+                    .child(div().text_color(cx.theme().muted_foreground).child(format!(
+                        "{} · {} attempts · {solved} of {} solved",
+                        record.status.label(),
+                        record.attempts,
+                        trainer.problems.len()
+                    ))),
+            )
+            .child(state)
+            .child(
+                h_flex()
+                    .gap_1()
+                    .child(action(
+                        "tsume-previous",
+                        "Previous",
+                        trainer.index.saturating_sub(1),
+                    ))
+                    .child(action("tsume-retry", "Retry", trainer.index))
+                    .child(action("tsume-next", "Next", (trainer.index + 1).min(last)))
+                    .child(close),
+            )
+            .child(list)
+            .into_any_element()
+    }
+}
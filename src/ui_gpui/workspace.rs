@@ -8,13 +8,24 @@ use gpui_component::{
     v_flex,
 };
 
+use crate::core::game::ImpasseRule;
+use crate::core::handicap::HANDICAPS;
+use crate::core::notation::Notation;
+use crate::core::usi;
+
 use super::assets::{BoardWallpaper, PieceWallpaper};
-use super::model::GpuiP1Shell;
+use super::game_clock::CLOCK_PRESETS;
+use super::keymap::{
+    Cancel, ClearDrawings, FlipBoard, FocusMoveInput, GoToEnd, GoToStart, KEY_CONTEXT,
+    StepBackward, StepForward, ToggleAnalysis, ToggleBottomDock, ToggleRightDock,
+};
+use super::model::{BottomTab, GpuiP1Shell};
 
 const RSHOGI_DOCK_ID: &str = "rshogi-main-dock";
 const RSHOGI_DOCK_VERSION: usize = 1;
 
 pub(crate) struct GpuiDockWorkspace {
+    focus_handle: FocusHandle,
     dock_area: Entity<DockArea>,
     board: Entity<GpuiP1Shell>,
 }
@@ -39,6 +50,7 @@ struct BottomDockPanel {
 impl GpuiDockWorkspace {
     pub(crate) fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let board = cx.new(|_| GpuiP1Shell::new());
+        board.update(cx, |board, cx| board.init_move_input(window, cx));
         let center_panel = cx.new(|cx| BoardDockPanel::new(board.clone(), cx));
         let right_panel = cx.new(|cx| RightDockPanel::new(board.clone(), cx));
         let bottom_panel = cx.new(|cx| BottomDockPanel::new(board.clone(), cx));
@@ -80,7 +92,129 @@ impl GpuiDockWorkspace {
             dock.set_toggle_button_visible(false, cx);
         });
 
-        Self { dock_area, board }
+        // Keyboard shortcuts need a focused element inside the workspace.
+        let focus_handle = cx.focus_handle();
+        window.focus(&focus_handle);
+
+        Self {
+            focus_handle,
+            dock_area,
+            board,
+        }
+    }
+
+    fn step_backward(&mut self, _: &StepBackward, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            let ply = board.game.current_ply();
+            if ply > 0 {
+                board.go_to_ply(ply - 1);
+                cx.notify();
+            }
+        });
+    }
+
+    fn step_forward(&mut self, _: &StepForward, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            let ply = board.game.current_ply();
+            if ply < board.game.history().len() {
+                board.go_to_ply(ply + 1);
+                cx.notify();
+            }
+        });
+    }
+
+    fn go_to_start(&mut self, _: &GoToStart, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            board.go_to_ply(0);
+            cx.notify();
+        });
+    }
+
+    fn go_to_end(&mut self, _: &GoToEnd, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            let end = board.game.history().len();
+            board.go_to_ply(end);
+            cx.notify();
+        });
+    }
+
+    fn flip_board(&mut self, _: &FlipBoard, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            board.flip_board();
+            cx.notify();
+        });
+    }
+
+    fn toggle_right_dock(
+        &mut self,
+        _: &ToggleRightDock,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.dock_area.update(cx, |dock, cx| {
+            dock.toggle_dock(DockPlacement::Right, window, cx);
+        });
+        cx.notify();
+    }
+
+    fn toggle_bottom_dock(
+        &mut self,
+        _: &ToggleBottomDock,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.dock_area.update(cx, |dock, cx| {
+            dock.toggle_dock(DockPlacement::Bottom, window, cx);
+        });
+        cx.notify();
+    }
+
+    fn toggle_analysis(&mut self, _: &ToggleAnalysis, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            if board.is_analyzing() {
+                board.stop_analysis();
+            } else {
+                board.prompt_start_analysis(cx);
+            }
+            cx.notify();
+        });
+    }
+
+    fn cancel(&mut self, _: &Cancel, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            board.cancel_input();
+            cx.notify();
+        });
+    }
+
+    fn clear_drawings(&mut self, _: &ClearDrawings, _: &mut Window, cx: &mut Context<Self>) {
+        self.board.update(cx, |board, cx| {
+            board.clear_drawings();
+            cx.notify();
+        });
+    }
+
+    /// Shows the Moves tab and puts the cursor in its move box.
+    fn focus_move_input(
+        &mut self,
+        _: &FocusMoveInput,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.dock_area.update(cx, |dock, cx| {
+            if !dock.is_dock_open(DockPlacement::Bottom, cx) {
+                dock.toggle_dock(DockPlacement::Bottom, window, cx);
+            }
+        });
+        let input = self.board.update(cx, |board, cx| {
+            board.bottom_tab = BottomTab::Moves;
+            cx.notify();
+            board.move_input.as_ref().map(|input| input.state.clone())
+        });
+        if let Some(input) = input {
+            let handle = input.read(cx).focus_handle(cx);
+            window.focus(&handle);
+        }
     }
 
     fn render_panel_toggle_buttons(&self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -162,21 +296,119 @@ impl GpuiDockWorkspace {
     }
 
     fn render_file_menu(&self, cx: &App) -> impl IntoElement {
-        Self::menu_trigger_button(cx, "menu-file", "File").dropdown_menu(|menu, _, _| {
-            menu.item(PopupMenuItem::new("New Game").disabled(true))
-                .item(PopupMenuItem::new("Open Record").disabled(true))
-                .separator()
-                .item(PopupMenuItem::new("Save Record").disabled(true))
+        let board_entity = self.board.clone();
+        Self::menu_trigger_button(cx, "menu-file", "File").dropdown_menu(move |menu, window, cx| {
+            let board_for_new = board_entity.clone();
+            let board_for_open = board_entity.clone();
+            let board_for_save = board_entity.clone();
+            let board_for_editor = board_entity.clone();
+            menu.submenu("New Game", window, cx, move |mut submenu, _, _| {
+                for handicap in HANDICAPS {
+                    let board = board_for_new.clone();
+                    submenu = submenu.item(PopupMenuItem::new(handicap.name).on_click(
+                        move |_, _, cx| {
+                            board.update(cx, |board, cx| {
+                                board.new_game(handicap);
+                                cx.notify();
+                            });
+                        },
+                    ));
+                }
+                submenu
+            })
+            .item(PopupMenuItem::new("Open Record").on_click(move |_, _, cx| {
+                prompt_open_record(board_for_open.clone(), cx);
+            }))
+            .item(
+                PopupMenuItem::new("Set Up Position…").on_click(move |_, _, cx| {
+                    board_for_editor.update(cx, |board, cx| {
+                        board.open_board_editor();
+                        cx.notify();
+                    });
+                }),
+            )
+            .separator()
+            .item(PopupMenuItem::new("Save Record").on_click(move |_, _, cx| {
+                prompt_save_record(board_for_save.clone(), cx);
+            }))
         })
     }
 
     fn render_edit_menu(&self, cx: &App) -> impl IntoElement {
-        Self::menu_trigger_button(cx, "menu-edit", "Edit").dropdown_menu(|menu, _, _| {
-            menu.item(PopupMenuItem::new("Undo").disabled(true))
-                .item(PopupMenuItem::new("Redo").disabled(true))
-                .separator()
-                .item(PopupMenuItem::new("Copy").disabled(true))
-                .item(PopupMenuItem::new("Paste").disabled(true))
+        let board_entity = self.board.clone();
+        Self::menu_trigger_button(cx, "menu-edit", "Edit").dropdown_menu(move |menu, _, cx| {
+            let (can_undo, can_redo) = {
+                let game = &board_entity.read(cx).game;
+                (game.can_undo(), game.can_redo())
+            };
+            let board_for_undo = board_entity.clone();
+            let board_for_redo = board_entity.clone();
+            let board_for_copy = board_entity.clone();
+            let board_for_paste = board_entity.clone();
+            let board_for_copy_usi = board_entity.clone();
+            let board_for_paste_usi = board_entity.clone();
+            menu.item(
+                PopupMenuItem::new("Undo")
+                    .disabled(!can_undo)
+                    .on_click(move |_, _, cx| {
+                        board_for_undo.update(cx, |board, cx| {
+                            board.undo_move();
+                            cx.notify();
+                        });
+                    }),
+            )
+            .item(
+                PopupMenuItem::new("Redo")
+                    .disabled(!can_redo)
+                    .on_click(move |_, _, cx| {
+                        board_for_redo.update(cx, |board, cx| {
+                            board.redo_move();
+                            cx.notify();
+                        });
+                    }),
+            )
+            .separator()
+            .item(PopupMenuItem::new("Copy").on_click(move |_, _, cx| {
+                let sfen = board_for_copy.read(cx).game.to_sfen();
+                cx.write_to_clipboard(ClipboardItem::new_string(sfen));
+                board_for_copy.update(cx, |board, cx| {
+                    board.game.set_status("SFEN copied to clipboard");
+                    cx.notify();
+                });
+            }))
+            .item(PopupMenuItem::new("Paste").on_click(move |_, _, cx| {
+                let text = cx
+                    .read_from_clipboard()
+                    .and_then(|item| item.text())
+                    .unwrap_or_default();
+                board_for_paste.update(cx, |board, cx| {
+                    board.load_sfen(&text);
+                    cx.notify();
+                });
+            }))
+            .separator()
+            .item(
+                PopupMenuItem::new("Copy USI Position").on_click(move |_, _, cx| {
+                    let command = usi::position_command(&board_for_copy_usi.read(cx).game);
+                    cx.write_to_clipboard(ClipboardItem::new_string(command));
+                    board_for_copy_usi.update(cx, |board, cx| {
+                        board.game.set_status("USI position copied to clipboard");
+                        cx.notify();
+                    });
+                }),
+            )
+            .item(
+                PopupMenuItem::new("Paste USI Position").on_click(move |_, _, cx| {
+                    let text = cx
+                        .read_from_clipboard()
+                        .and_then(|item| item.text())
+                        .unwrap_or_default();
+                    board_for_paste_usi.update(cx, |board, cx| {
+                        board.load_usi_position(&text);
+                        cx.notify();
+                    });
+                }),
+            )
         })
     }
 
@@ -184,6 +416,7 @@ impl GpuiDockWorkspace {
         let board_entity = self.board.clone();
         Self::menu_trigger_button(cx, "menu-view", "View").dropdown_menu(move |menu, window, cx| {
             let board_for_appearance = board_entity.clone();
+            let board_for_notation = board_entity.clone();
             menu.submenu("Appearance", window, cx, move |submenu, window, cx| {
                 let selected_piece_wallpaper = board_for_appearance.read(cx).piece_wallpaper();
                 let selected_board_wallpaper = board_for_appearance.read(cx).board_wallpaper();
@@ -235,6 +468,24 @@ impl GpuiDockWorkspace {
 
                 submenu
             })
+            .submenu("Notation", window, cx, move |submenu, _, cx| {
+                let selected = board_for_notation.read(cx).notation;
+                let mut submenu = submenu.check_side(Side::Left);
+                for notation in Notation::all() {
+                    let board = board_for_notation.clone();
+                    submenu = submenu.item(
+                        PopupMenuItem::new(notation.label())
+                            .checked(notation == selected)
+                            .on_click(move |_, _, cx| {
+                                board.update(cx, |board, cx| {
+                                    board.set_notation(notation);
+                                    cx.notify();
+                                });
+                            }),
+                    );
+                }
+                submenu
+            })
             .submenu("Theme", window, cx, |submenu, _, cx| {
                 let current_name = cx.theme().theme_name().clone();
                 let mut submenu = submenu.check_side(Side::Left);
@@ -260,18 +511,205 @@ impl GpuiDockWorkspace {
     }
 
     fn render_game_menu(&self, cx: &App) -> impl IntoElement {
-        Self::menu_trigger_button(cx, "menu-game", "Game").dropdown_menu(|menu, _, _| {
-            menu.item(PopupMenuItem::new("Resign").disabled(true))
-                .item(PopupMenuItem::new("Offer Draw").disabled(true))
+        let board_entity = self.board.clone();
+        Self::menu_trigger_button(cx, "menu-game", "Game").dropdown_menu(move |menu, window, cx| {
+            let game_over = board_entity.read(cx).game.is_game_over();
+            let analyzing = board_entity.read(cx).is_analyzing();
+            let match_running = board_entity.read(cx).is_match_running();
+            let board_for_engine = board_entity.clone();
+            let board_for_match = board_entity.clone();
+            let board_for_analysis = board_entity.clone();
+            let board_for_resign = board_entity.clone();
+            let board_for_draw = board_entity.clone();
+            let board_for_impasse = board_entity.clone();
+            let board_for_clock = board_entity.clone();
+            let board_for_flip = board_entity.clone();
+            let menu = menu.item(PopupMenuItem::new("New Game vs Engine…").on_click(
+                move |_, _, cx| {
+                    board_for_engine.update(cx, |board, cx| {
+                        board.open_engine_game_dialog();
+                        cx.notify();
+                    });
+                },
+            ));
+            let menu = if match_running {
+                menu.item(
+                    PopupMenuItem::new("Stop Engine Match").on_click(move |_, _, cx| {
+                        board_for_match.update(cx, |board, cx| {
+                            board.stop_engine_match();
+                            cx.notify();
+                        });
+                    }),
+                )
+            } else {
+                menu.item(
+                    PopupMenuItem::new("Engine Match…").on_click(move |_, window, cx| {
+                        board_for_match.update(cx, |board, cx| {
+                            board.open_match_dialog(window, cx);
+                            cx.notify();
+                        });
+                    }),
+                )
+            };
+            let menu = if analyzing {
+                menu.item(
+                    PopupMenuItem::new("Stop Analysis").on_click(move |_, _, cx| {
+                        board_for_analysis.update(cx, |board, cx| {
+                            board.stop_analysis();
+                            cx.notify();
+                        });
+                    }),
+                )
+            } else {
+                menu.submenu("Analyze with Engine", window, cx, move |submenu, _, cx| {
+                    let engines = board_for_analysis.read(cx).engine_registry.engines.clone();
+                    let mut submenu = submenu;
+                    for entry in engines {
+                        let board = board_for_analysis.clone();
+                        submenu = submenu.item(PopupMenuItem::new(entry.name.clone()).on_click(
+                            move |_, _, cx| {
+                                let path = entry.path.clone();
+                                board.update(cx, |board, cx| {
+                                    board.start_analysis(path, cx);
+                                    cx.notify();
+                                });
+                            },
+                        ));
+                    }
+                    let board = board_for_analysis.clone();
+                    submenu.item(PopupMenuItem::new("Other…").on_click(move |_, _, cx| {
+                        board.update(cx, |board, cx| board.prompt_start_analysis(cx));
+                    }))
+                })
+            };
+            menu.separator()
+                .item(
+                    PopupMenuItem::new("Resign")
+                        .disabled(game_over)
+                        .on_click(move |_, _, cx| {
+                            board_for_resign.update(cx, |board, cx| {
+                                board.resign();
+                                cx.notify();
+                            });
+                        }),
+                )
+                .item(
+                    PopupMenuItem::new("Offer Draw")
+                        .disabled(game_over)
+                        .on_click(move |_, _, cx| {
+                            board_for_draw.update(cx, |board, cx| {
+                                board.agree_draw();
+                                cx.notify();
+                            });
+                        }),
+                )
+                .submenu("Declare Impasse", window, cx, move |submenu, _, _| {
+                    let board_for_24 = board_for_impasse.clone();
+                    let board_for_27 = board_for_impasse.clone();
+                    submenu
+                        .item(
+                            PopupMenuItem::new("24-Point Rule")
+                                .disabled(game_over)
+                                .on_click(move |_, _, cx| {
+                                    board_for_24.update(cx, |board, cx| {
+                                        board.declare_impasse(ImpasseRule::TwentyFourPoint);
+                                        cx.notify();
+                                    });
+                                }),
+                        )
+                        .item(
+                            PopupMenuItem::new("27-Point Rule")
+                                .disabled(game_over)
+                                .on_click(move |_, _, cx| {
+                                    board_for_27.update(cx, |board, cx| {
+                                        board.declare_impasse(ImpasseRule::TwentySevenPoint);
+                                        cx.notify();
+                                    });
+                                }),
+                        )
+                })
+                .submenu("Clock", window, cx, move |submenu, _, cx| {
+                    let (has_clock, can_resume) = {
+                        let board = board_for_clock.read(cx);
+                        (board.clock.is_some(), board.can_resume_clock())
+                    };
+                    let mut submenu = submenu;
+                    for control in CLOCK_PRESETS {
+                        let board = board_for_clock.clone();
+                        submenu = submenu.item(PopupMenuItem::new(control.label()).on_click(
+                            move |_, _, cx| {
+                                board.update(cx, |board, cx| {
+                                    board.set_clock(control, cx);
+                                    board.resume_clock();
+                                    cx.notify();
+                                });
+                            },
+                        ));
+                    }
+                    let board_for_resume = board_for_clock.clone();
+                    let board_for_remove = board_for_clock.clone();
+                    submenu
+                        .separator()
+                        .item(
+                            PopupMenuItem::new("Resume Clock")
+                                .disabled(!can_resume)
+                                .on_click(move |_, _, cx| {
+                                    board_for_resume.update(cx, |board, cx| {
+                                        board.resume_clock();
+                                        cx.notify();
+                                    });
+                                }),
+                        )
+                        .item(
+                            PopupMenuItem::new("Remove Clock")
+                                .disabled(!has_clock)
+                                .on_click(move |_, _, cx| {
+                                    board_for_remove.update(cx, |board, cx| {
+                                        board.clear_clock();
+                                        cx.notify();
+                                    });
+                                }),
+                        )
+                })
                 .separator()
-                .item(PopupMenuItem::new("Flip Board").disabled(true))
+                .item(PopupMenuItem::new("Flip Board").on_click(move |_, _, cx| {
+                    board_for_flip.update(cx, |board, cx| {
+                        board.flip_board();
+                        cx.notify();
+                    });
+                }))
         })
     }
 
     fn render_tools_menu(&self, cx: &App) -> impl IntoElement {
-        Self::menu_trigger_button(cx, "menu-tools", "Tools").dropdown_menu(|menu, _, _| {
-            menu.item(PopupMenuItem::new("Engine Settings").disabled(true))
-                .item(PopupMenuItem::new("Board Preferences").disabled(true))
+        let board_entity = self.board.clone();
+        Self::menu_trigger_button(cx, "menu-tools", "Tools").dropdown_menu(move |menu, _, _| {
+            let board_for_engines = board_entity.clone();
+            let board_for_solver = board_entity.clone();
+            let board_for_trainer = board_entity.clone();
+            menu.item(
+                PopupMenuItem::new("Engine Settings…").on_click(move |_, window, cx| {
+                    board_for_engines.update(cx, |board, cx| {
+                        board.open_engine_settings(window, cx);
+                        cx.notify();
+                    });
+                }),
+            )
+            .item(PopupMenuItem::new("Solve Mate…").on_click(move |_, _, cx| {
+                board_for_solver.update(cx, |board, cx| {
+                    board.open_tsume_solver(cx);
+                    cx.notify();
+                });
+            }))
+            .item(
+                PopupMenuItem::new("Tsume Trainer…").on_click(move |_, _, cx| {
+                    board_for_trainer.update(cx, |board, cx| {
+                        board.prompt_open_tsume_set(cx);
+                        cx.notify();
+                    });
+                }),
+            )
+            .item(PopupMenuItem::new("Board Preferences").disabled(true))
         })
     }
 
@@ -459,13 +897,30 @@ impl Render for BottomDockPanel {
             .when(window.is_maximized(), |this| {
                 this.cursor(CursorStyle::default())
             })
-            .child(self.board.read(cx).render_bottom_panel(cx))
+            .child(
+                self.board
+                    .read(cx)
+                    .render_bottom_panel(self.board.clone(), cx),
+            )
     }
 }
 
 impl Render for GpuiDockWorkspace {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         v_flex()
+            .key_context(KEY_CONTEXT)
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::step_backward))
+            .on_action(cx.listener(Self::step_forward))
+            .on_action(cx.listener(Self::go_to_start))
+            .on_action(cx.listener(Self::go_to_end))
+            .on_action(cx.listener(Self::flip_board))
+            .on_action(cx.listener(Self::toggle_right_dock))
+            .on_action(cx.listener(Self::toggle_bottom_dock))
+            .on_action(cx.listener(Self::toggle_analysis))
+            .on_action(cx.listener(Self::cancel))
+            .on_action(cx.listener(Self::clear_drawings))
+            .on_action(cx.listener(Self::focus_move_input))
             .relative()
             .size_full()
             .bg(cx.theme().background)
@@ -474,3 +929,40 @@ impl Render for GpuiDockWorkspace {
             .child(self.dock_area.clone())
     }
 }
+
+fn prompt_open_record(board: Entity<GpuiP1Shell>, cx: &mut App) {
+    let paths = cx.prompt_for_paths(PathPromptOptions {
+        files: true,
+        directories: false,
+        multiple: false,
+        prompt: Some("Open Record".into()),
+    });
+    cx.spawn(async move |cx| {
+        let Ok(Ok(Some(paths))) = paths.await else {
+            return;
+        };
+        let Some(path) = paths.into_iter().next() else {
+            return;
+        };
+        let _ = board.update(cx, |board, cx| {
+            board.open_record(&path);
+            cx.notify();
+        });
+    })
+    .detach();
+}
+
+fn prompt_save_record(board: Entity<GpuiP1Shell>, cx: &mut App) {
+    let directory = std::env::current_dir().unwrap_or_default();
+    let path = cx.prompt_for_new_path(&directory, Some("game.kif"));
+    cx.spawn(async move |cx| {
+        let Ok(Ok(Some(path))) = path.await else {
+            return;
+        };
+        let _ = board.update(cx, |board, cx| {
+            board.save_record(&path);
+            cx.notify();
+        });
+    })
+    .detach();
+}
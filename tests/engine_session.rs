@@ -0,0 +1,152 @@
+//! Drives [`EngineHandle`] against the scripted engine in
+//! `examples/fake_usi_engine.rs`, which `cargo test` builds along with the
+//! tests.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rshogi::engine::bridge::Timeouts;
+use rshogi::engine::{EngineCommand, EngineConfig, EngineEvent, EngineHandle, GoParams};
+
+/// Short limits, so a silent engine fails the handshake quickly.
+const TIMEOUTS: Timeouts = Timeouts {
+    usiok: Duration::from_millis(500),
+    readyok: Duration::from_millis(500),
+    stop: Duration::from_millis(500),
+    quit: Duration::from_millis(500),
+};
+
+/// Longest wait for any event; well above every limit in [`TIMEOUTS`].
+const WAIT: Duration = Duration::from_secs(10);
+
+fn fake_engine_path() -> PathBuf {
+    // Test binaries live in `target/<profile>/deps`, examples next to it.
+    let mut path = std::env::current_exe().expect("test binary has a path");
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push("examples");
+    path.push(format!("fake_usi_engine{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.is_file(),
+        "{} is missing; build it with `cargo build --example fake_usi_engine`",
+        path.display()
+    );
+    path
+}
+
+fn start(args: &[&str]) -> EngineHandle {
+    EngineHandle::start(EngineConfig {
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        timeouts: TIMEOUTS,
+        ..EngineConfig::new(fake_engine_path())
+    })
+}
+
+/// The next event other than `info`, or `None` after [`WAIT`].
+fn next_event(handle: &EngineHandle) -> Option<EngineEvent> {
+    next_event_within(handle, WAIT)
+}
+
+fn next_event_within(handle: &EngineHandle, wait: Duration) -> Option<EngineEvent> {
+    let deadline = Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match handle.recv_timeout(remaining)? {
+            EngineEvent::Info(_) => {}
+            event => return Some(event),
+        }
+    }
+}
+
+fn search(handle: &EngineHandle) {
+    search_from(handle, "position startpos moves 7g7f");
+}
+
+fn search_from(handle: &EngineHandle, position: &str) {
+    handle.send(EngineCommand::Position(position.to_string()));
+    handle.send(EngineCommand::Go(GoParams {
+        movetime: Some(100),
+        ..GoParams::default()
+    }));
+}
+
+#[test]
+fn handshake_reaches_ready_and_searches() {
+    let handle = start(&[]);
+    match next_event(&handle) {
+        Some(EngineEvent::Ready(id)) => {
+            assert_eq!(id.name, "FakeEngine");
+            assert_eq!(id.author, "rshogi");
+            assert_eq!(id.options.len(), 2);
+        }
+        other => panic!("expected Ready, got {other:?}"),
+    }
+
+    handle.send(EngineCommand::NewGame);
+    search(&handle);
+    match next_event(&handle) {
+        Some(EngineEvent::BestMove { mv, .. }) => assert!(!mv.is_empty()),
+        other => panic!("expected BestMove, got {other:?}"),
+    }
+}
+
+#[test]
+fn crash_mid_search_is_reported() {
+    let handle = start(&["--crash-on", "go"]);
+    assert!(matches!(next_event(&handle), Some(EngineEvent::Ready(_))));
+
+    search(&handle);
+    match next_event(&handle) {
+        Some(EngineEvent::Crashed(message)) => {
+            assert!(message.contains("exited unexpectedly"), "{message}");
+        }
+        other => panic!("expected Crashed, got {other:?}"),
+    }
+}
+
+#[test]
+fn silent_usi_hits_the_usiok_timeout() {
+    let started = Instant::now();
+    let handle = start(&["--hang-on", "usi"]);
+    match next_event(&handle) {
+        Some(EngineEvent::Crashed(message)) => assert!(message.contains("usiok"), "{message}"),
+        other => panic!("expected Crashed, got {other:?}"),
+    }
+    assert!(started.elapsed() < WAIT / 2);
+}
+
+#[test]
+fn silent_isready_hits_the_readyok_timeout() {
+    let started = Instant::now();
+    let handle = start(&["--hang-on", "isready"]);
+    match next_event(&handle) {
+        Some(EngineEvent::Crashed(message)) => assert!(message.contains("readyok"), "{message}"),
+        other => panic!("expected Crashed, got {other:?}"),
+    }
+    assert!(started.elapsed() < WAIT / 2);
+}
+
+#[test]
+fn stop_timeout_ends_the_search_and_drops_its_late_bestmove() {
+    let stall = Duration::from_millis(1_500);
+    let handle = start(&["--stall-ms", "1500"]);
+    assert!(matches!(next_event(&handle), Some(EngineEvent::Ready(_))));
+
+    search(&handle);
+    handle.send(EngineCommand::Stop);
+    assert_eq!(next_event(&handle), Some(EngineEvent::StopTimedOut));
+    // The stalled search still ends in a `bestmove`, which nothing waits for.
+    assert_eq!(next_event_within(&handle, stall * 2), None);
+
+    // The next search gets its own reply, not the stale one.
+    search_from(&handle, "position startpos");
+    assert_eq!(
+        next_event(&handle),
+        Some(EngineEvent::BestMove {
+            mv: "1g1f".to_string(),
+            ponder: None,
+        })
+    );
+}